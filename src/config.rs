@@ -0,0 +1,32 @@
+//! the directory with the configuration of the user, like the definitions
+//! of the websites added without changing comic-dl
+
+use std::{env, path::PathBuf};
+
+/// Environment variable that replaces the configuration directory
+pub const CONFIG_ENV: &str = "COMIC_DL_CONFIG";
+
+/// `comic-dl` in the configuration directory of the system, like
+/// `~/.config/comic-dl` on linux, or the directory in `COMIC_DL_CONFIG`
+pub fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os(CONFIG_ENV).filter(|e| !e.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    return dirs::config_dir().map(|e| e.join("comic-dl"));
+}
+
+/// Directory of the site definitions, toml or yaml files
+pub fn sites_dir() -> Option<PathBuf> {
+    return config_dir().map(|e| e.join("sites"));
+}
+
+/// Directory of the plugins, WebAssembly modules
+pub fn plugins_dir() -> Option<PathBuf> {
+    return config_dir().map(|e| e.join("plugins"));
+}
+
+/// File with the canary comics checked by `doctor`, the link of a comic
+/// for each name of a website
+pub fn canaries_file() -> Option<PathBuf> {
+    return config_dir().map(|e| e.join("canaries.toml"));
+}
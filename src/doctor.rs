@@ -0,0 +1,258 @@
+//! health check of the websites: every stage of the strategy runs on a
+//! canary comic and its results are checked, so a change of the layout of a
+//! website is reported with the stage it broke instead of failing downloads
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+};
+
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config,
+    error::SiteDownloaderError,
+    http::HttpClient,
+    image_info::image_dimensions,
+    pages::{page_files, verify_issue},
+    sites::{registry::Site, ComicDownloader, Issue},
+};
+
+/// The steps of a download, checked in this order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Stage {
+    ComicName,
+    IssueList,
+    Metadata,
+    Pages,
+    Search,
+}
+
+impl Stage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::ComicName => return "comic name",
+            Stage::IssueList => return "issue list",
+            Stage::Metadata => return "metadata",
+            Stage::Pages => return "pages",
+            Stage::Search => return "search",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StageStatus {
+    Ok,
+    Failed,
+    /// the stage needs one that failed, or the website can't do it
+    Skipped,
+}
+
+/// The result of a stage, `detail` is what was found or why it failed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StageReport {
+    pub stage: Stage,
+    pub status: StageStatus,
+    pub detail: String,
+}
+
+/// The stages of a website checked on the comic at `url`, none without a
+/// canary comic
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SiteReport {
+    pub site: String,
+    pub url: Option<String>,
+    pub stages: Vec<StageReport>,
+}
+
+/// The result of the check of a website
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SiteStatus {
+    Healthy,
+    Broken,
+    /// the website has no canary comic
+    NotChecked,
+}
+
+impl SiteReport {
+    /// The first stage that failed, the one to report as broken
+    pub fn broken_stage(&self) -> Option<&StageReport> {
+        return self.stages.iter().find(|e| e.status == StageStatus::Failed);
+    }
+
+    pub fn status(&self) -> SiteStatus {
+        if self.url.is_none() {
+            return SiteStatus::NotChecked;
+        }
+        if self.broken_stage().is_some() {
+            return SiteStatus::Broken;
+        }
+        return SiteStatus::Healthy;
+    }
+
+    /// Whether the website was checked and every stage worked
+    pub fn is_healthy(&self) -> bool {
+        return self.status() == SiteStatus::Healthy;
+    }
+}
+
+/// The canary comics in the file at `path`, the link of a comic for each
+/// name of a website, none when the file doesn't exist
+pub fn load_canaries(path: &Path) -> Result<BTreeMap<String, String>, SiteDownloaderError> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(SiteDownloaderError::file_system(path, e)),
+    };
+    return toml::from_str(&text)
+        .map_err(|e| SiteDownloaderError::Canaries { path: path.to_path_buf(), reason: e.to_string() });
+}
+
+/// The canary comics of the user, in [`config::canaries_file`]
+pub fn user_canaries() -> Result<BTreeMap<String, String>, SiteDownloaderError> {
+    match config::canaries_file() {
+        Some(path) => return load_canaries(&path),
+        None => return Ok(BTreeMap::new()),
+    }
+}
+
+/// Checks every stage of `site` on its canary comic, or on `url` when given
+pub fn check_site(client: &HttpClient, site: &Site, url: Option<&str>) -> SiteReport {
+    let Some(url) = url.or(site.canary.as_deref()) else {
+        return SiteReport { site: site.name.clone(), url: None, stages: Vec::new() };
+    };
+    let downloader = site.downloader();
+    let downloader = downloader.as_ref();
+    let mut stages = Vec::new();
+
+    let name = run_stage(&mut stages, Stage::ComicName, || {
+        let name = downloader.get_comic_name(client, url).map_err(|e| e.to_string())?;
+        if name.trim().is_empty() {
+            return Err("the name is empty".to_string());
+        }
+        return Ok((name.clone(), name));
+    });
+
+    let issues = run_stage(&mut stages, Stage::IssueList, || {
+        let issues = downloader.get_issues_list(client, url).map_err(|e| e.to_string())?;
+        check_issues(&issues)?;
+        let detail = format!("{} issues, from {} to {}", issues.len(), issues[0].name, issues[issues.len() - 1].name);
+        return Ok((detail, issues));
+    });
+
+    if site.capabilities.metadata {
+        run_stage(&mut stages, Stage::Metadata, || {
+            let metadata = downloader.get_comic_metadata(client, url).map_err(|e| e.to_string())?;
+            if metadata.title.trim().is_empty() {
+                return Err("the title is empty".to_string());
+            }
+            return Ok((metadata.title, ()));
+        });
+    } else {
+        skip(&mut stages, Stage::Metadata, "the website has no details of the comics");
+    }
+
+    match &issues {
+        Some(issues) => {
+            run_stage(&mut stages, Stage::Pages, || {
+                let detail = check_pages(client, downloader, &site.name, &issues[0])?;
+                return Ok((detail, ()));
+            });
+        }
+        None => skip(&mut stages, Stage::Pages, "needs the issue list"),
+    }
+
+    match (&name, site.capabilities.search) {
+        (_, false) => skip(&mut stages, Stage::Search, "the website can't search"),
+        (None, true) => skip(&mut stages, Stage::Search, "needs the comic name"),
+        (Some(name), true) => {
+            let query = name.replace(['-', '_'], " ");
+            run_stage(&mut stages, Stage::Search, || {
+                let results = downloader.search(client, &query).map_err(|e| e.to_string())?;
+                if results.is_empty() {
+                    return Err(format!("no results for \"{query}\""));
+                }
+                if let Some(result) = results.iter().find(|e| e.title.trim().is_empty() || Url::parse(&e.url).is_err()) {
+                    return Err(format!("a result without title or with an invalid link: {result:?}"));
+                }
+                return Ok((format!("{} results for \"{query}\"", results.len()), ()));
+            });
+        }
+    }
+
+    return SiteReport { site: site.name.clone(), url: Some(url.to_string()), stages };
+}
+
+/// Runs a stage and records its result, a panic of the strategy is a failure
+/// of the stage too
+fn run_stage<T>(stages: &mut Vec<StageReport>, stage: Stage, check: impl FnOnce() -> Result<(String, T), String>) -> Option<T> {
+    let result = panic::catch_unwind(AssertUnwindSafe(check)).unwrap_or_else(|payload| {
+        let message = payload.downcast_ref::<&str>().map(|e| e.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        return Err(format!("the strategy panicked: {message}"));
+    });
+    match result {
+        Ok((detail, value)) => {
+            stages.push(StageReport { stage, status: StageStatus::Ok, detail });
+            return Some(value);
+        }
+        Err(detail) => {
+            stages.push(StageReport { stage, status: StageStatus::Failed, detail });
+            return None;
+        }
+    }
+}
+
+fn skip(stages: &mut Vec<StageReport>, stage: Stage, reason: &str) {
+    stages.push(StageReport { stage, status: StageStatus::Skipped, detail: reason.to_string() });
+}
+
+/// The list must have issues, all with a name and a different link
+fn check_issues(issues: &[Issue]) -> Result<(), String> {
+    if issues.is_empty() {
+        return Err("no issues found".to_string());
+    }
+    for (index, issue) in issues.iter().enumerate() {
+        if issue.name.trim().is_empty() {
+            return Err(format!("the issue with link {} has no name", issue.link));
+        }
+        if !Url::parse(&issue.link).is_ok_and(|e| matches!(e.scheme(), "http" | "https")) {
+            return Err(format!("issue {} has an invalid link: {}", issue.name, issue.link));
+        }
+        if issues[..index].iter().any(|e| e.link == issue.link) {
+            return Err(format!("the link of issue {} is repeated: {}", issue.name, issue.link));
+        }
+    }
+    return Ok(());
+}
+
+/// Downloads `issue` in a temporary directory, every page must be a
+/// complete image
+fn check_pages(client: &HttpClient, downloader: &dyn ComicDownloader, site: &str, issue: &Issue) -> Result<String, String> {
+    let dir = std::env::temp_dir().join(format!("comic-dl-doctor-{}-{}", std::process::id(), site.replace(['/', '\\'], "_")));
+    let _ = fs::remove_dir_all(&dir);
+    let result = download_and_check(client, downloader, &dir, issue);
+    let _ = fs::remove_dir_all(&dir);
+    return result;
+}
+
+fn download_and_check(client: &HttpClient, downloader: &dyn ComicDownloader, dir: &Path, issue: &Issue) -> Result<String, String> {
+    downloader.download_issue(client, dir, issue).map_err(|e| e.to_string())?;
+    let issue_path = dir.join(&issue.name);
+    verify_issue(&issue_path).map_err(|e| e.to_string())?;
+    let pages = page_files(&issue_path).map_err(|e| e.to_string())?;
+    for page in &pages {
+        let bytes = fs::read(page).map_err(|e| e.to_string())?;
+        if image_dimensions(&bytes).is_none() {
+            return Err(format!("{} is not an image", page.file_name().unwrap_or_default().to_string_lossy()));
+        }
+    }
+    return Ok(format!("{} pages of issue {}", pages.len(), issue.name));
+}
@@ -0,0 +1,229 @@
+//! documents of the fixed-layout epub 3 volumes: one xhtml page for every
+//! image, the package with the spine and the navigation document; the kepub
+//! variant adds the markup the Kobo reader expects
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::metadata::{escape_xml, IssueMetadata};
+
+/// Directory of the package inside the archive
+pub const PACKAGE_DIR: &str = "OEBPS";
+
+/// Size of the pages whose size can't be read from the image, the screen of
+/// most 6" e-readers
+const DEFAULT_SIZE: (u32, u32) = (1072, 1448);
+
+pub const CONTAINER_XML: &str = concat!(
+    "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n",
+    "<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n",
+    "  <rootfiles>\n",
+    "    <rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/>\n",
+    "  </rootfiles>\n",
+    "</container>\n",
+);
+
+/// Tells the Kobo and Apple readers that the pages have a fixed layout
+pub const DISPLAY_OPTIONS_XML: &str = concat!(
+    "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n",
+    "<display_options>\n",
+    "  <platform name=\"*\">\n",
+    "    <option name=\"fixed-layout\">true</option>\n",
+    "    <option name=\"open-to-spread\">false</option>\n",
+    "  </platform>\n",
+    "</display_options>\n",
+);
+
+pub const STYLE_CSS: &str = concat!(
+    "html, body { margin: 0; padding: 0; width: 100%; height: 100%; }\n",
+    "img { display: block; width: 100%; height: 100%; object-fit: contain; }\n",
+);
+
+/// An image of the volume, `file_name` is its name in the images directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EpubImage {
+    pub file_name: String,
+    pub media_type: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+impl EpubImage {
+    fn size(&self) -> (u32, u32) {
+        match (self.width, self.height) {
+            (Some(width), Some(height)) => return (width, height),
+            _ => return DEFAULT_SIZE,
+        }
+    }
+}
+
+/// Title of the book: the series with the title or the number of the issue
+pub fn book_title(metadata: &IssueMetadata) -> String {
+    match &metadata.title {
+        Some(title) => return format!("{} - {title}", metadata.series),
+        None => return format!("{} {}", metadata.series, metadata.number),
+    }
+}
+
+/// Name of the xhtml file of the page at `index`, counting from 0
+pub fn page_file_name(index: usize) -> String {
+    return format!("page-{:04}.xhtml", index + 1);
+}
+
+/// The xhtml page showing `image` at its own size, for kepub the image is
+/// in the spans and divs the Kobo reader uses to paginate
+pub fn page_xhtml(metadata: &IssueMetadata, index: usize, image: &EpubImage, kobo: bool) -> String {
+    let (width, height) = image.size();
+    let img = format!("<img src=\"images/{}\" alt=\"page {}\"/>", escape_xml(&image.file_name), index + 1);
+    let content = if kobo {
+        format!(
+            "<div id=\"book-columns\"><div id=\"book-inner\"><span class=\"koboSpan\" id=\"kobo.1.1\">{img}</span></div></div>"
+        )
+    } else {
+        img
+    };
+    return format!(
+        concat!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n",
+            "<!DOCTYPE html>\n",
+            "<html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\">\n",
+            "<head>\n",
+            "  <title>{title} - {page}</title>\n",
+            "  <meta name=\"viewport\" content=\"width={width}, height={height}\"/>\n",
+            "  <link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\"/>\n",
+            "</head>\n",
+            "<body>\n",
+            "  {content}\n",
+            "</body>\n",
+            "</html>\n",
+        ),
+        title = escape_xml(&book_title(metadata)),
+        page = index + 1,
+        width = width,
+        height = height,
+        content = content,
+    );
+}
+
+/// The navigation document, with an entry of the table of contents for
+/// every title and the index of the page it starts from
+pub fn nav_xhtml(metadata: &IssueMetadata, toc: &[(String, usize)]) -> String {
+    let title = escape_xml(&book_title(metadata));
+    let entries: String = toc.iter()
+        .map(|(entry, page)| format!("      <li><a href=\"{}\">{}</a></li>\n", page_file_name(*page), escape_xml(entry)))
+        .collect();
+    return format!(
+        concat!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n",
+            "<!DOCTYPE html>\n",
+            "<html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\">\n",
+            "<head>\n",
+            "  <title>{title}</title>\n",
+            "</head>\n",
+            "<body>\n",
+            "  <nav epub:type=\"toc\" id=\"toc\">\n",
+            "    <ol>\n",
+            "{entries}",
+            "    </ol>\n",
+            "  </nav>\n",
+            "  <nav epub:type=\"landmarks\" hidden=\"\">\n",
+            "    <ol>\n",
+            "      <li><a epub:type=\"cover\" href=\"{first}\">cover</a></li>\n",
+            "      <li><a epub:type=\"bodymatter\" href=\"{first}\">{title}</a></li>\n",
+            "    </ol>\n",
+            "  </nav>\n",
+            "</body>\n",
+            "</html>\n",
+        ),
+        title = title,
+        entries = entries,
+        first = page_file_name(0),
+    );
+}
+
+/// The package document: metadata of the issue, the manifest with every
+/// page and image, the first image as cover and the pages in the spine
+pub fn content_opf(metadata: &IssueMetadata, images: &[EpubImage], modified: SystemTime) -> String {
+    let identifier = match &metadata.source_url {
+        Some(url) => url.clone(),
+        None => format!("comic-dl:{}:{}", metadata.series, metadata.number),
+    };
+    let mut opf = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n",
+        "<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\">\n",
+        "  <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n",
+    ));
+    opf += &format!("    <dc:identifier id=\"book-id\">{}</dc:identifier>\n", escape_xml(&identifier));
+    opf += &format!("    <dc:title>{}</dc:title>\n", escape_xml(&book_title(metadata)));
+    let language = metadata.language.as_deref().unwrap_or("und");
+    opf += &format!("    <dc:language>{}</dc:language>\n", escape_xml(language));
+    let elements = [
+        ("dc:creator", metadata.writer.as_deref()),
+        ("dc:publisher", metadata.publisher.as_deref()),
+        ("dc:description", metadata.summary.as_deref()),
+        ("dc:source", metadata.source_url.as_deref()),
+    ];
+    for (name, value) in elements {
+        if let Some(value) = value.filter(|e| !e.is_empty()) {
+            opf += &format!("    <{name}>{}</{name}>\n", escape_xml(value));
+        }
+    }
+    opf += &format!("    <meta property=\"dcterms:modified\">{}</meta>\n", utc_timestamp(modified));
+    opf += &format!("    <meta property=\"belongs-to-collection\" id=\"series\">{}</meta>\n", escape_xml(&metadata.series));
+    opf += "    <meta refines=\"#series\" property=\"collection-type\">series</meta>\n";
+    if metadata.number.parse::<f64>().is_ok() {
+        opf += &format!("    <meta refines=\"#series\" property=\"group-position\">{}</meta>\n", metadata.number);
+    }
+    opf += "    <meta property=\"rendition:layout\">pre-paginated</meta>\n";
+    opf += "    <meta property=\"rendition:orientation\">auto</meta>\n";
+    opf += "    <meta property=\"rendition:spread\">none</meta>\n";
+    if !images.is_empty() {
+        // for the readers that only know epub 2
+        opf += "    <meta name=\"cover\" content=\"image-0001\"/>\n";
+    }
+    opf += "  </metadata>\n  <manifest>\n";
+    opf += "    <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n";
+    opf += "    <item id=\"style\" href=\"style.css\" media-type=\"text/css\"/>\n";
+    for (index, image) in images.iter().enumerate() {
+        let properties = if index == 0 { " properties=\"cover-image\"" } else { "" };
+        opf += &format!(
+            "    <item id=\"image-{:04}\" href=\"images/{}\" media-type=\"{}\"{properties}/>\n",
+            index + 1,
+            escape_xml(&image.file_name),
+            image.media_type,
+        );
+        opf += &format!(
+            "    <item id=\"page-{:04}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n",
+            index + 1,
+            page_file_name(index),
+        );
+    }
+    opf += "  </manifest>\n  <spine>\n";
+    for index in 0..images.len() {
+        opf += &format!("    <itemref idref=\"page-{:04}\"/>\n", index + 1);
+    }
+    opf += "  </spine>\n</package>\n";
+    return opf;
+}
+
+/// The time in the format of dcterms:modified, like `2024-05-01T10:20:30Z`
+pub fn utc_timestamp(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map(|e| e.as_secs()).unwrap_or_default();
+    let days = (seconds / 86400) as i64;
+    let seconds_of_day = seconds % 86400;
+    // civil date from the days since the epoch, from Howard Hinnant's algorithm
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    return format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60,
+    );
+}
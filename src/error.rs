@@ -0,0 +1,133 @@
+use std::{fmt, io, path::{Path, PathBuf}};
+
+/// Everything that can go wrong while finding, downloading or packing a comic
+#[derive(Debug)]
+pub enum SiteDownloaderError{
+    /// the text given is not a valid link
+    InvalidUrl { url: String },
+    /// the link is valid but no strategy can download from its website,
+    /// `suggestions` are the supported websites with a similar domain
+    UnsupportedWebsite { url: String, suggestions: Vec<String> },
+    /// the website is supported but the link is not to one of its comics
+    NotAComic { url: String, site: String, example: String },
+    /// the request couldn't be sent or the response couldn't be read
+    Request { url: String, source: reqwest::Error },
+    /// the server answered with an unsuccessful status
+    HttpStatus { url: String, status: reqwest::StatusCode },
+    /// the connection was closed before receiving all the body
+    IncompleteBody { url: String, expected: u64, received: u64 },
+    /// the page doesn't have the structure the strategy expects
+    ParsingError { url: String, reason: String },
+    FileSystemError { path: PathBuf, source: io::Error },
+    ZipError { path: PathBuf, source: zip::result::ZipError },
+    ImageError { path: PathBuf, reason: String },
+    /// the directory of the issue has no pages or pages that weren't
+    /// completely downloaded, so it can't be packed
+    IncompleteIssue { path: PathBuf, pages: Vec<String> },
+    /// a site definition of the user can't be read or is not valid
+    SiteDefinition { path: PathBuf, reason: String },
+    /// the canary comics of the user can't be read or are not valid
+    Canaries { path: PathBuf, reason: String },
+    /// a plugin can't be loaded, or failed or broke the interface in a call
+    Plugin { path: PathBuf, reason: String },
+    /// comic-dl was compiled without a feature that is needed
+    FeatureDisabled { feature: &'static str },
+    /// a single page of an issue failed
+    Page { issue: String, page_number: u32, source: Box<SiteDownloaderError> },
+    /// an issue failed, either while downloading or while creating the volume
+    Issue { issue: String, source: Box<SiteDownloaderError> },
+    /// the download went on but these issues failed
+    IncompleteDownload { failed: Vec<String> },
+}
+
+impl SiteDownloaderError {
+    pub(crate) fn parsing(url: &str, reason: impl Into<String>) -> SiteDownloaderError {
+        return SiteDownloaderError::ParsingError { url: url.to_string(), reason: reason.into() };
+    }
+
+    pub(crate) fn file_system(path: &Path, source: io::Error) -> SiteDownloaderError {
+        return SiteDownloaderError::FileSystemError { path: path.to_path_buf(), source };
+    }
+
+    pub(crate) fn zip(path: &Path, source: zip::result::ZipError) -> SiteDownloaderError {
+        return SiteDownloaderError::ZipError { path: path.to_path_buf(), source };
+    }
+
+    pub(crate) fn image(path: &Path, reason: impl ToString) -> SiteDownloaderError {
+        return SiteDownloaderError::ImageError { path: path.to_path_buf(), reason: reason.to_string() };
+    }
+
+    /// Adds the page of the issue where the error happened
+    pub fn in_page(self, issue: &str, page_number: u32) -> SiteDownloaderError {
+        return SiteDownloaderError::Page { issue: issue.to_string(), page_number, source: Box::new(self) };
+    }
+
+    /// Adds the issue where the error happened
+    pub fn in_issue(self, issue: &str) -> SiteDownloaderError {
+        return SiteDownloaderError::Issue { issue: issue.to_string(), source: Box::new(self) };
+    }
+}
+
+impl fmt::Display for SiteDownloaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SiteDownloaderError::InvalidUrl { url } =>
+                write!(f, "{url} is not a valid link"),
+            SiteDownloaderError::UnsupportedWebsite { url, suggestions } => {
+                write!(f, "unsupported site: the website of {url} is not supported")?;
+                match suggestions.as_slice() {
+                    [] => write!(f, ", see --list-websites"),
+                    [site] => write!(f, ", did you mean {site}?"),
+                    [others @ .., last] => write!(f, ", did you mean {} or {last}?", others.join(", ")),
+                }
+            },
+            SiteDownloaderError::NotAComic { url, site, example } =>
+                write!(f, "{url} is not a link to a comic of {site}, it should be like {example}"),
+            SiteDownloaderError::Request { url, source } =>
+                write!(f, "request to {url} failed: {source}"),
+            SiteDownloaderError::HttpStatus { url, status } =>
+                write!(f, "{url} answered with status {status}"),
+            SiteDownloaderError::IncompleteBody { url, expected, received } =>
+                write!(f, "{url} sent {received} bytes out of {expected}"),
+            SiteDownloaderError::ParsingError { url, reason } =>
+                write!(f, "couldn't parse {url}: {reason}"),
+            SiteDownloaderError::FileSystemError { path, source } =>
+                write!(f, "{}: {source}", path.display()),
+            SiteDownloaderError::ZipError { path, source } =>
+                write!(f, "couldn't write {}: {source}", path.display()),
+            SiteDownloaderError::ImageError { path, reason } =>
+                write!(f, "image {}: {reason}", path.display()),
+            SiteDownloaderError::IncompleteIssue { path, pages } if pages.is_empty() =>
+                write!(f, "{} has no pages", path.display()),
+            SiteDownloaderError::IncompleteIssue { path, pages } =>
+                write!(f, "{} has pages not completely downloaded: {}", path.display(), pages.join(", ")),
+            SiteDownloaderError::SiteDefinition { path, reason } =>
+                write!(f, "site definition {}: {reason}", path.display()),
+            SiteDownloaderError::Canaries { path, reason } =>
+                write!(f, "canary comics {}: {reason}", path.display()),
+            SiteDownloaderError::Plugin { path, reason } =>
+                write!(f, "plugin {}: {reason}", path.display()),
+            SiteDownloaderError::FeatureDisabled { feature } =>
+                write!(f, "the {feature} feature is not enabled"),
+            SiteDownloaderError::Page { issue, page_number, source } =>
+                write!(f, "page {page_number} of issue {issue}: {source}"),
+            SiteDownloaderError::Issue { issue, source } =>
+                write!(f, "issue {issue}: {source}"),
+            SiteDownloaderError::IncompleteDownload { failed } =>
+                write!(f, "{} issues couldn't be downloaded: {}", failed.len(), failed.join(", ")),
+        }
+    }
+}
+
+impl std::error::Error for SiteDownloaderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SiteDownloaderError::Request { source, .. } => Some(source),
+            SiteDownloaderError::FileSystemError { source, .. } => Some(source),
+            SiteDownloaderError::ZipError { source, .. } => Some(source),
+            SiteDownloaderError::Page { source, .. } => Some(source.as_ref()),
+            SiteDownloaderError::Issue { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
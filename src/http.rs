@@ -0,0 +1,346 @@
+//! the http layer shared by every strategy: all the requests to the
+//! websites go through [`HttpClient`], that spaces them out per host and
+//! retries the ones that fail for reasons that are likely temporary
+
+use std::{
+    collections::HashMap,
+    fmt,
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use rand::Rng;
+use reqwest::{blocking::{Client, ClientBuilder, Response}, header::{CONTENT_TYPE, RETRY_AFTER}, StatusCode};
+
+use crate::error::SiteDownloaderError;
+
+/// How many times and how long to wait before repeating a failed request
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// retries after the first attempt, 0 disables retrying
+    pub retries: u32,
+    /// wait before the first retry, doubled at every following one
+    pub initial_backoff: Duration,
+    /// upper bound of the exponential backoff
+    pub max_backoff: Duration,
+    /// fraction of the backoff, between 0 and 1, that is randomly removed
+    /// so the threads don't retry all at the same time
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        return RetryPolicy {
+            retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            jitter: 0.5,
+        };
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries
+    pub fn none() -> RetryPolicy {
+        return RetryPolicy { retries: 0, ..RetryPolicy::default() };
+    }
+
+    /// Wait before the retry number `retry`, starting from 1
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exponential = self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(self.max_backoff);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return exponential;
+        }
+        let kept = 1.0 - rand::thread_rng().gen_range(0.0..=jitter);
+        return exponential.mul_f64(kept);
+    }
+}
+
+/// How fast the requests to a single host can be sent
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimit {
+    /// average requests per second, 0 means no limit
+    pub requests_per_second: f64,
+    /// requests that can be sent at once after a pause
+    pub burst: u32,
+    /// minimum time between the start of two requests
+    pub min_delay: Duration,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        return RateLimit {
+            requests_per_second: 4.0,
+            burst: 4,
+            min_delay: Duration::ZERO,
+        };
+    }
+}
+
+impl RateLimit {
+    /// No limit at all
+    pub fn unlimited() -> RateLimit {
+        return RateLimit { requests_per_second: 0.0, burst: 1, min_delay: Duration::ZERO };
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+    last_start: Option<Instant>,
+}
+
+/// Token bucket per host, shared by every clone of the [`HttpClient`] so
+/// all the threads of a download respect the same limits
+#[derive(Debug, Default)]
+struct RateLimiter {
+    state: Mutex<LimiterState>,
+}
+
+#[derive(Debug, Default)]
+struct LimiterState {
+    default_limit: RateLimit,
+    host_limits: HashMap<String, RateLimit>,
+    buckets: HashMap<String, Bucket>,
+}
+
+impl LimiterState {
+    fn limit_for(&self, host: &str) -> &RateLimit {
+        return self.host_limits.get(host).unwrap_or(&self.default_limit);
+    }
+}
+
+impl RateLimiter {
+    /// Reserves the next slot for `host` and returns how long to wait for it
+    fn reserve(&self, host: &str) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let limit = state.limit_for(host).clone();
+        let now = Instant::now();
+        let burst = limit.burst.max(1) as f64;
+        let bucket = state.buckets.entry(host.to_string()).or_insert(Bucket {
+            tokens: burst,
+            last_refill: now,
+            last_start: None,
+        });
+
+        let mut start = now;
+        if limit.requests_per_second > 0.0 {
+            let refilled = now.duration_since(bucket.last_refill).as_secs_f64() * limit.requests_per_second;
+            bucket.tokens = (bucket.tokens + refilled).min(burst) - 1.0;
+            bucket.last_refill = now;
+            if bucket.tokens < 0.0 {
+                start += Duration::from_secs_f64(-bucket.tokens / limit.requests_per_second);
+            }
+        }
+        if let Some(last_start) = bucket.last_start {
+            start = start.max(last_start + limit.min_delay);
+        }
+        bucket.last_start = Some(start);
+        return start.duration_since(now);
+    }
+}
+
+/// How the reqwest client is built, kept to build it again with another
+/// redirect policy
+#[derive(Clone)]
+struct ClientSettings(Arc<dyn Fn() -> ClientBuilder + Send + Sync>);
+
+impl Default for ClientSettings {
+    fn default() -> Self {
+        return ClientSettings(Arc::new(Client::builder));
+    }
+}
+
+impl fmt::Debug for ClientSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.write_str("ClientSettings");
+    }
+}
+
+/// Blocking http client used by the strategies
+#[derive(Debug, Clone, Default)]
+pub struct HttpClient {
+    client: Client,
+    settings: ClientSettings,
+    retry: RetryPolicy,
+    limiter: Arc<RateLimiter>,
+}
+
+impl HttpClient {
+    pub fn new() -> HttpClient {
+        return HttpClient::default();
+    }
+
+    /// A client with the settings of `builder`, like timeouts, proxies and
+    /// headers, that is called again when the redirect policy changes
+    pub fn with_builder(builder: impl Fn() -> ClientBuilder + Send + Sync + 'static) -> Result<HttpClient, reqwest::Error> {
+        let client = builder().build()?;
+        return Ok(HttpClient { client, settings: ClientSettings(Arc::new(builder)), ..HttpClient::default() });
+    }
+
+    pub fn change_retry_policy(&mut self, retry: RetryPolicy) {
+        self.retry = retry;
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        return &self.retry;
+    }
+
+    /// Changes the limit of the hosts without a limit of their own, also
+    /// for the clones of this client
+    pub fn change_default_rate_limit(&mut self, limit: RateLimit) {
+        self.limiter.state.lock().unwrap().default_limit = limit;
+    }
+
+    /// Changes the limit of a single host, like `scanita.org`
+    pub fn change_host_rate_limit(&mut self, host: &str, limit: RateLimit) {
+        self.limiter.state.lock().unwrap().host_limits.insert(host.to_string(), limit);
+    }
+
+    pub fn rate_limit(&self, host: &str) -> RateLimit {
+        return self.limiter.state.lock().unwrap().limit_for(host).clone();
+    }
+
+    /// The same client, sharing the settings, the retries and the rate limits,
+    /// that follows the redirects with `policy`
+    pub fn with_redirect_policy(&self, policy: reqwest::redirect::Policy) -> Result<HttpClient, reqwest::Error> {
+        let client = (self.settings.0)().redirect(policy).build()?;
+        return Ok(HttpClient { client, ..self.clone() });
+    }
+
+    /// This client with the retries and the rate limits of `other`
+    pub fn with_limits_of(&self, other: &HttpClient) -> HttpClient {
+        return HttpClient { retry: other.retry.clone(), limiter: other.limiter.clone(), ..self.clone() };
+    }
+
+    /// The underlying reqwest client, requests made with it are not retried
+    pub fn inner(&self) -> &Client {
+        return &self.client;
+    }
+
+    /// Sends a GET request to `url` and returns the response whatever its
+    /// status, unless it stayed 429 or 5xx after all the retries
+    pub fn send(&self, url: &str) -> Result<Response, SiteDownloaderError> {
+        return self.execute(url, Ok);
+    }
+
+    /// Sends a GET request to `url`, an unsuccessful status is an error
+    pub fn get(&self, url: &str) -> Result<Response, SiteDownloaderError> {
+        return self.execute(url, |response| require_success(url, response));
+    }
+
+    /// Body of the page at `url` as text
+    pub fn get_text(&self, url: &str) -> Result<String, SiteDownloaderError> {
+        return self.execute(url, |response| {
+            require_success(url, response)?.text()
+                .map_err(|source| SiteDownloaderError::Request { url: url.to_string(), source })
+        });
+    }
+
+    /// Body of the response from `url` as bytes
+    pub fn get_bytes(&self, url: &str) -> Result<Vec<u8>, SiteDownloaderError> {
+        return self.get_bytes_with_type(url).map(|(content, _)| content);
+    }
+
+    /// Body of the response from `url` as bytes with its `Content-Type`
+    pub fn get_bytes_with_type(&self, url: &str) -> Result<(Vec<u8>, Option<String>), SiteDownloaderError> {
+        return self.execute(url, |response| {
+            let response = require_success(url, response)?;
+            let content_type = response.headers().get(CONTENT_TYPE)
+                .and_then(|e| e.to_str().ok())
+                .map(|e| e.to_string());
+            let expected = response.content_length();
+            let content = response.bytes()
+                .map_err(|source| SiteDownloaderError::Request { url: url.to_string(), source })?;
+            let received = content.len() as u64;
+            if expected.is_some_and(|expected| expected != received) {
+                return Err(SiteDownloaderError::IncompleteBody { url: url.to_string(), expected: expected.unwrap_or_default(), received });
+            }
+            Ok((content.to_vec(), content_type))
+        });
+    }
+
+    /// Downloads `url` into the file at `path`
+    pub fn save_to_file(&self, url: &str, path: &Path) -> Result<(), SiteDownloaderError> {
+        let content = self.get_bytes(url)?;
+        fs::write(path, &content).map_err(|e| SiteDownloaderError::file_system(path, e))?;
+        return Ok(());
+    }
+
+    /// Sends the request until `read` accepts the response or the error is
+    /// not worth retrying, waiting as the policy or the server asks between attempts
+    fn execute<T>(
+        &self,
+        url: &str,
+        read: impl Fn(Response) -> Result<T, SiteDownloaderError>,
+    ) -> Result<T, SiteDownloaderError> {
+        let host = reqwest::Url::parse(url).ok()
+            .and_then(|e| e.host_str().map(|host| match e.port() {
+                Some(port) => format!("{host}:{port}"),
+                None => host.to_string(),
+            }))
+            .unwrap_or_default();
+        let mut retry = 0;
+        loop {
+            thread::sleep(self.limiter.reserve(&host));
+            let mut retry_after = None;
+            let result = match self.client.get(url).send() {
+                Err(source) => Err(SiteDownloaderError::Request { url: url.to_string(), source }),
+                Ok(response) if is_retryable_status(response.status()) => {
+                    retry_after = parse_retry_after(&response);
+                    Err(SiteDownloaderError::HttpStatus { url: url.to_string(), status: response.status() })
+                },
+                Ok(response) => read(response),
+            };
+            match result {
+                Err(e) if retry < self.retry.retries && is_retryable(&e) => {
+                    retry += 1;
+                    let wait = retry_after
+                        .map(|e| e.min(self.retry.max_backoff))
+                        .unwrap_or_else(|| self.retry.backoff(retry));
+                    eprintln!("{e}, retry {retry} of {} in {:.1}s", self.retry.retries, wait.as_secs_f32());
+                    thread::sleep(wait);
+                },
+                other => return other,
+            }
+        }
+    }
+}
+
+fn require_success(url: &str, response: Response) -> Result<Response, SiteDownloaderError> {
+    if !response.status().is_success() {
+        return Err(SiteDownloaderError::HttpStatus { url: url.to_string(), status: response.status() });
+    }
+    return Ok(response);
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    return status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+}
+
+fn is_retryable(error: &SiteDownloaderError) -> bool {
+    match error {
+        SiteDownloaderError::Request { source, .. } =>
+            return source.is_connect() || source.is_timeout() || source.is_request() || source.is_body(),
+        SiteDownloaderError::HttpStatus { status, .. } => return is_retryable_status(*status),
+        SiteDownloaderError::IncompleteBody { .. } => return true,
+        _ => return false,
+    }
+}
+
+/// The `Retry-After` header, either in seconds or as an http date
+fn parse_retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    return Some(date.duration_since(SystemTime::now()).unwrap_or(Duration::ZERO));
+}
@@ -0,0 +1,195 @@
+//! format and size of the pages read from the first bytes of the file,
+//! without decoding them and without the image crate, that is not in the
+//! builds without the pdf feature
+
+/// Formats of images found on the websites
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageKind {
+    Jpeg,
+    Png,
+    WebP,
+    Gif,
+    Avif,
+}
+
+impl ImageKind {
+    pub const ALL: [ImageKind; 5] = [ImageKind::Jpeg, ImageKind::Png, ImageKind::WebP, ImageKind::Gif, ImageKind::Avif];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageKind::Jpeg => "jpg",
+            ImageKind::Png => "png",
+            ImageKind::WebP => "webp",
+            ImageKind::Gif => "gif",
+            ImageKind::Avif => "avif",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            ImageKind::Jpeg => "image/jpeg",
+            ImageKind::Png => "image/png",
+            ImageKind::WebP => "image/webp",
+            ImageKind::Gif => "image/gif",
+            ImageKind::Avif => "image/avif",
+        }
+    }
+}
+
+/// The format of the image from its magic bytes
+pub fn image_kind(bytes: &[u8]) -> Option<ImageKind> {
+    if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        return Some(ImageKind::Jpeg);
+    }
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some(ImageKind::Png);
+    }
+    if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        return Some(ImageKind::WebP);
+    }
+    if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        return Some(ImageKind::Gif);
+    }
+    if bytes.len() >= 12 && &bytes[4..8] == b"ftyp" && (&bytes[8..12] == b"avif" || &bytes[8..12] == b"avis") {
+        return Some(ImageKind::Avif);
+    }
+    return None;
+}
+
+/// The format of the image from its `Content-Type`, like `image/webp`
+pub fn kind_from_mime_type(content_type: &str) -> Option<ImageKind> {
+    let mime_type = content_type.split(';').next().unwrap_or_default().trim().to_lowercase();
+    match mime_type.as_str() {
+        "image/jpeg" | "image/jpg" | "image/pjpeg" => return Some(ImageKind::Jpeg),
+        "image/png" => return Some(ImageKind::Png),
+        "image/webp" => return Some(ImageKind::WebP),
+        "image/gif" => return Some(ImageKind::Gif),
+        "image/avif" => return Some(ImageKind::Avif),
+        _ => return None,
+    }
+}
+
+/// The format of a downloaded image: the magic bytes, or the
+/// `Content-Type` when they are not known
+pub fn detect_kind(bytes: &[u8], content_type: Option<&str>) -> Option<ImageKind> {
+    return image_kind(bytes).or_else(|| content_type.and_then(kind_from_mime_type));
+}
+
+/// Width and height of the image, `None` if the format is unknown or the
+/// header is broken
+pub fn image_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    match image_kind(bytes)? {
+        ImageKind::Jpeg => return jpeg_dimensions(bytes),
+        ImageKind::Png => {
+            return Some((read_u32_be(bytes, 16)?, read_u32_be(bytes, 20)?));
+        },
+        ImageKind::Gif => {
+            return Some((read_u16_le(bytes, 6)? as u32, read_u16_le(bytes, 8)? as u32));
+        },
+        ImageKind::WebP => return webp_dimensions(bytes),
+        ImageKind::Avif => return None,
+    }
+}
+
+fn jpeg_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    return jpeg_frame(bytes).map(|frame| (frame.width, frame.height));
+}
+
+/// The start of frame of a jpeg, what a pdf needs to embed it as it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JpegFrame {
+    pub width: u32,
+    pub height: u32,
+    /// 1 for grayscale, 3 for color, 4 for cmyk
+    pub components: u8,
+    /// saved by Adobe software, that writes cmyk inverted
+    pub adobe: bool,
+    /// bits of every sample, 8 in almost every jpeg
+    pub precision: u8,
+    /// the start of frame marker, from 0xC0 to 0xCF
+    pub marker: u8,
+}
+
+impl JpegFrame {
+    /// Whether a pdf reader can show the jpeg as it is: 8 bit, baseline or
+    /// progressive with Huffman coding; the lossless, 12 bit and arithmetic
+    /// coded ones must be decoded
+    pub fn is_embeddable(&self) -> bool {
+        return self.precision == 8 && matches!(self.marker, 0xC0..=0xC2);
+    }
+}
+
+/// Reads the segments of the jpeg until the start of frame
+pub fn jpeg_frame(bytes: &[u8]) -> Option<JpegFrame> {
+    if image_kind(bytes)? != ImageKind::Jpeg {
+        return None;
+    }
+    let mut adobe = false;
+    let mut position = 2;
+    loop {
+        while *bytes.get(position)? == 0xFF && *bytes.get(position + 1)? == 0xFF {
+            position += 1;
+        }
+        if *bytes.get(position)? != 0xFF {
+            return None;
+        }
+        let marker = *bytes.get(position + 1)?;
+        let length = read_u16_be(bytes, position + 2)? as usize;
+        if marker == 0xEE && bytes.get(position + 4..position + 9) == Some(b"Adobe") {
+            adobe = true;
+        }
+        // start of frame markers, without the ones that are not frames
+        if (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker) {
+            return Some(JpegFrame {
+                height: read_u16_be(bytes, position + 5)? as u32,
+                width: read_u16_be(bytes, position + 7)? as u32,
+                components: *bytes.get(position + 9)?,
+                adobe,
+                precision: *bytes.get(position + 4)?,
+                marker,
+            });
+        }
+        position += 2 + length;
+    }
+}
+
+fn webp_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    match bytes.get(12..16)? {
+        b"VP8 " => {
+            let width = read_u16_le(bytes, 26)? & 0x3FFF;
+            let height = read_u16_le(bytes, 28)? & 0x3FFF;
+            return Some((width as u32, height as u32));
+        },
+        b"VP8L" => {
+            let bits = read_u32_le(bytes, 21)?;
+            return Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1));
+        },
+        b"VP8X" => {
+            let width = read_u24_le(bytes, 24)? + 1;
+            let height = read_u24_le(bytes, 27)? + 1;
+            return Some((width, height));
+        },
+        _ => return None,
+    }
+}
+
+fn read_u16_be(bytes: &[u8], at: usize) -> Option<u16> {
+    return Some(u16::from_be_bytes(bytes.get(at..at + 2)?.try_into().ok()?));
+}
+
+fn read_u16_le(bytes: &[u8], at: usize) -> Option<u16> {
+    return Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?));
+}
+
+fn read_u24_le(bytes: &[u8], at: usize) -> Option<u32> {
+    let b = bytes.get(at..at + 3)?;
+    return Some(b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16);
+}
+
+fn read_u32_be(bytes: &[u8], at: usize) -> Option<u32> {
+    return Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+}
+
+fn read_u32_le(bytes: &[u8], at: usize) -> Option<u32> {
+    return Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+}
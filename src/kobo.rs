@@ -0,0 +1,63 @@
+//! generation of the scripts to run comic-dl directly on a kobo e-reader
+
+use std::{error::Error, fs::{self, File}, io::Write, path::Path};
+
+use crate::sites::ComicUrl;
+
+const KOBO_VERSION_LINK: &str = "https://github.com/leonardoCorti/comic-dl/releases/download/v0.5.0/comic-dl-armv7-linux";
+
+/// Creates the `install` directory with the script for `url`, the
+/// `download_all.sh` and `update.sh` scripts and the armv7 build of comic-dl
+pub fn generate_install(url: &str) -> Result<(), Box<dyn Error>>{
+    let installation_path = Path::new("install");
+    if !installation_path.exists(){
+        fs::create_dir(installation_path)?;
+    }
+    let script = format!(
+r#"#!/bin/sh
+cd "$(dirname "$0")"
+./comic-dl-armv7-linux {url}"#);
+    let script = script.replace("\\", "/");
+    let comic_dw = ComicUrl::new(url)?;
+    let comic_name = &comic_dw.comic_name;
+
+    let mut script_file = File::create(installation_path.join(format!("{comic_name}.sh")))?;
+    script_file.write_all(script.as_bytes())?;
+
+    let list_of_file: Vec<String> = installation_path.read_dir()?
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.file_name().to_str().map(|s| s.to_string()))
+        .filter(|e| e.ends_with("sh") && e != "update.sh" && e != "download_all.sh")
+        .collect();
+
+    if list_of_file.len() > 1 {
+        let scripts: String = list_of_file.iter().fold("#!/bin/sh \ncd \"$(dirname \"$0\")\"\n".to_string(), |a,b| a + "./" + b + "\n");
+        let mut download_all = File::create(installation_path.join("download_all.sh"))?;
+        download_all.write_all(scripts.as_bytes())?;
+    }
+
+    let update_script = include_str!("./assets/update.sh");
+    let mut update_script_file = File::create(installation_path.join("update.sh"))?;
+    update_script_file.write_all(update_script.as_bytes())?;
+
+    match reqwest::blocking::Client::new().get(KOBO_VERSION_LINK).send(){
+        Ok(program_download) => {
+            let progam = program_download.bytes()?;
+            if progam.len() >= 4 && is_elf(&progam[..4].try_into()?) {
+                let mut program_file = File::create(installation_path.join("comic-dl-armv7-linux"))?;
+                program_file.write_all(&progam)?;
+            } else {
+                println!("couldn't download the kobo version of comic-dl, donwload it manually");
+            }
+        }
+        Err(_) => {
+            println!("couldn't download the kobo version of comic-dl, donwload it manually");
+        } ,
+    };
+    return Ok(());
+}
+
+fn is_elf(first_byes: &[u8;4]) -> bool {
+    let magic_number = [0x7F, b'E', b'L', b'F'];
+    return *first_byes == magic_number;
+}
@@ -0,0 +1,41 @@
+//! comic-dl as a library: detect the website of a comic, list its issues,
+//! download them and pack them into volumes (cbz, epub or pdf).
+//!
+//! ```no_run
+//! use comic_dl::{ComicUrl, OutputFormats};
+//!
+//! let mut comic = ComicUrl::new("https://readcomic.me/comic/some-comic").unwrap();
+//! comic.change_format(OutputFormats::Cbz);
+//! comic.download_all().unwrap();
+//! ```
+#![allow(clippy::needless_return)]
+
+pub mod config;
+pub mod doctor;
+pub mod epub;
+mod error;
+pub mod http;
+pub mod image_info;
+pub mod kobo;
+pub mod library;
+pub mod metadata;
+pub mod omnibus;
+pub mod pages;
+pub mod pdf;
+pub mod pipeline;
+pub mod search;
+pub mod selection;
+pub mod sites;
+pub mod state;
+pub mod volume;
+
+pub use http::{HttpClient, RateLimit, RetryPolicy};
+pub use sites::{
+    identify_website,
+    print_supported_websites,
+    ComicDownloader,
+    ComicUrl,
+    Issue,
+    OutputFormats,
+    SiteDownloaderError,
+};
@@ -0,0 +1,175 @@
+//! the comics followed by the user, kept in a manifest so they can all be
+//! updated at once
+
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::SiteDownloaderError,
+    pdf::PageSize,
+    pipeline::ImagePipeline,
+    selection::Selection,
+    sites::{ComicUrl, OutputFormats},
+};
+
+/// Name of the manifest in the directory of the library
+pub const LIBRARY_FILE: &str = "comic-dl-library.json";
+
+/// A comic in the library
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FollowedComic {
+    pub url: String,
+    pub name: String,
+    /// download directory, relative paths start from the directory of the library
+    pub path: PathBuf,
+    pub format: OutputFormats,
+    /// processing of the pages, missing when they are packed as downloaded
+    #[serde(default, skip_serializing_if = "ImagePipeline::is_noop")]
+    pub pipeline: ImagePipeline,
+    /// size of the pages of the pdf volumes
+    #[serde(default, skip_serializing_if = "is_default_page_size")]
+    pub page_size: PageSize,
+    /// issues in each omnibus volume, missing when every issue is a volume
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub omnibus: Option<usize>,
+    /// expressions choosing the issues to download
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub selection: Vec<Selection>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Library {
+    pub comics: Vec<FollowedComic>,
+}
+
+/// What happened to a comic during an update
+#[derive(Debug)]
+pub struct ComicUpdate {
+    pub name: String,
+    pub downloaded: Vec<String>,
+    pub failed: Vec<String>,
+    /// the comic couldn't be updated at all, like when the website is down
+    pub error: Option<SiteDownloaderError>,
+}
+
+impl Library {
+    /// Reads the library in `library_path`, empty if there isn't one yet
+    pub fn load(library_path: &Path) -> Result<Library, SiteDownloaderError> {
+        let path = library_path.join(LIBRARY_FILE);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Library::default()),
+            Err(e) => return Err(SiteDownloaderError::file_system(&path, e)),
+        };
+        return serde_json::from_str(&content)
+            .map_err(|e| SiteDownloaderError::file_system(&path, io::Error::new(io::ErrorKind::InvalidData, e)));
+    }
+
+    pub fn save(&self, library_path: &Path) -> Result<(), SiteDownloaderError> {
+        let path = library_path.join(LIBRARY_FILE);
+        let partial_path = library_path.join(format!("{LIBRARY_FILE}.part"));
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| SiteDownloaderError::file_system(&path, io::Error::new(io::ErrorKind::InvalidData, e)))?;
+        fs::write(&partial_path, content).map_err(|e| SiteDownloaderError::file_system(&partial_path, e))?;
+        fs::rename(&partial_path, &path).map_err(|e| SiteDownloaderError::file_system(&path, e))?;
+        return Ok(());
+    }
+
+    /// Adds the comic, or updates its path and format if it was already
+    /// followed; returns whether it's new
+    pub fn follow(&mut self, comic: FollowedComic) -> bool {
+        if let Some(followed) = self.comics.iter_mut().find(|e| e.url == comic.url) {
+            *followed = comic;
+            return false;
+        }
+        self.comics.push(comic);
+        return true;
+    }
+
+    /// Removes the comic with link `url`, returns whether it was followed
+    pub fn unfollow(&mut self, url: &str) -> bool {
+        let before = self.comics.len();
+        self.comics.retain(|e| e.url != url);
+        return self.comics.len() != before;
+    }
+
+    /// Downloads the new issues of every comic with up to `jobs_quantity`
+    /// threads, `configure` can change the settings of each comic before its
+    /// download, a comic that fails doesn't stop the others
+    pub fn update(
+        &self,
+        library_path: &Path,
+        jobs_quantity: usize,
+        configure: impl Fn(&mut ComicUrl),
+    ) -> Vec<ComicUpdate> {
+        return self.comics.iter()
+            .map(|followed| {
+                println!("updating {}", followed.name);
+                match followed.open(library_path) {
+                    Ok(mut comic) => {
+                        configure(&mut comic);
+                        update_comic(&followed.name, &comic, jobs_quantity)
+                    },
+                    Err(error) => ComicUpdate { name: followed.name.clone(), downloaded: vec![], failed: vec![], error: Some(error) },
+                }
+            })
+            .collect();
+    }
+}
+
+impl FollowedComic {
+    pub fn new(comic: &ComicUrl) -> FollowedComic {
+        return FollowedComic {
+            url: comic.url.clone(),
+            name: comic.comic_name.clone(),
+            path: comic.download_path.clone(),
+            format: comic.format,
+            pipeline: comic.pipeline,
+            page_size: comic.page_size,
+            omnibus: comic.omnibus,
+            selection: comic.selection.clone(),
+        };
+    }
+
+    /// The comic ready to be downloaded with the settings it was followed with
+    pub fn open(&self, library_path: &Path) -> Result<ComicUrl, SiteDownloaderError> {
+        let mut comic = ComicUrl::new(&self.url)?;
+        let download_path = library_path.join(&self.path);
+        fs::create_dir_all(&download_path).map_err(|e| SiteDownloaderError::file_system(&download_path, e))?;
+        comic.change_path(&download_path.to_string_lossy())?;
+        comic.change_format(self.format);
+        comic.change_pipeline(self.pipeline);
+        comic.change_page_size(self.page_size);
+        comic.change_omnibus(self.omnibus);
+        comic.change_selection(self.selection.clone());
+        return Ok(comic);
+    }
+}
+
+fn is_default_page_size(page_size: &PageSize) -> bool {
+    return *page_size == PageSize::default();
+}
+
+fn update_comic(name: &str, comic: &ComicUrl, jobs_quantity: usize) -> ComicUpdate {
+    let mut update = ComicUpdate { name: name.to_string(), downloaded: vec![], failed: vec![], error: None };
+    let results = match comic.download_new(jobs_quantity) {
+        Ok(results) => results,
+        Err(error) => {
+            eprintln!("{error}");
+            update.error = Some(error);
+            return update;
+        },
+    };
+    for (name, result) in results {
+        match result {
+            Ok(()) => update.downloaded.push(name),
+            Err(_) => update.failed.push(name),
+        }
+    }
+    return update;
+}
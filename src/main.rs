@@ -1,27 +1,155 @@
-use std::collections::VecDeque;
-use std::env::args;
+#![allow(clippy::needless_return)]
 use std::error::Error;
-use std::fs::File;
-use std::sync::Arc;
-use std::{fs, thread};
-use std::io::Write;
-use clap::Parser;
-
-use sites::ComicUrl;
-
-mod sites;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use clap::{Parser, Subcommand, ValueEnum};
+
+use comic_dl::{
+    config,
+    doctor::{self, SiteStatus, StageStatus},
+    kobo,
+    library::{FollowedComic, Library},
+    pdf::PageSize,
+    pipeline::{AutoCrop, DeviceProfile, ImagePipeline, SpreadMode},
+    search,
+    selection::Selection,
+    sites::registry::{Site, SiteRegistry},
+    ComicUrl,
+    HttpClient,
+    OutputFormats,
+    RateLimit,
+    RetryPolicy,
+};
 
 #[derive(Parser, Debug)]
-#[command(version, about)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
 struct Args {
-    /// Number of threads to use for dowloading
-    #[arg(short = 'J', long, default_value = "1")]
-    threads: usize,
+    #[command(subcommand)]
+    command: Option<Command>,
 
     /// list supported websites
     #[arg(long)]
     list_websites: bool,
 
+    /// Directory with the list of the followed comics
+    #[arg(long, global = true, value_name = "DIR", default_value = ".")]
+    library: PathBuf,
+
+    #[command(flatten)]
+    download: DownloadOptions,
+
+    #[command(flatten)]
+    network: NetworkOptions,
+
+    /// Install to Kobo after download
+    #[arg(long)]
+    kobo_install: bool,
+
+    /// The link to the comic
+    comic_link: Option<String>,
+
+    /// interactive mode (todo!)
+    #[arg(short= 'I', long)]
+    interactive: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Add a comic to the library, its new issues are downloaded by update
+    Follow {
+        /// The link to the comic
+        comic_link: String,
+
+        /// Download path
+        #[arg(short = 'p', long)]
+        path: Option<String>,
+
+        /// Issues to download: numbers like 7, ranges like 10-25 or 40-, /regex/ on the name, latest:N or new (nothing on the first download, that records the issues), separated by commas; when repeated an issue must match all of them
+        #[arg(long, value_name = "EXPRESSION")]
+        select: Vec<Selection>,
+
+        #[command(flatten)]
+        volume: VolumeOptions,
+    },
+    /// Remove a comic from the library, the issues already downloaded are kept
+    Unfollow {
+        /// The link to the comic
+        comic_link: String,
+    },
+    /// Show the issues of a comic and whether they were downloaded, without downloading them
+    List {
+        /// The link to the comic
+        comic_link: String,
+
+        /// Download path, by default the one of the comic in the library
+        #[arg(short = 'p', long)]
+        path: Option<String>,
+
+        /// Issues to show, with the expressions of download
+        #[arg(long, value_name = "EXPRESSION")]
+        select: Vec<Selection>,
+
+        /// Print the list as json
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show the details of a comic, like its authors and how many issues it has
+    Info {
+        /// The link to the comic
+        comic_link: String,
+
+        /// Print the details as json
+        #[arg(long)]
+        json: bool,
+    },
+    /// Search a comic by title on every website that supports it
+    Search {
+        /// Title of the comic, or words in it
+        #[arg(required = true)]
+        query: Vec<String>,
+
+        /// Number of results to show
+        #[arg(long, default_value = "20")]
+        limit: usize,
+
+        /// Print the results as json
+        #[arg(long)]
+        json: bool,
+    },
+    /// Download the new issues of every comic in the library
+    Update {
+        /// Number of threads to use for dowloading
+        #[arg(short = 'J', long, default_value = "1")]
+        threads: usize,
+
+        #[command(flatten)]
+        network: NetworkOptions,
+    },
+    /// Check that the websites still work, on a comic of each one
+    #[command(alias = "check-sites")]
+    Doctor {
+        /// Names of the websites to check, all by default
+        sites: Vec<String>,
+
+        /// Check the website of this comic on it, instead of its usual one
+        #[arg(long, value_name = "COMIC_LINK", conflicts_with = "sites")]
+        url: Option<String>,
+
+        /// Print the reports as json
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        network: NetworkOptions,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct DownloadOptions {
+    /// Number of threads to use for dowloading
+    #[arg(short = 'J', long, default_value = "1")]
+    threads: usize,
+
     /// Number of issues to skip from the start
     #[arg(short= 'S', long, value_name = "SKIP_COUNT", default_value = "0")]
     skip_start: usize,
@@ -34,34 +162,310 @@ struct Args {
     #[arg(short = 'p', long)]
     path: Option<String>,
 
+    /// Issues to download: numbers like 7, ranges like 10-25 or 40-, /regex/ on the name, latest:N or new (nothing on the first download, that records the issues), separated by commas; when repeated an issue must match all of them
+    #[arg(long, value_name = "EXPRESSION")]
+    select: Vec<Selection>,
+
+    #[command(flatten)]
+    volume: VolumeOptions,
+}
+
+/// How the volumes are made
+#[derive(clap::Args, Debug)]
+struct VolumeOptions {
+    #[command(flatten)]
+    format: FormatOptions,
+
+    #[command(flatten)]
+    images: ImageOptions,
+
+    #[command(flatten)]
+    pdf: PdfOptions,
+
+    /// Pack this many consecutive issues in each volume, with a bookmark for each issue, 0 for all of them in one
+    #[arg(long, value_name = "ISSUES")]
+    omnibus: Option<usize>,
+}
+
+#[derive(clap::Args, Debug)]
+struct PdfOptions {
+    /// Size of the PDF pages: the size of the images at --dpi, A5, A4 or the screen of --device
+    #[arg(long, value_enum, value_name = "SIZE", default_value = "original")]
+    page_size: PageSizes,
+
+    /// Dots per inch of the images in the PDF pages of the original size
+    #[arg(long, default_value = "300")]
+    dpi: f32,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum PageSizes {
+    Original,
+    A5,
+    A4,
+    Device,
+}
+
+impl VolumeOptions {
+    fn apply(&self, comic: &mut ComicUrl) -> Result<(), String> {
+        comic.change_format(self.format.format());
+        comic.change_pipeline(self.images.pipeline());
+        let page_size = match self.pdf.page_size {
+            PageSizes::Original if self.pdf.dpi > 0.0 => PageSize::Original { dpi: self.pdf.dpi },
+            PageSizes::Original => return Err("the dpi must be more than 0".to_string()),
+            PageSizes::A5 => PageSize::A5,
+            PageSizes::A4 => PageSize::A4,
+            PageSizes::Device => match self.images.device {
+                Some(device) => PageSize::device(device.profile()),
+                None => return Err("--page-size device needs --device".to_string()),
+            },
+        };
+        comic.change_page_size(page_size);
+        comic.change_omnibus(self.omnibus);
+        return Ok(());
+    }
+}
+
+#[derive(clap::Args, Debug)]
+#[group(multiple = false)]
+struct FormatOptions {
     /// Download as PDF
     #[arg(long)]
     pdf: bool,
 
-    /// Install to Kobo after download
+    /// Download as fixed-layout EPUB
     #[arg(long)]
-    kobo_install: bool,
+    epub: bool,
 
-    /// The link to the comic
-    #[arg(required = false)]
-    comic_link: String,
+    /// Download as KEPUB, for the reader of Kobo e-readers
+    #[arg(long)]
+    kepub: bool,
+}
 
-    /// interactive mode (todo!)
-    #[arg(short= 'I', long)]
-    interactive: bool,
+#[derive(clap::Args, Debug)]
+struct ImageOptions {
+    /// Resize the pages to the screen of the e-reader and reduce them to 16 grays
+    #[arg(long, value_enum)]
+    device: Option<Device>,
+
+    /// Gamma correction of the pages, over 1 darkens the middle tones
+    #[arg(long)]
+    gamma: Option<f32>,
+
+    /// Contrast of the pages, over 1 increases it
+    #[arg(long)]
+    contrast: Option<f32>,
+
+    /// Number of gray levels the pages are reduced to
+    #[arg(long, value_name = "LEVELS", value_parser = clap::value_parser!(u8).range(2..))]
+    gray_levels: Option<u8>,
+
+    /// Landscape pages, usually two pages side by side, are kept, split in two, rotated or both rotated and split
+    #[arg(long, value_enum, value_name = "MODE", default_value = "keep")]
+    spreads: Spreads,
+
+    /// The comic is read from right to left, like manga: the halves of the spreads are swapped
+    #[arg(long)]
+    right_to_left: bool,
+
+    /// Cut the uniform borders of the pages
+    #[arg(long)]
+    crop: bool,
+
+    /// How different from the border a pixel can be and still be cut, from 0 to 255
+    #[arg(long, value_name = "TOLERANCE", default_value = "24", requires = "crop")]
+    crop_tolerance: u8,
+
+    /// The most that can be cut from every side, in percent of the page
+    #[arg(long, value_name = "PERCENT", default_value = "15", requires = "crop",
+        value_parser = clap::value_parser!(u8).range(0..=40))]
+    crop_limit: u8,
+
+    /// Convert the WebP pages to JPEG, for the readers that can't show them
+    #[arg(long)]
+    to_jpeg: bool,
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let raw_args: Vec<String> = args().collect();
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Spreads {
+    Keep,
+    Split,
+    Rotate,
+    Both,
+}
 
-    if raw_args.contains(&"--list-websites".to_string()) {
-        print!("{}",sites::print_supported_websites());
-        std::process::exit(0);
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Device {
+    KoboClara,
+    KoboLibra,
+    KoboSage,
+    KindlePaperwhite,
+}
+
+impl Device {
+    fn profile(&self) -> DeviceProfile {
+        match self {
+            Device::KoboClara => return DeviceProfile::KoboClara,
+            Device::KoboLibra => return DeviceProfile::KoboLibra,
+            Device::KoboSage => return DeviceProfile::KoboSage,
+            Device::KindlePaperwhite => return DeviceProfile::KindlePaperwhite,
+        }
     }
+}
+
+impl ImageOptions {
+    /// The processing of the pages, the options given change the one of the device
+    fn pipeline(&self) -> ImagePipeline {
+        let mut pipeline = match self.device {
+            Some(device) => device.profile().pipeline(),
+            None => ImagePipeline::default(),
+        };
+        if let Some(gamma) = self.gamma {
+            pipeline.gamma = gamma;
+        }
+        if let Some(contrast) = self.contrast {
+            pipeline.contrast = contrast;
+        }
+        if let Some(gray_levels) = self.gray_levels {
+            pipeline.grayscale = true;
+            pipeline.gray_levels = Some(gray_levels);
+        }
+        pipeline.spreads = match self.spreads {
+            Spreads::Keep => SpreadMode::Keep,
+            Spreads::Split => SpreadMode::Split,
+            Spreads::Rotate => SpreadMode::Rotate,
+            Spreads::Both => SpreadMode::Both,
+        };
+        pipeline.right_to_left = self.right_to_left;
+        if self.crop {
+            pipeline.crop = Some(AutoCrop { tolerance: self.crop_tolerance, max_fraction: self.crop_limit as f32 / 100.0 });
+        }
+        pipeline.to_jpeg = self.to_jpeg;
+        return pipeline;
+    }
+}
+
+impl FormatOptions {
+    /// The format chosen, cbz when there is no option
+    fn format(&self) -> OutputFormats {
+        if self.pdf {
+            return OutputFormats::Pdf;
+        }
+        if self.epub {
+            return OutputFormats::Epub;
+        }
+        if self.kepub {
+            return OutputFormats::Kepub;
+        }
+        return OutputFormats::Cbz;
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct NetworkOptions {
+    /// Times a failed request is repeated before giving up
+    #[arg(long, value_name = "RETRIES", default_value = "3")]
+    retries: u32,
+
+    /// Seconds to wait before the first retry, doubled at every following one
+    #[arg(long, value_name = "SECONDS", default_value = "0.5")]
+    retry_delay: f64,
+
+    /// Requests per second to each website, 0 for no limit
+    #[arg(long, value_name = "RPS", default_value = "4")]
+    rate_limit: f64,
+
+    /// Minimum seconds between two requests to the same website
+    #[arg(long, value_name = "SECONDS", default_value = "0")]
+    min_delay: f64,
 
+    /// Limit for a single website, like scanita.org=1 or scanita.org=1,2.5 to also set the minimum delay
+    #[arg(long, value_name = "HOST=RPS[,SECONDS]", value_parser = parse_host_limit)]
+    host_limit: Vec<(String, f64, Option<f64>)>,
+}
+
+/// The network options checked and converted
+struct NetworkSettings {
+    retry: RetryPolicy,
+    rate_limit: RateLimit,
+    host_limits: Vec<(String, RateLimit)>,
+}
+
+impl NetworkOptions {
+    fn settings(&self) -> Result<NetworkSettings, Box<dyn Error>> {
+        let retry = RetryPolicy {
+            retries: self.retries,
+            initial_backoff: Duration::try_from_secs_f64(self.retry_delay)?,
+            ..RetryPolicy::default()
+        };
+        let rate_limit = RateLimit {
+            requests_per_second: self.rate_limit,
+            min_delay: Duration::try_from_secs_f64(self.min_delay)?,
+            ..RateLimit::default()
+        };
+        let mut host_limits = Vec::new();
+        for (host, requests_per_second, min_delay) in &self.host_limit {
+            host_limits.push((host.clone(), RateLimit {
+                requests_per_second: *requests_per_second,
+                min_delay: Duration::try_from_secs_f64(min_delay.unwrap_or(self.min_delay))?,
+                ..RateLimit::default()
+            }));
+        }
+        return Ok(NetworkSettings { retry, rate_limit, host_limits });
+    }
+}
+
+impl NetworkSettings {
+    fn apply(&self, comicdwl: &mut ComicUrl) {
+        comicdwl.change_retry_policy(self.retry.clone());
+        comicdwl.change_rate_limit(self.rate_limit.clone());
+        for (host, limit) in &self.host_limits {
+            comicdwl.change_host_rate_limit(host, limit.clone());
+        }
+    }
+
+    /// A client with these settings, for the commands that don't download a comic
+    fn client(&self) -> HttpClient {
+        let mut client = HttpClient::new();
+        client.change_retry_policy(self.retry.clone());
+        client.change_default_rate_limit(self.rate_limit.clone());
+        for (host, limit) in &self.host_limits {
+            client.change_host_rate_limit(host, limit.clone());
+        }
+        return client;
+    }
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
 
-    let url = args.comic_link;
+    if args.list_websites {
+        print!("{}",comic_dl::print_supported_websites());
+        return Ok(());
+    }
+
+    match args.command {
+        Some(Command::Follow { comic_link, path, select, volume }) => return follow(&args.library, &comic_link, path, select, &volume),
+        Some(Command::Unfollow { comic_link }) => return unfollow(&args.library, &comic_link),
+        Some(Command::List { comic_link, path, select, json }) => return list(&args.library, &comic_link, path, select, json),
+        Some(Command::Info { comic_link, json }) => return info(&comic_link, json),
+        Some(Command::Search { query, limit, json }) => return search(&query.join(" "), limit, json),
+        Some(Command::Update { threads, network }) => return update(&args.library, threads, &network),
+        Some(Command::Doctor { sites, url, json, network }) => return doctor(&sites, url.as_deref(), json, &network),
+        None => {},
+    }
+
+    let Some(url) = args.comic_link else {
+        eprintln!("the link to the comic is required");
+        std::process::exit(1);
+    };
     if !is_link(&url) {
         eprintln!("the link provided is not a valid url");
         std::process::exit(1);
@@ -72,132 +476,256 @@ fn main() -> Result<(), Box<dyn Error>> {
     }
 
     if args.kobo_install {
-        generate_install(url)?;
+        kobo::generate_install(&url)?;
         println!("copy the file in the install directory to the kobo");
         return Ok(());
     }
     
-    let mut comicdwl = sites::ComicUrl::new(&url).expect("website not supported");
+    let mut comicdwl = ComicUrl::new(&url)?;
+    let options = args.download;
 
-    if args.skip_start > 0 {
-        comicdwl.change_skip_first(args.skip_start);
+    if options.skip_start > 0 {
+        comicdwl.change_skip_first(options.skip_start);
     }
 
-    if args.skip_last > 0 {
-        comicdwl.change_skip_lasts(args.skip_last);
+    if options.skip_last > 0 {
+        comicdwl.change_skip_lasts(options.skip_last);
     }
 
-    if let Some(ref new_path) = args.path {
-        comicdwl.change_path(&new_path)?;
+    if let Some(ref new_path) = options.path {
+        comicdwl.change_path(new_path)?;
     }
 
-    if args.pdf {
-        comicdwl.change_format(sites::OutputFormats::Pdf);
-    }
+    comicdwl.change_selection(options.select);
+
+    args.network.settings()?.apply(&mut comicdwl);
 
-    match args.threads {
+    options.volume.apply(&mut comicdwl)?;
+
+    match options.threads {
         0 => {
             comicdwl.download_all()?
         },
         number => {
-            multithread_download(number, comicdwl)?;
+            comicdwl.download_all_multithread(number)?;
         }
     }
 
     return Ok(());
 }
 
-fn multithread_download(
-    jobs_quantity: usize,
-    comicdwl: ComicUrl,
-) -> Result<(), Box<dyn Error>> {
-    let issue_list = comicdwl.get_issues_list()?;
-    println!("starting download with {jobs_quantity} threads" );
-    let comicdwl_arc = Arc::new(comicdwl);
-    let mut handles: VecDeque<thread::JoinHandle<()>> = VecDeque::new();
-    for issue in issue_list {
-        if handles.len() == jobs_quantity {
-            handles.pop_front().unwrap().join().unwrap();
-        }
-        let comicdwl_thread = comicdwl_arc.clone();
-        let handle = thread::spawn(move  || {
-            comicdwl_thread
-                .site_downloader
-                .download_issue(
-                    &comicdwl_thread.client,
-                    &comicdwl_thread.download_path,
-                    &issue)
-                .expect("couldn't download issue");
-            comicdwl_thread.create_volume(
-                &issue,
-                &comicdwl_thread.download_path
-                    .join(issue.name.clone())
-            ).expect("couldn't create volume");
-        });
-        handles.push_back(handle);
-    }
-    Ok(for handle in handles{
-        handle.join().unwrap();
-    })
-}
-
-fn generate_install(url: String) -> Result<(), Box<dyn Error>>{
-    let installation_path = std::path::Path::new("install");
-    if !installation_path.exists(){
-        fs::create_dir(installation_path)?;
-    }
-    let kobo_version_link = "https://github.com/leonardoCorti/comic-dl/releases/download/v0.5.0/comic-dl-armv7-linux";
-    let script = format!(
-r#"#!/bin/sh
-cd "$(dirname "$0")"
-./comic-dl-armv7-linux {url}"#); 
-    let script = script.replace("\\", "/");
-    let comic_dw = sites::ComicUrl::new(&url)?;
-    let comic_name = &comic_dw.comic_name;
-
-    let mut script_file = File::create(installation_path.join(format!("{comic_name}.sh")))?;
-    script_file.write_all(script.as_bytes())?;
-
-    let list_of_file: Vec<String> = installation_path.read_dir().unwrap().into_iter()
-        .map(|e| e.unwrap().path()
-            .file_name().unwrap()
-            .to_str().unwrap()
-            .to_string())
-        .filter(|e| e.ends_with("sh") && e != "update.sh")
-        .collect();
-
-    if list_of_file.len() > 1 {
-        let scripts: String = list_of_file.iter().fold("#!/bin/sh \ncd \"$(dirname \"$0\")\"\n".to_string(), |a,b| a + "./" + b + "\n");
-        let mut download_all = File::create(installation_path.join("download_all.sh"))?;
-        download_all.write_all(scripts.as_bytes())?;
-    }
-
-    let update_script = include_str!("./assets/update.sh");
-    let mut update_script_file = File::create(installation_path.join("update.sh"))?;
-    update_script_file.write_all(update_script.as_bytes())?;
-
-    match reqwest::blocking::Client::new().get(kobo_version_link).send(){
-        Ok(program_download) => {
-            let progam = program_download.bytes()?;
-            if is_elf(&progam[..4].try_into()?) {
-                let mut program_file = File::create(installation_path.join("comic-dl-armv7-linux"))?;
-                program_file.write_all(&progam)?;
-            } else {
-                println!("couldn't download the kobo version of comic-dl, donwload it manually");
-            }
+fn follow(library_path: &Path, url: &str, path: Option<String>, select: Vec<Selection>, volume: &VolumeOptions) -> Result<(), Box<dyn Error>> {
+    let mut comicdwl = ComicUrl::new(url)?;
+    if let Some(path) = path {
+        comicdwl.download_path = PathBuf::from(path);
+    }
+    comicdwl.change_selection(select);
+    volume.apply(&mut comicdwl)?;
+    let mut library = Library::load(library_path)?;
+    if library.follow(FollowedComic::new(&comicdwl)) {
+        println!("following {}", comicdwl.comic_name);
+    } else {
+        println!("{} was already followed, its settings are updated", comicdwl.comic_name);
+    }
+    library.save(library_path)?;
+    return Ok(());
+}
+
+fn unfollow(library_path: &Path, url: &str) -> Result<(), Box<dyn Error>> {
+    let mut library = Library::load(library_path)?;
+    if !library.unfollow(url) {
+        return Err(format!("{url} is not followed").into());
+    }
+    library.save(library_path)?;
+    return Ok(());
+}
+
+fn list(library_path: &Path, url: &str, path: Option<String>, select: Vec<Selection>, json: bool) -> Result<(), Box<dyn Error>> {
+    let mut comicdwl = ComicUrl::new(url)?;
+    let library = Library::load(library_path)?;
+    let followed_path = library.comics.iter()
+        .find(|e| e.url == url)
+        .map(|e| library_path.join(&e.path))
+        .filter(|e| e.is_dir());
+    if let Some(path) = path {
+        comicdwl.change_path(&path)?;
+    } else if let Some(path) = followed_path {
+        comicdwl.change_path(&path.to_string_lossy())?;
+    }
+    comicdwl.change_selection(select);
+    let issues = comicdwl.list_issues()?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&issues)?);
+        return Ok(());
+    }
+    let name_width = issues.iter().map(|e| e.name.chars().count()).chain(["name".len()]).max().unwrap_or_default();
+    let index_width = issues.iter().map(|e| e.index).max().unwrap_or_default().to_string().len();
+    println!("{:>index_width$}  {:<name_width$}  {:<10}  link", "#", "name", "status");
+    for issue in &issues {
+        println!("{:>index_width$}  {:<name_width$}  {:<10}  {}", issue.index, issue.name, issue.status.name(), issue.link);
+    }
+    return Ok(());
+}
+
+fn info(url: &str, json: bool) -> Result<(), Box<dyn Error>> {
+    let comicdwl = ComicUrl::new(url)?;
+    let metadata = comicdwl.get_comic_metadata()?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&metadata)?);
+        return Ok(());
+    }
+    let fields = [
+        ("title", Some(metadata.title)),
+        ("link", Some(metadata.url)),
+        ("cover", metadata.cover_url),
+        ("authors", Some(metadata.authors.join(", ")).filter(|e| !e.is_empty())),
+        ("status", metadata.status),
+        ("genres", Some(metadata.genres.join(", ")).filter(|e| !e.is_empty())),
+        ("issues", metadata.issue_count.map(|e| e.to_string())),
+        ("last update", metadata.last_update),
+    ];
+    for (name, value) in fields {
+        println!("{:<12} {}", format!("{name}:"), value.as_deref().unwrap_or("unknown"));
+    }
+    if let Some(description) = metadata.description {
+        println!("\n{description}");
+    }
+    return Ok(());
+}
+
+fn search(query: &str, limit: usize, json: bool) -> Result<(), Box<dyn Error>> {
+    let (mut results, errors) = search::search_all(&HttpClient::new(), query);
+    for (site, error) in &errors {
+        eprintln!("couldn't search on {site}: {error}");
+    }
+    results.truncate(limit);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+        return Ok(());
+    }
+    if results.is_empty() {
+        println!("no comic found for \"{query}\"");
+        return Ok(());
+    }
+    let title_width = results.iter().map(|e| e.title.chars().count()).chain(["title".len()]).max().unwrap_or_default();
+    let site_width = results.iter().map(|e| e.site.len()).chain(["site".len()]).max().unwrap_or_default();
+    let index_width = results.len().to_string().len();
+    println!("{:>index_width$}  {:<title_width$}  {:<site_width$}  link", "#", "title", "site");
+    for (index, result) in results.iter().enumerate() {
+        println!("{:>index_width$}  {:<title_width$}  {:<site_width$}  {}", index + 1, result.title, result.site, result.url);
+    }
+    return Ok(());
+}
+
+fn update(library_path: &Path, threads: usize, network: &NetworkOptions) -> Result<(), Box<dyn Error>> {
+    let library = Library::load(library_path)?;
+    if library.comics.is_empty() {
+        println!("no comic is followed, add one with: comic-dl follow <COMIC_LINK>");
+        return Ok(());
+    }
+    let network = network.settings()?;
+    let updates = library.update(library_path, threads, |comicdwl| network.apply(comicdwl));
+
+    println!("\nupdate summary:");
+    let mut problems = 0;
+    for update in &updates {
+        match &update.error {
+            Some(error) => {
+                problems += 1;
+                println!("- {}: not updated, {error}", update.name);
+            },
+            None if update.downloaded.is_empty() && update.failed.is_empty() => {
+                println!("- {}: no new issues", update.name);
+            },
+            None => {
+                println!("- {}: {} new issues {}", update.name, update.downloaded.len(), update.downloaded.join(", "));
+            },
         }
-        Err(_) => {
-            println!("couldn't download the kobo version of comic-dl, donwload it manually");
-        } ,
-    };
+        if !update.failed.is_empty() {
+            problems += 1;
+            println!("  failed: {}", update.failed.join(", "));
+        }
+    }
+    if problems > 0 {
+        return Err(format!("{problems} comics had problems").into());
+    }
     return Ok(());
 }
 
-fn is_elf(first_byes: &[u8;4]) -> bool {
-    let magic_number = [0x7F, b'E', b'L', b'F'];
-    return *first_byes == magic_number;
+fn doctor(names: &[String], url: Option<&str>, json: bool, network: &NetworkOptions) -> Result<(), Box<dyn Error>> {
+    let registry = SiteRegistry::load();
+    let sites: Vec<&Site> = match url {
+        Some(url) => vec![registry.find(url)?],
+        None if names.is_empty() => registry.sites().iter().collect(),
+        None => {
+            let mut sites = Vec::new();
+            for name in names {
+                let Some(site) = registry.sites().iter().find(|e| e.name == *name) else {
+                    return Err(format!("no website named {name}, see --list-websites").into());
+                };
+                sites.push(site);
+            }
+            sites
+        },
+    };
+    let canaries = doctor::user_canaries()?;
+    let canaries_file = config::canaries_file().unwrap_or_else(|| PathBuf::from("canaries.toml"));
+    for name in canaries.keys().filter(|name| registry.sites().iter().all(|e| e.name != **name)) {
+        eprintln!("the canary comic of {name} is not checked, there is no website with this name");
+    }
+    let client = network.settings()?.client();
+    let mut reports = Vec::new();
+    for site in sites {
+        if !json {
+            eprintln!("checking {}...", site.name);
+        }
+        let canary = url.or(canaries.get(&site.name).map(|e| e.as_str()));
+        reports.push(doctor::check_site(&client, site, canary));
+    }
+    let broken = reports.iter().filter(|e| e.status() == SiteStatus::Broken).count();
+    let checked = reports.iter().filter(|e| e.status() != SiteStatus::NotChecked).count();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+    } else {
+        for report in &reports {
+            match (report.status(), report.broken_stage()) {
+                (SiteStatus::Broken, Some(stage)) => println!("- {}: broken at the {} stage", report.site, stage.stage.name()),
+                (SiteStatus::NotChecked, _) => println!("- {}: not checked, it has no canary comic, give one with --url or in {}", report.site, canaries_file.display()),
+                _ => println!("- {}: ok", report.site),
+            }
+            if let Some(url) = &report.url {
+                println!("  comic: {url}");
+            }
+            for stage in &report.stages {
+                let status = match stage.status {
+                    StageStatus::Ok => "ok",
+                    StageStatus::Failed => "FAILED",
+                    StageStatus::Skipped => "skipped",
+                };
+                println!("  {:<8} {:<11} {}", status, stage.stage.name(), stage.detail);
+            }
+        }
+    }
+    if broken > 0 {
+        return Err(format!("{broken} websites are broken").into());
+    }
+    if checked == 0 {
+        return Err(format!("no website was checked, give a comic with --url or in {}", canaries_file.display()).into());
+    }
+    return Ok(());
 }
 
-fn is_link(e: &String) -> bool {
+fn is_link(e: &str) -> bool {
     return e.starts_with("https://") || e.starts_with("http://") ;
 }
+
+fn parse_host_limit(value: &str) -> Result<(String, f64, Option<f64>), String> {
+    let (host, limit) = value.split_once('=')
+        .ok_or("expected HOST=RPS[,SECONDS]")?;
+    let (rps, min_delay) = match limit.split_once(',') {
+        Some((rps, min_delay)) => (rps, Some(min_delay.parse::<f64>().map_err(|e| e.to_string())?)),
+        None => (limit, None),
+    };
+    let rps = rps.parse::<f64>().map_err(|e| e.to_string())?;
+    return Ok((host.to_string(), rps, min_delay));
+}
@@ -0,0 +1,131 @@
+//! metadata of an issue and the ComicInfo.xml written in the cbz volumes,
+//! read by Komga, Kavita and KOReader
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::sites::Issue;
+
+/// What is known about an issue, the strategies fill what their website shows
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IssueMetadata {
+    pub series: String,
+    pub number: String,
+    pub title: Option<String>,
+    pub summary: Option<String>,
+    pub writer: Option<String>,
+    pub publisher: Option<String>,
+    /// ISO 639 code, like `en` or `it`
+    pub language: Option<String>,
+    pub page_count: usize,
+    /// link to the issue on the website
+    pub source_url: Option<String>,
+}
+
+impl IssueMetadata {
+    /// Metadata from the name of the comic and of the issue: the number is
+    /// the first number in the name, the name becomes the title when it's
+    /// more than just the number
+    pub fn new(comic_name: &str, issue: &Issue) -> IssueMetadata {
+        let number_pattern = Regex::new(r"\d+(\.\d+)?").unwrap();
+        let number = number_pattern.find(&issue.name)
+            .map(|e| e.as_str().to_string())
+            .unwrap_or_else(|| issue.name.clone());
+        let title = if number == issue.name.trim() { None } else { Some(issue.name.clone()) };
+        return IssueMetadata {
+            series: comic_name.to_string(),
+            number,
+            title,
+            source_url: Some(issue.link.clone()).filter(|e| !e.is_empty()),
+            ..IssueMetadata::default()
+        };
+    }
+}
+
+/// What is known about a comic as a whole, what the website shows on its page
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComicMetadata {
+    pub title: String,
+    pub url: String,
+    pub cover_url: Option<String>,
+    pub authors: Vec<String>,
+    /// like ongoing or completed, in the words of the website
+    pub status: Option<String>,
+    pub genres: Vec<String>,
+    pub description: Option<String>,
+    pub issue_count: Option<usize>,
+    /// when the last issue came out, as the website writes it
+    pub last_update: Option<String>,
+}
+
+/// A page of the volume as listed in ComicInfo.xml
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageInfo {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// size of the file in bytes
+    pub size: u64,
+    /// title of the chapter that starts from this page
+    pub bookmark: Option<String>,
+}
+
+/// ComicInfo.xml following the ComicInfo 2.0 schema, the pages are in the
+/// order of the volume and the first is the cover
+pub fn comic_info_xml(metadata: &IssueMetadata, pages: &[PageInfo]) -> String {
+    let mut xml = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n",
+        "<ComicInfo xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\n",
+    ));
+    // the schema is a sequence, so the elements must be in this order
+    let page_count = pages.len().max(metadata.page_count).to_string();
+    let elements = [
+        ("Title", metadata.title.as_deref()),
+        ("Series", Some(metadata.series.as_str())),
+        ("Number", Some(metadata.number.as_str())),
+        ("Summary", metadata.summary.as_deref()),
+        ("Writer", metadata.writer.as_deref()),
+        ("Publisher", metadata.publisher.as_deref()),
+        ("Web", metadata.source_url.as_deref()),
+        ("PageCount", Some(page_count.as_str())),
+        ("LanguageISO", metadata.language.as_deref()),
+    ];
+    for (name, value) in elements {
+        if let Some(value) = value.filter(|e| !e.is_empty()) {
+            xml += &format!("  <{name}>{}</{name}>\n", escape_xml(value));
+        }
+    }
+    if !pages.is_empty() {
+        xml += "  <Pages>\n";
+        for (index, page) in pages.iter().enumerate() {
+            xml += &format!("    <Page Image=\"{index}\"");
+            if index == 0 {
+                xml += " Type=\"FrontCover\"";
+            }
+            xml += &format!(" ImageSize=\"{}\"", page.size);
+            if let (Some(width), Some(height)) = (page.width, page.height) {
+                xml += &format!(" ImageWidth=\"{width}\" ImageHeight=\"{height}\"");
+            }
+            if let Some(bookmark) = &page.bookmark {
+                xml += &format!(" Bookmark=\"{}\"", escape_xml(bookmark));
+            }
+            xml += " />\n";
+        }
+        xml += "  </Pages>\n";
+    }
+    xml += "</ComicInfo>\n";
+    return xml;
+}
+
+/// Escapes the special characters and drops the control characters that
+/// are not allowed in xml
+pub fn escape_xml(text: &str) -> String {
+    return text
+        .chars()
+        .filter(|e| !e.is_control() || ['\t', '\n', '\r'].contains(e))
+        .collect::<String>()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;");
+}
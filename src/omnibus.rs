@@ -0,0 +1,81 @@
+//! omnibus volumes: consecutive issues packed together in a single file,
+//! each one a chapter with its own bookmark
+
+use crate::{metadata::IssueMetadata, sites::Issue};
+
+/// A group of consecutive issues of the list, `number` counts from 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Omnibus {
+    pub number: usize,
+    pub issues: Vec<Issue>,
+    /// position of the first issue in the list, counting from 1
+    pub first_position: usize,
+}
+
+impl Omnibus {
+    /// Splits the issues in groups of `size` in the order of the list, the
+    /// last one can have less; with `size` 0 they are all in one group
+    pub fn group(issues: &[Issue], size: usize) -> Vec<Omnibus> {
+        if issues.is_empty() {
+            return Vec::new();
+        }
+        let size = if size == 0 { issues.len() } else { size };
+        return issues
+            .chunks(size)
+            .enumerate()
+            .map(|(index, chunk)| Omnibus {
+                number: index + 1,
+                issues: chunk.to_vec(),
+                first_position: index * size + 1,
+            })
+            .collect();
+    }
+
+    /// The chapters in the volume, like `ch21-30`: the numbers in the names
+    /// of the first and last issue, or their positions in the list when the
+    /// names have no number
+    pub fn chapters(&self) -> String {
+        let numbers: Vec<String> = [self.issues.first(), self.issues.last()]
+            .into_iter()
+            .flatten()
+            .map(|issue| IssueMetadata::new("", issue).number)
+            .collect();
+        let (first, last) = if numbers.iter().all(|e| e.parse::<f64>().is_ok()) {
+            (numbers[0].clone(), numbers[1].clone())
+        } else {
+            (self.first_position.to_string(), (self.first_position + self.issues.len() - 1).to_string())
+        };
+        if first == last {
+            return format!("ch{first}");
+        }
+        return format!("ch{first}-{last}");
+    }
+
+    /// Name of the volume without the extension, like `comic-vol03-(ch21-30)`
+    pub fn file_stem(&self, comic_name: &str) -> String {
+        return format!("{}({})", self.file_prefix(comic_name), self.chapters());
+    }
+
+    /// Start of the names of this volume, the same when it grows with new issues
+    pub fn file_prefix(&self, comic_name: &str) -> String {
+        return format!("{comic_name}-vol{:02}-", self.number);
+    }
+
+    /// Name used in the messages, like `vol03 (ch21-30)`
+    pub fn name(&self) -> String {
+        return format!("vol{:02} ({})", self.number, self.chapters());
+    }
+
+    /// Metadata of the volume, the people and the language are the ones of
+    /// its first issue
+    pub fn metadata(&self, first_issue: IssueMetadata, page_count: usize) -> IssueMetadata {
+        return IssueMetadata {
+            number: self.number.to_string(),
+            title: Some(format!("Volume {} ({})", self.number, self.chapters())),
+            summary: None,
+            page_count,
+            source_url: None,
+            ..first_issue
+        };
+    }
+}
@@ -0,0 +1,166 @@
+//! pages of an issue on disk: every page is written to a temporary file and
+//! renamed when complete, then recorded with its size in a manifest in the
+//! directory of the issue, so an interrupted download can go on from the
+//! first page that is missing
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::{error::SiteDownloaderError, http::HttpClient, image_info::{self, ImageKind}};
+
+/// File in the directory of the issue with the name and size of every page
+/// completely downloaded
+pub const PAGES_MANIFEST: &str = ".pages";
+
+/// Extension added to a page while it's being written
+pub const PARTIAL_EXTENSION: &str = "part";
+
+/// Extension of the pages whose format is not known
+pub const DEFAULT_EXTENSION: &str = "jpg";
+
+/// Where the page number `page_number` of the issue is saved, the
+/// extension is the one of its format
+pub fn page_path(issue_path: &Path, page_number: u32, extension: &str) -> PathBuf {
+    return issue_path.join(format!("{:04}.{extension}", page_number));
+}
+
+/// The page number `page_number` of the issue, whatever its format
+pub fn find_page(issue_path: &Path, page_number: u32) -> Option<PathBuf> {
+    let stem = format!("{:04}", page_number);
+    return page_files(issue_path).ok()?
+        .into_iter()
+        .find(|path| path.file_stem().and_then(|e| e.to_str()) == Some(stem.as_str()));
+}
+
+/// Whether the page was completely downloaded by an earlier run, the
+/// strategies load [`DownloadedPages`] once to check every page
+pub fn is_page_downloaded(issue_path: &Path, page_number: u32) -> bool {
+    return DownloadedPages::load(issue_path).contains(page_number);
+}
+
+/// The pages of an issue completely downloaded by an earlier run, read
+/// once from the directory and the manifest
+#[derive(Debug, Clone, Default)]
+pub struct DownloadedPages {
+    pages: HashSet<u32>,
+}
+
+impl DownloadedPages {
+    pub fn load(issue_path: &Path) -> DownloadedPages {
+        let manifest = read_manifest(issue_path);
+        let pages = page_files(issue_path).unwrap_or_default().iter()
+            .filter(|path| is_recorded(&manifest, path))
+            .filter_map(|path| path.file_stem()?.to_str()?.parse::<u32>().ok())
+            .collect();
+        return DownloadedPages { pages };
+    }
+
+    pub fn contains(&self, page_number: u32) -> bool {
+        return self.pages.contains(&page_number);
+    }
+}
+
+/// Downloads the page at `url`, writing it under a temporary name and
+/// renaming it only once it's complete; the extension comes from the magic
+/// bytes of the image or from its `Content-Type`
+pub fn save_page(client: &HttpClient, url: &str, issue_path: &Path, page_number: u32) -> Result<(), SiteDownloaderError> {
+    let (content, content_type) = client.get_bytes_with_type(url)?;
+    if content.is_empty() {
+        return Err(SiteDownloaderError::parsing(url, "the page is empty"));
+    }
+    let extension = match image_info::detect_kind(&content, content_type.as_deref()) {
+        Some(kind) => kind.extension(),
+        None if content_type.as_deref().is_some_and(|e| e.starts_with("text/")) =>
+            return Err(SiteDownloaderError::parsing(url, "the page is not an image")),
+        None => DEFAULT_EXTENSION,
+    };
+    let path = page_path(issue_path, page_number, extension);
+    // a page downloaded again can have changed format
+    for kind in ImageKind::ALL {
+        let old_path = page_path(issue_path, page_number, kind.extension());
+        if old_path != path && old_path.is_file() {
+            fs::remove_file(&old_path).map_err(|e| SiteDownloaderError::file_system(&old_path, e))?;
+        }
+    }
+    let mut partial_name = path.file_name().unwrap_or_default().to_os_string();
+    partial_name.push(format!(".{PARTIAL_EXTENSION}"));
+    let partial_path = path.with_file_name(partial_name);
+    fs::write(&partial_path, &content).map_err(|e| SiteDownloaderError::file_system(&partial_path, e))?;
+    fs::rename(&partial_path, &path).map_err(|e| SiteDownloaderError::file_system(&path, e))?;
+    record_page(issue_path, &path, content.len() as u64)?;
+    return Ok(());
+}
+
+/// The pages in the directory of the issue, in order, without the manifest
+/// and the pages still being written
+pub fn page_files(issue_path: &Path) -> Result<Vec<PathBuf>, SiteDownloaderError> {
+    let mut files: Vec<PathBuf> = fs::read_dir(issue_path)
+        .map_err(|e| SiteDownloaderError::file_system(issue_path, e))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| is_page_file(path))
+        .collect();
+    files.sort();
+    return Ok(files);
+}
+
+/// Checks that the issue has pages and that all of them were completely downloaded
+pub fn verify_issue(issue_path: &Path) -> Result<(), SiteDownloaderError> {
+    let files = page_files(issue_path)?;
+    if files.is_empty() {
+        return Err(SiteDownloaderError::IncompleteIssue { path: issue_path.to_path_buf(), pages: vec![] });
+    }
+    let manifest = read_manifest(issue_path);
+    let unverified: Vec<String> = files.iter()
+        .filter(|path| !is_recorded(&manifest, path))
+        .filter_map(|path| path.file_name().and_then(|e| e.to_str()).map(|e| e.to_string()))
+        .collect();
+    if !unverified.is_empty() {
+        return Err(SiteDownloaderError::IncompleteIssue { path: issue_path.to_path_buf(), pages: unverified });
+    }
+    return Ok(());
+}
+
+fn is_page_file(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|e| e.to_str()) else {
+        return false;
+    };
+    return path.is_file()
+        && !name.starts_with('.')
+        && path.extension().and_then(|e| e.to_str()) != Some(PARTIAL_EXTENSION);
+}
+
+fn is_recorded(manifest: &HashMap<String, u64>, path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|e| e.to_str()) else {
+        return false;
+    };
+    let Ok(metadata) = fs::metadata(path) else {
+        return false;
+    };
+    return manifest.get(name).is_some_and(|size| *size > 0 && *size == metadata.len());
+}
+
+/// Name and size of the pages in the manifest, a page recorded twice keeps the last size
+fn read_manifest(issue_path: &Path) -> HashMap<String, u64> {
+    let Ok(content) = fs::read_to_string(issue_path.join(PAGES_MANIFEST)) else {
+        return HashMap::new();
+    };
+    return content.lines()
+        .filter_map(|line| line.rsplit_once(' '))
+        .filter_map(|(name, size)| size.parse::<u64>().ok().map(|size| (name.to_string(), size)))
+        .collect();
+}
+
+fn record_page(issue_path: &Path, path: &Path, size: u64) -> Result<(), SiteDownloaderError> {
+    let manifest_path = issue_path.join(PAGES_MANIFEST);
+    let name = path.file_name().and_then(|e| e.to_str()).unwrap_or_default();
+    let mut manifest = OpenOptions::new().create(true).append(true).open(&manifest_path)
+        .map_err(|e| SiteDownloaderError::file_system(&manifest_path, e))?;
+    writeln!(manifest, "{name} {size}")
+        .map_err(|e| SiteDownloaderError::file_system(&manifest_path, e))?;
+    return Ok(());
+}
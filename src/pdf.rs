@@ -0,0 +1,304 @@
+//! pdf writer for the volumes: every page is written as soon as its image
+//! is read, so only one image at a time is in memory, and the jpegs are
+//! embedded as they are instead of being decoded and encoded again
+
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{image_info, pipeline::DeviceProfile};
+
+#[cfg(feature = "pdf")]
+use flate2::{write::ZlibEncoder, Compression};
+
+/// Points in an inch, the unit of the sizes in a pdf
+const POINTS_PER_INCH: f32 = 72.0;
+
+/// Size of the pages of the document
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageSize {
+    /// every page as big as its image printed at `dpi` dots per inch
+    Original { dpi: f32 },
+    /// every page of this size in points, the image is scaled to fit and centered
+    Fixed { width: f32, height: f32 },
+}
+
+impl Default for PageSize {
+    fn default() -> Self {
+        return PageSize::Original { dpi: 300.0 };
+    }
+}
+
+impl PageSize {
+    pub const A5: PageSize = PageSize::Fixed { width: 419.53, height: 595.28 };
+    pub const A4: PageSize = PageSize::Fixed { width: 595.28, height: 841.89 };
+
+    /// Pages with the proportions of the screen of the device, at the
+    /// size they have on it
+    pub fn device(device: DeviceProfile) -> PageSize {
+        let (width, height) = device.resolution();
+        let dpi = DeviceProfile::DPI as f32;
+        return PageSize::Fixed { width: width as f32 * POINTS_PER_INCH / dpi, height: height as f32 * POINTS_PER_INCH / dpi };
+    }
+
+    /// Size of the page and the rectangle of the image in it, as x, y,
+    /// width and height
+    fn layout(&self, image_width: u32, image_height: u32) -> ((f32, f32), (f32, f32, f32, f32)) {
+        let (image_width, image_height) = (image_width as f32, image_height as f32);
+        match *self {
+            PageSize::Original { dpi } => {
+                let width = image_width * POINTS_PER_INCH / dpi;
+                let height = image_height * POINTS_PER_INCH / dpi;
+                return ((width, height), (0.0, 0.0, width, height));
+            },
+            PageSize::Fixed { width, height } => {
+                let scale = (width / image_width).min(height / image_height);
+                let (drawn_width, drawn_height) = (image_width * scale, image_height * scale);
+                let x = (width - drawn_width) / 2.0;
+                let y = (height - drawn_height) / 2.0;
+                return ((width, height), (x, y, drawn_width, drawn_height));
+            },
+        }
+    }
+}
+
+/// The information of the document
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocumentInfo {
+    pub title: String,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    /// like `2024-05-01T10:20:30Z`
+    pub creation_date: Option<String>,
+}
+
+/// An image ready to be written in the pdf
+pub struct PdfImage {
+    pub width: u32,
+    pub height: u32,
+    color_space: &'static str,
+    decode: Option<&'static str>,
+    filter: &'static str,
+    data: Vec<u8>,
+}
+
+impl PdfImage {
+    /// The image from the bytes of its file: a jpeg that every reader shows
+    /// is kept as it is, the other images are decoded and compressed
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<PdfImage, String> {
+        let image = PdfImage::read(bytes)?;
+        if image.width == 0 || image.height == 0 {
+            return Err(format!("the image is {}x{} pixels", image.width, image.height));
+        }
+        return Ok(image);
+    }
+
+    fn read(bytes: Vec<u8>) -> Result<PdfImage, String> {
+        if let Some(frame) = image_info::jpeg_frame(&bytes).filter(|e| e.is_embeddable()) {
+            let (color_space, decode) = match (frame.components, frame.adobe) {
+                (1, _) => ("/DeviceGray", None),
+                (3, _) => ("/DeviceRGB", None),
+                (4, true) => ("/DeviceCMYK", Some("[1 0 1 0 1 0 1 0]")),
+                (4, false) => ("/DeviceCMYK", None),
+                (components, _) => return Err(format!("jpeg with {components} components")),
+            };
+            return Ok(PdfImage { width: frame.width, height: frame.height, color_space, decode, filter: "/DCTDecode", data: bytes });
+        }
+        return PdfImage::decode(&bytes);
+    }
+
+    #[cfg(feature = "pdf")]
+    fn decode(bytes: &[u8]) -> Result<PdfImage, String> {
+        let image = image::load_from_memory(bytes).map_err(|e| e.to_string())?;
+        let (width, height) = (image.width(), image.height());
+        let (color_space, pixels) = if image.color().has_color() {
+            let mut rgba = image.into_rgba8();
+            // the transparent parts become white, like the paper
+            rgba.pixels_mut().for_each(|pixel| {
+                let alpha = pixel.0[3] as u32;
+                for channel in 0..3 {
+                    pixel.0[channel] = ((pixel.0[channel] as u32 * alpha + 255 * (255 - alpha)) / 255) as u8;
+                }
+            });
+            ("/DeviceRGB", rgba.pixels().flat_map(|e| [e.0[0], e.0[1], e.0[2]]).collect::<Vec<u8>>())
+        } else {
+            let gray = image.into_luma_alpha8();
+            ("/DeviceGray", gray.pixels().map(|e| ((e.0[0] as u32 * e.0[1] as u32 + 255 * (255 - e.0[1] as u32)) / 255) as u8).collect())
+        };
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&pixels).map_err(|e| e.to_string())?;
+        let data = encoder.finish().map_err(|e| e.to_string())?;
+        return Ok(PdfImage { width, height, color_space, decode: None, filter: "/FlateDecode", data });
+    }
+
+    /// Without the pdf feature only the jpegs can be embedded
+    #[cfg(not(feature = "pdf"))]
+    fn decode(bytes: &[u8]) -> Result<PdfImage, String> {
+        let format = image_info::image_kind(bytes).map(|e| e.mime_type()).unwrap_or("unknown");
+        return Err(format!("{format} images need the pdf feature"));
+    }
+}
+
+/// Writes a pdf one page at a time: the objects are written as soon as
+/// they are complete, only their offsets are kept until the end
+pub struct PdfWriter<W: Write> {
+    writer: W,
+    position: u64,
+    /// offset of every object, the object number is the index plus one
+    offsets: Vec<u64>,
+    pages: Vec<usize>,
+    /// title and first page of every entry of the outline
+    bookmarks: Vec<(String, usize)>,
+    size: PageSize,
+}
+
+/// Objects written at the end but referenced by the pages
+const CATALOG: usize = 1;
+const PAGE_TREE: usize = 2;
+
+impl<W: Write> PdfWriter<W> {
+    pub fn new(writer: W, size: PageSize) -> io::Result<PdfWriter<W>> {
+        let mut pdf = PdfWriter { writer, position: 0, offsets: vec![0, 0], pages: Vec::new(), bookmarks: Vec::new(), size };
+        // the binary comment tells the programs that the file is not text
+        pdf.write(b"%PDF-1.7\n%\xE2\xE3\xCF\xD3\n")?;
+        return Ok(pdf);
+    }
+
+    /// Adds an entry to the outline that opens the next page added
+    pub fn add_bookmark(&mut self, title: &str) {
+        self.bookmarks.push((title.to_string(), self.pages.len()));
+    }
+
+    /// Adds a page with the image, an image without pixels is an error
+    pub fn add_page(&mut self, image: PdfImage) -> io::Result<()> {
+        if image.width == 0 || image.height == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("the image is {}x{} pixels", image.width, image.height)));
+        }
+        let ((page_width, page_height), (x, y, width, height)) = self.size.layout(image.width, image.height);
+        let image_id = self.begin_object()?;
+        let mut dictionary = format!(
+            "<< /Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace {} /BitsPerComponent 8 /Filter {} /Length {}",
+            image.width, image.height, image.color_space, image.filter, image.data.len(),
+        );
+        if let Some(decode) = image.decode {
+            dictionary += &format!(" /Decode {decode}");
+        }
+        dictionary += " >>\nstream\n";
+        self.write(dictionary.as_bytes())?;
+        self.write(&image.data)?;
+        self.write(b"\nendstream\nendobj\n")?;
+
+        let content = format!("q {width:.2} 0 0 {height:.2} {x:.2} {y:.2} cm /Im0 Do Q");
+        let content_id = self.begin_object()?;
+        self.write(format!("<< /Length {} >>\nstream\n{content}\nendstream\nendobj\n", content.len()).as_bytes())?;
+
+        let page_id = self.begin_object()?;
+        self.write(format!(
+            "<< /Type /Page /Parent {PAGE_TREE} 0 R /MediaBox [0 0 {page_width:.2} {page_height:.2}] \
+            /Resources << /XObject << /Im0 {image_id} 0 R >> >> /Contents {content_id} 0 R >>\nendobj\n",
+        ).as_bytes())?;
+        self.pages.push(page_id);
+        return Ok(());
+    }
+
+    /// Writes the page tree, the outline, the information and the cross
+    /// reference table, then returns the writer
+    pub fn finish(mut self, info: &DocumentInfo) -> io::Result<W> {
+        self.begin_numbered_object(PAGE_TREE)?;
+        let kids: Vec<String> = self.pages.iter().map(|e| format!("{e} 0 R")).collect();
+        self.write(format!("<< /Type /Pages /Kids [{}] /Count {} >>\nendobj\n", kids.join(" "), self.pages.len()).as_bytes())?;
+
+        let outline = self.write_outline()?;
+
+        let info_id = self.begin_object()?;
+        let mut dictionary = format!("<< /Title {} /Creator {}", pdf_string(&info.title), pdf_string("comic-dl"));
+        if let Some(author) = &info.author {
+            dictionary += &format!(" /Author {}", pdf_string(author));
+        }
+        if let Some(subject) = &info.subject {
+            dictionary += &format!(" /Subject {}", pdf_string(subject));
+        }
+        if let Some(date) = &info.creation_date {
+            let digits: String = date.chars().filter(|e| e.is_ascii_digit()).collect();
+            dictionary += &format!(" /CreationDate (D:{digits}Z)");
+        }
+        dictionary += " >>\nendobj\n";
+        self.write(dictionary.as_bytes())?;
+
+        self.begin_numbered_object(CATALOG)?;
+        let catalog = match outline {
+            Some(outline) => format!("<< /Type /Catalog /Pages {PAGE_TREE} 0 R /Outlines {outline} 0 R /PageMode /UseOutlines >>\nendobj\n"),
+            None => format!("<< /Type /Catalog /Pages {PAGE_TREE} 0 R >>\nendobj\n"),
+        };
+        self.write(catalog.as_bytes())?;
+
+        let xref_position = self.position;
+        let mut xref = format!("xref\n0 {}\n0000000000 65535 f \n", self.offsets.len() + 1);
+        for offset in &self.offsets {
+            xref += &format!("{offset:010} 00000 n \n");
+        }
+        xref += &format!(
+            "trailer\n<< /Size {} /Root {CATALOG} 0 R /Info {info_id} 0 R >>\nstartxref\n{xref_position}\n%%EOF\n",
+            self.offsets.len() + 1,
+        );
+        self.write(xref.as_bytes())?;
+        self.writer.flush()?;
+        return Ok(self.writer);
+    }
+
+    /// The outline with the bookmarks that point to a page, `None` if there are none
+    fn write_outline(&mut self) -> io::Result<Option<usize>> {
+        let bookmarks: Vec<(String, usize)> = self.bookmarks.iter()
+            .filter(|(_, page)| *page < self.pages.len())
+            .cloned()
+            .collect();
+        if bookmarks.is_empty() {
+            return Ok(None);
+        }
+        // the items are numbered right after the outline
+        let outline_id = self.offsets.len() + 1;
+        let first = outline_id + 1;
+        let last = outline_id + bookmarks.len();
+        self.begin_object()?;
+        self.write(format!("<< /Type /Outlines /First {first} 0 R /Last {last} 0 R /Count {} >>\nendobj\n", bookmarks.len()).as_bytes())?;
+        for (index, (title, page)) in bookmarks.iter().enumerate() {
+            let id = self.begin_object()?;
+            let mut item = format!("<< /Title {} /Parent {outline_id} 0 R /Dest [{} 0 R /Fit]", pdf_string(title), self.pages[*page]);
+            if index > 0 {
+                item += &format!(" /Prev {} 0 R", id - 1);
+            }
+            if id < last {
+                item += &format!(" /Next {} 0 R", id + 1);
+            }
+            item += " >>\nendobj\n";
+            self.write(item.as_bytes())?;
+        }
+        return Ok(Some(outline_id));
+    }
+
+    fn begin_object(&mut self) -> io::Result<usize> {
+        self.offsets.push(0);
+        let id = self.offsets.len();
+        self.begin_numbered_object(id)?;
+        return Ok(id);
+    }
+
+    fn begin_numbered_object(&mut self, id: usize) -> io::Result<()> {
+        self.offsets[id - 1] = self.position;
+        self.write(format!("{id} 0 obj\n").as_bytes())?;
+        return Ok(());
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.writer.write_all(bytes)?;
+        self.position += bytes.len() as u64;
+        return Ok(());
+    }
+}
+
+/// The text as a pdf string in utf-16, that works for every language
+fn pdf_string(text: &str) -> String {
+    let hex: String = text.encode_utf16().map(|e| format!("{e:04X}")).collect();
+    return format!("<FEFF{hex}>");
+}
@@ -0,0 +1,369 @@
+//! processing of the pages between the download and the volume, to make
+//! them lighter and better looking on e-ink screens: the downloaded pages
+//! are kept and the processed ones are written in a hidden directory of the
+//! issue, so an interrupted issue is processed again from the originals
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::SiteDownloaderError;
+
+#[cfg(feature = "images")]
+use std::{fs, io::Cursor};
+
+#[cfg(feature = "images")]
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage, GrayImage, ImageFormat};
+
+#[cfg(feature = "images")]
+use crate::{image_info::{image_kind, ImageKind}, sites::create_dir_if_missing};
+
+/// Directory in the issue with the processed pages
+pub const PROCESSED_DIR: &str = ".processed";
+
+/// Quality of the processed pages saved as jpeg
+#[cfg(feature = "images")]
+pub(crate) const JPEG_QUALITY: u8 = 90;
+
+/// What is done to every page, the default leaves them as downloaded
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImagePipeline {
+    /// pages bigger than this are scaled down to fit, keeping the proportions
+    pub max_size: Option<(u32, u32)>,
+    pub grayscale: bool,
+    /// values over 1 darken the middle tones, under 1 lighten them
+    pub gamma: f32,
+    /// values over 1 increase the contrast around the middle gray
+    pub contrast: f32,
+    /// number of gray levels the pages are reduced to, e-ink screens show 16
+    pub gray_levels: Option<u8>,
+    /// what is done to the landscape pages, that usually are two pages side by side
+    pub spreads: SpreadMode,
+    /// the comic is read from right to left, like manga
+    pub right_to_left: bool,
+    /// removes the uniform borders of the scans
+    pub crop: Option<AutoCrop>,
+    /// the webp pages are converted to jpeg, for the readers that can't
+    /// show them
+    pub to_jpeg: bool,
+}
+
+/// Cropping of the borders: a line of the border is cut when its pixels
+/// are all as its outermost line, a few specks of dust aside
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoCrop {
+    /// how much a pixel can be different from the border and still be border
+    pub tolerance: u8,
+    /// the most that can be cut from every side, as a fraction of the page,
+    /// so the art of pages with little contrast isn't cut
+    pub max_fraction: f32,
+}
+
+impl Default for AutoCrop {
+    fn default() -> Self {
+        return AutoCrop { tolerance: 24, max_fraction: 0.15 };
+    }
+}
+
+/// How the double-page spreads are shown on a portrait screen
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpreadMode {
+    /// left as a single small page
+    #[default]
+    Keep,
+    /// cut in two pages, in reading order
+    Split,
+    /// turned so it fills the screen held in landscape
+    Rotate,
+    /// the turned page followed by the two halves
+    Both,
+}
+
+impl Default for ImagePipeline {
+    fn default() -> Self {
+        return ImagePipeline {
+            max_size: None,
+            grayscale: false,
+            gamma: 1.0,
+            contrast: 1.0,
+            gray_levels: None,
+            spreads: SpreadMode::Keep,
+            right_to_left: false,
+            crop: None,
+            to_jpeg: false,
+        };
+    }
+}
+
+/// E-readers with a known screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceProfile {
+    KoboClara,
+    KoboLibra,
+    KoboSage,
+    KindlePaperwhite,
+}
+
+impl DeviceProfile {
+    /// Pixels per inch of the screen, the same for all the e-readers
+    pub const DPI: u32 = 300;
+
+    pub fn all() -> [DeviceProfile; 4] {
+        return [DeviceProfile::KoboClara, DeviceProfile::KoboLibra, DeviceProfile::KoboSage, DeviceProfile::KindlePaperwhite];
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeviceProfile::KoboClara => "Kobo Clara",
+            DeviceProfile::KoboLibra => "Kobo Libra",
+            DeviceProfile::KoboSage => "Kobo Sage",
+            DeviceProfile::KindlePaperwhite => "Kindle Paperwhite",
+        }
+    }
+
+    /// Width and height of the screen in portrait
+    pub fn resolution(&self) -> (u32, u32) {
+        match self {
+            DeviceProfile::KoboClara => (1072, 1448),
+            DeviceProfile::KoboLibra => (1264, 1680),
+            DeviceProfile::KoboSage => (1440, 1920),
+            DeviceProfile::KindlePaperwhite => (1236, 1648),
+        }
+    }
+
+
+    /// Pages at the size of the screen in the 16 grays of e-ink
+    pub fn pipeline(&self) -> ImagePipeline {
+        return ImagePipeline {
+            max_size: Some(self.resolution()),
+            grayscale: true,
+            gray_levels: Some(16),
+            ..ImagePipeline::default()
+        };
+    }
+}
+
+impl ImagePipeline {
+    /// Whether the pages would come out as they went in
+    pub fn is_noop(&self) -> bool {
+        return *self == ImagePipeline::default();
+    }
+
+    /// Whether every page is changed, not only the spreads, the pages with
+    /// borders and the ones to convert
+    #[cfg(feature = "images")]
+    fn changes_every_page(&self) -> bool {
+        return !ImagePipeline { spreads: SpreadMode::Keep, right_to_left: false, crop: None, to_jpeg: false, ..*self }.is_noop();
+    }
+
+    /// Whether the only change is the conversion to jpeg, so the other
+    /// pages don't even need to be decoded
+    #[cfg(feature = "images")]
+    fn only_converts(&self) -> bool {
+        return ImagePipeline { to_jpeg: false, ..*self }.is_noop();
+    }
+
+    /// Processes `files` writing the results in [`PROCESSED_DIR`] inside the
+    /// issue, returns the pages to pack in order; a page that can't be
+    /// decoded or doesn't need changes is packed as it is, a spread can
+    /// become more pages
+    #[cfg(feature = "images")]
+    pub fn process_pages(&self, issue_path: &Path, files: &[PathBuf]) -> Result<Vec<PathBuf>, SiteDownloaderError> {
+        if self.is_noop() {
+            return Ok(files.to_vec());
+        }
+        let processed_path = issue_path.join(PROCESSED_DIR);
+        create_dir_if_missing(&processed_path)?;
+        let mut pages = Vec::new();
+        for file in files {
+            let content = fs::read(file).map_err(|e| SiteDownloaderError::file_system(file, e))?;
+            let convert = self.to_jpeg && matches!(image_kind(&content), Some(ImageKind::WebP));
+            if !convert && self.only_converts() {
+                pages.push(file.clone());
+                continue;
+            }
+            let image = match image::load_from_memory(&content) {
+                Ok(image) => image,
+                Err(e) => {
+                    eprintln!("{} is packed without processing: {e}", file.display());
+                    pages.push(file.clone());
+                    continue;
+                },
+            };
+            let (image, cropped) = self.crop_margins(image);
+            if !self.changes_every_page() && !cropped && !convert && (self.spreads == SpreadMode::Keep || !is_spread(&image)) {
+                pages.push(file.clone());
+                continue;
+            }
+            let stem = file.file_stem().and_then(|e| e.to_str()).unwrap_or_default();
+            let processed = self.split_and_adjust(image);
+            let numbered = processed.len() > 1;
+            for (index, image) in processed.iter().enumerate() {
+                let name = if numbered { format!("{stem}-{}", index + 1) } else { stem.to_string() };
+                pages.push(self.save(image, &processed_path, &name)?);
+            }
+        }
+        return Ok(pages);
+    }
+
+    /// Without the images feature the pages can't be decoded
+    #[cfg(not(feature = "images"))]
+    pub fn process_pages(&self, _issue_path: &Path, files: &[PathBuf]) -> Result<Vec<PathBuf>, SiteDownloaderError> {
+        if self.is_noop() {
+            return Ok(files.to_vec());
+        }
+        return Err(SiteDownloaderError::FeatureDisabled { feature: "images" });
+    }
+
+    /// The pages made from a downloaded one: the borders are cut, the
+    /// spread is split or turned, then every page is resized and its tones
+    /// changed
+    #[cfg(feature = "images")]
+    pub fn process_image(&self, image: DynamicImage) -> Vec<DynamicImage> {
+        return self.split_and_adjust(self.crop_margins(image).0);
+    }
+
+    /// The page without its borders and whether there were any
+    #[cfg(feature = "images")]
+    fn crop_margins(&self, image: DynamicImage) -> (DynamicImage, bool) {
+        let Some(crop) = self.crop else {
+            return (image, false);
+        };
+        let gray = image.to_luma8();
+        let (width, height) = gray.dimensions();
+        let max_x = (width as f32 * crop.max_fraction.clamp(0.0, 0.4)) as u32;
+        let max_y = (height as f32 * crop.max_fraction.clamp(0.0, 0.4)) as u32;
+        let column = |x: u32| (0..height).map(|y| gray.get_pixel(x, y).0[0]).collect::<Vec<u8>>();
+        let row = |y: u32| (0..width).map(|x| gray.get_pixel(x, y).0[0]).collect::<Vec<u8>>();
+        let left = border_size(max_x, crop.tolerance, column);
+        let right = border_size(max_x, crop.tolerance, |i| column(width - 1 - i));
+        let top = border_size(max_y, crop.tolerance, row);
+        let bottom = border_size(max_y, crop.tolerance, |i| row(height - 1 - i));
+        if left + right + top + bottom == 0 {
+            return (image, false);
+        }
+        return (image.crop_imm(left, top, width - left - right, height - top - bottom), true);
+    }
+
+    #[cfg(feature = "images")]
+    fn split_and_adjust(&self, image: DynamicImage) -> Vec<DynamicImage> {
+        let pages = if is_spread(&image) { self.handle_spread(image) } else { vec![image] };
+        return pages.into_iter().map(|page| self.adjust(page)).collect();
+    }
+
+    /// The spread turned so its first half is at the top, or split in
+    /// reading order, or both
+    #[cfg(feature = "images")]
+    fn handle_spread(&self, image: DynamicImage) -> Vec<DynamicImage> {
+        let rotated = || if self.right_to_left { image.rotate270() } else { image.rotate90() };
+        let halves = || {
+            let half = image.width() / 2;
+            let left = image.crop_imm(0, 0, half, image.height());
+            let right = image.crop_imm(half, 0, image.width() - half, image.height());
+            if self.right_to_left { vec![right, left] } else { vec![left, right] }
+        };
+        match self.spreads {
+            SpreadMode::Keep => return vec![image.clone()],
+            SpreadMode::Split => return halves(),
+            SpreadMode::Rotate => return vec![rotated()],
+            SpreadMode::Both => return [vec![rotated()], halves()].concat(),
+        }
+    }
+
+    /// Resizes, then changes the tones of the page
+    #[cfg(feature = "images")]
+    fn adjust(&self, image: DynamicImage) -> DynamicImage {
+        let mut image = image;
+        if let Some((width, height)) = self.max_size {
+            if image.width() > width || image.height() > height {
+                image = image.resize(width, height, FilterType::Lanczos3);
+            }
+        }
+        if self.grayscale || self.gray_levels.is_some() {
+            let mut gray = image.to_luma8();
+            let table = self.tone_table();
+            gray.pixels_mut().for_each(|pixel| pixel.0[0] = table[pixel.0[0] as usize]);
+            return DynamicImage::ImageLuma8(gray);
+        }
+        if self.gamma != 1.0 || self.contrast != 1.0 {
+            let mut rgb = image.to_rgb8();
+            let table = self.tone_table();
+            rgb.pixels_mut().for_each(|pixel| pixel.0 = pixel.0.map(|e| table[e as usize]));
+            return DynamicImage::ImageRgb8(rgb);
+        }
+        return image;
+    }
+
+    /// The new value of every value of a channel: gamma, then contrast,
+    /// then the reduction to the gray levels
+    #[cfg(feature = "images")]
+    fn tone_table(&self) -> [u8; 256] {
+        let mut table = [0; 256];
+        for (value, entry) in table.iter_mut().enumerate() {
+            let mut tone = (value as f32 / 255.0).powf(self.gamma);
+            tone = ((tone - 0.5) * self.contrast + 0.5).clamp(0.0, 1.0);
+            if let Some(levels) = self.gray_levels.filter(|e| *e >= 2) {
+                let steps = (levels - 1) as f32;
+                tone = (tone * steps).round() / steps;
+            }
+            *entry = (tone * 255.0).round() as u8;
+        }
+        return table;
+    }
+
+    /// Saves the page as png when it has few grays, that compress well, as
+    /// jpeg otherwise
+    #[cfg(feature = "images")]
+    fn save(&self, image: &DynamicImage, directory: &Path, stem: &str) -> Result<PathBuf, SiteDownloaderError> {
+        let mut buffer = Cursor::new(Vec::new());
+        let out_path = if self.gray_levels.is_some() {
+            let out_path = directory.join(format!("{stem}.png"));
+            let gray: GrayImage = image.to_luma8();
+            gray.write_to(&mut buffer, ImageFormat::Png).map_err(|e| SiteDownloaderError::image(&out_path, e))?;
+            out_path
+        } else {
+            let out_path = directory.join(format!("{stem}.jpg"));
+            let encoder = JpegEncoder::new_with_quality(&mut buffer, JPEG_QUALITY);
+            let result = match image {
+                DynamicImage::ImageLuma8(gray) => gray.write_with_encoder(encoder),
+                other => other.to_rgb8().write_with_encoder(encoder),
+            };
+            result.map_err(|e| SiteDownloaderError::image(&out_path, e))?;
+            out_path
+        };
+        fs::write(&out_path, buffer.into_inner()).map_err(|e| SiteDownloaderError::file_system(&out_path, e))?;
+        return Ok(out_path);
+    }
+}
+
+/// How many lines from the side are border, at most `limit`: the lines
+/// with nearly all the pixels close to the middle value of the outermost one
+#[cfg(feature = "images")]
+fn border_size(limit: u32, tolerance: u8, line: impl Fn(u32) -> Vec<u8>) -> u32 {
+    if limit == 0 {
+        return 0;
+    }
+    let mut outermost = line(0);
+    outermost.sort_unstable();
+    let reference = outermost[outermost.len() / 2];
+    let mut size = 0;
+    while size < limit {
+        let values = line(size);
+        let outliers = values.iter().filter(|e| e.abs_diff(reference) > tolerance).count();
+        // up to 1 pixel out of 200 can be a speck of dust
+        if outliers * 200 > values.len() {
+            break;
+        }
+        size += 1;
+    }
+    return size;
+}
+
+/// Whether the page is in landscape, so it's probably two pages side by side
+#[cfg(feature = "images")]
+pub fn is_spread(image: &DynamicImage) -> bool {
+    return image.width() > image.height();
+}
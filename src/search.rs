@@ -0,0 +1,84 @@
+//! search of a comic by title on every website that can do it, the results
+//! are merged in a single list with the best matches first
+
+use serde::{Deserialize, Serialize};
+
+use crate::{http::HttpClient, sites::{registry::SiteRegistry, SiteDownloaderError}};
+
+/// A comic found on a website, `url` can be given to download it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchResult {
+    /// domain of the website
+    pub site: String,
+    pub title: String,
+    pub url: String,
+}
+
+/// Searches `query` on every website that supports it, a website that fails
+/// is returned with its error so the others are still shown
+pub fn search_all(client: &HttpClient, query: &str) -> (Vec<SearchResult>, Vec<(String, SiteDownloaderError)>) {
+    let mut results = Vec::new();
+    let mut errors = Vec::new();
+    for site in SiteRegistry::load().sites() {
+        if !site.capabilities.search {
+            continue;
+        }
+        match site.downloader().search(client, query) {
+            Ok(found) => results.extend(found),
+            Err(e) => errors.push((site.name.clone(), e)),
+        }
+    }
+    return (rank(query, results), errors);
+}
+
+/// Sorts the results from the best match of `query`, without the duplicates
+/// and the ones that don't have any word of it
+pub fn rank(query: &str, results: Vec<SearchResult>) -> Vec<SearchResult> {
+    let mut scored: Vec<(u32, SearchResult)> = Vec::new();
+    for result in results {
+        if scored.iter().any(|(_, e)| e.url == result.url) {
+            continue;
+        }
+        let score = score(query, &result.title);
+        if score > 0 {
+            scored.push((score, result));
+        }
+    }
+    scored.sort_by(|(a_score, a), (b_score, b)| {
+        b_score.cmp(a_score)
+            .then_with(|| a.title.len().cmp(&b.title.len()))
+            .then_with(|| a.title.cmp(&b.title))
+    });
+    return scored.into_iter().map(|(_, e)| e).collect();
+}
+
+/// How well the title matches the query: the same title is the best, then
+/// the titles starting with it, containing it and containing its words
+fn score(query: &str, title: &str) -> u32 {
+    let query = words(query);
+    let title = words(title);
+    if query.is_empty() {
+        return 0;
+    }
+    let (query_text, title_text) = (query.join(" "), title.join(" "));
+    if title_text == query_text {
+        return 1000;
+    }
+    if title_text.starts_with(&query_text) {
+        return 800;
+    }
+    if title_text.contains(&query_text) {
+        return 600;
+    }
+    let found = query.iter().filter(|word| title.contains(word)).count();
+    return (found * 500 / query.len()) as u32;
+}
+
+/// The words of the text in lowercase, without punctuation
+fn words(text: &str) -> Vec<String> {
+    return text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|e| !e.is_empty())
+        .map(|e| e.to_lowercase())
+        .collect();
+}
@@ -0,0 +1,181 @@
+//! expressions choosing the issues to download, the same for every website:
+//! terms separated by commas, an issue is chosen when it matches one of them
+//!
+//! - `10-25`, `40-`, `-5` and `7`: issue numbers, read from the names
+//! - `/annual/` or `/annual/i`: a regex on the name, `i` ignores the case
+//! - `latest:5`: the last 5 issues of the list
+//! - `new`: the issues that were not on the website at the last download;
+//!   the first download only records the issues on the website, so nothing
+//!   is new until the next one
+
+use std::{fmt, str::FromStr};
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{metadata::IssueMetadata, sites::Issue};
+
+/// A parsed selection expression, kept with its text to save it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Selection {
+    source: String,
+    terms: Vec<Term>,
+}
+
+#[derive(Debug, Clone)]
+enum Term {
+    /// issue numbers from the first to the last, both included
+    Numbers(Option<f64>, Option<f64>),
+    Pattern(Regex),
+    Latest(usize),
+    New,
+}
+
+impl Selection {
+    /// Whether the selection has the `new` term, that needs the issues of
+    /// the last download
+    pub fn has_new(&self) -> bool {
+        return self.terms.iter().any(|e| matches!(e, Term::New));
+    }
+
+    /// Whether the issue at `index` of `issues` matches one of the terms,
+    /// `listed` are the links on the website at the last download
+    pub fn matches(&self, index: usize, issues: &[Issue], listed: &[String]) -> bool {
+        let issue = &issues[index];
+        return self.terms.iter().any(|term| match term {
+            Term::Numbers(first, last) => match issue_number(issue) {
+                Some(number) => first.is_none_or(|e| number >= e) && last.is_none_or(|e| number <= e),
+                None => false,
+            },
+            Term::Pattern(pattern) => pattern.is_match(&issue.name),
+            Term::Latest(count) => index + count >= issues.len(),
+            // on the first download nothing is new, only what comes after
+            Term::New => !listed.is_empty() && !listed.contains(&issue.link),
+        });
+    }
+}
+
+/// The issues matching all the selections, in the order of the list
+pub fn select(selections: &[Selection], issues: Vec<Issue>, listed: &[String]) -> Vec<Issue> {
+    let chosen: Vec<bool> = (0..issues.len())
+        .map(|index| selections.iter().all(|e| e.matches(index, &issues, listed)))
+        .collect();
+    return issues.into_iter()
+        .zip(chosen)
+        .filter(|(_, chosen)| *chosen)
+        .map(|(issue, _)| issue)
+        .collect();
+}
+
+/// The first number in the name of the issue
+fn issue_number(issue: &Issue) -> Option<f64> {
+    return IssueMetadata::new("", issue).number.parse().ok();
+}
+
+impl FromStr for Selection {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Selection, String> {
+        let mut terms = Vec::new();
+        for text in split_terms(source)? {
+            terms.push(parse_term(text)?);
+        }
+        if terms.is_empty() {
+            return Err("the selection is empty".to_string());
+        }
+        return Ok(Selection { source: source.trim().to_string(), terms });
+    }
+}
+
+/// Splits the expression at the commas that are not in a regex
+fn split_terms(source: &str) -> Result<Vec<&str>, String> {
+    let mut terms = Vec::new();
+    let mut start = 0;
+    let mut in_pattern = false;
+    let mut escaped = false;
+    for (position, character) in source.char_indices() {
+        match character {
+            _ if escaped => escaped = false,
+            '\\' if in_pattern => escaped = true,
+            '/' => in_pattern = !in_pattern,
+            ',' if !in_pattern => {
+                terms.push(source[start..position].trim());
+                start = position + 1;
+            },
+            _ => {},
+        }
+    }
+    if in_pattern {
+        return Err(format!("the regex in \"{source}\" is not closed by /"));
+    }
+    terms.push(source[start..].trim());
+    return Ok(terms.into_iter().filter(|e| !e.is_empty()).collect());
+}
+
+fn parse_term(text: &str) -> Result<Term, String> {
+    if text == "new" {
+        return Ok(Term::New);
+    }
+    if let Some(count) = text.strip_prefix("latest:") {
+        let count = count.trim().parse::<usize>().map_err(|_| format!("\"{text}\" needs the number of issues, like latest:5"))?;
+        return Ok(Term::Latest(count));
+    }
+    if let Some(rest) = text.strip_prefix('/') {
+        let (pattern, flags) = rest.rsplit_once('/').ok_or_else(|| format!("the regex in \"{text}\" is not closed by /"))?;
+        if !["", "i"].contains(&flags) {
+            return Err(format!("unknown regex flags \"{flags}\", only i is supported"));
+        }
+        let pattern = RegexBuilder::new(pattern)
+            .case_insensitive(flags == "i")
+            .build()
+            .map_err(|e| format!("invalid regex \"{pattern}\": {e}"))?;
+        return Ok(Term::Pattern(pattern));
+    }
+    let number = |value: &str| match value.trim() {
+        "" => Ok(None),
+        value => value.parse::<f64>().map(Some).map_err(|_| format!("\"{text}\" is not a number, a range, a /regex/, latest:N or new")),
+    };
+    let (first, last) = match text.split_once('-') {
+        Some((first, last)) => (number(first)?, number(last)?),
+        None => {
+            let value = number(text)?;
+            (value, value)
+        },
+    };
+    if let (Some(first), Some(last)) = (first, last) {
+        if first > last {
+            return Err(format!("the range \"{text}\" ends before it starts"));
+        }
+    }
+    if first.is_none() && last.is_none() {
+        return Err(format!("the range \"{text}\" has no numbers"));
+    }
+    return Ok(Term::Numbers(first, last));
+}
+
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{}", self.source);
+    }
+}
+
+impl PartialEq for Selection {
+    fn eq(&self, other: &Selection) -> bool {
+        return self.source == other.source;
+    }
+}
+
+impl TryFrom<String> for Selection {
+    type Error = String;
+
+    fn try_from(source: String) -> Result<Selection, String> {
+        return source.parse();
+    }
+}
+
+impl From<Selection> for String {
+    fn from(selection: Selection) -> String {
+        return selection.source;
+    }
+}
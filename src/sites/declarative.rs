@@ -0,0 +1,406 @@
+//! websites described by a definition file instead of code: where the list
+//! of the issues is, the css selectors and regexes that find the issues and
+//! the images, read by a single generic strategy
+//!
+//! ```toml
+//! name = "example"
+//! domains = ["example.com", "*.example.com"]
+//! language = "en"
+//!
+//! [issues]
+//! url = "{url}?page={page}"
+//! selector = "ul.chapters a"
+//! name_pattern = "Chapter (\\d+)"
+//! newest_first = true
+//!
+//! [pages]
+//! selector = "div.reader img"
+//! attribute = "data-src"
+//! ```
+
+use std::{fs, path::{Path, PathBuf}};
+
+use regex::Regex;
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+
+use super::*;
+
+/// Most pages of the list of issues read, for the websites that repeat the
+/// last one forever
+const MAX_LIST_PAGES: u32 = 500;
+
+/// Most pages of an issue read when every page has its own link
+const MAX_ISSUE_PAGES: u32 = 2000;
+
+/// A website as written in its definition file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SiteDefinition {
+    pub name: String,
+    /// domains of the website, `*.example.com` is any subdomain of example.com
+    pub domains: Vec<String>,
+    /// ISO 639 code of the language of the comics
+    #[serde(default)]
+    pub language: Option<String>,
+    /// regex on the link of the comic, the first group is the name of the
+    /// comic; by default it's the last part of the path
+    #[serde(default)]
+    pub comic_name: Option<String>,
+    /// link of a comic that `doctor` checks the definition on
+    #[serde(default)]
+    pub canary: Option<String>,
+    pub issues: IssueListDefinition,
+    pub pages: PagesDefinition,
+}
+
+/// Where the issues are listed and how to find them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IssueListDefinition {
+    /// link of the list, `{url}` is the link of the comic; with `{page}` the
+    /// pages of the list are read until one without issues
+    #[serde(default = "default_list_url")]
+    pub url: String,
+    #[serde(default = "default_first_page")]
+    pub first_page: u32,
+    /// css selector of the links to the issues
+    pub selector: String,
+    /// attribute of the link with the link of the issue
+    #[serde(default = "default_link_attribute")]
+    pub attribute: String,
+    /// regex on the text of the link, the first group or the whole match is
+    /// the name of the issue; the whole text when it doesn't match
+    #[serde(default)]
+    pub name_pattern: Option<String>,
+    /// regex that the link of an issue must match, the others are skipped
+    #[serde(default)]
+    pub link_pattern: Option<String>,
+    /// the website lists the last issue first
+    #[serde(default)]
+    pub newest_first: bool,
+}
+
+/// How to find the images of an issue
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PagesDefinition {
+    /// link of every page of the issue, `{issue}` is the link of the issue
+    /// and `{page}` the number of the page; by default all the images are in
+    /// the page of the issue
+    #[serde(default)]
+    pub url: Option<String>,
+    /// css selector of the images
+    #[serde(default)]
+    pub selector: Option<String>,
+    /// attribute of the image with its link
+    #[serde(default = "default_image_attribute")]
+    pub attribute: String,
+    /// regex finding the links of the images in the html, for the websites
+    /// that load them with scripts; the first group or the whole match
+    #[serde(default)]
+    pub pattern: Option<String>,
+}
+
+fn default_list_url() -> String {
+    return "{url}".to_string();
+}
+
+fn default_first_page() -> u32 {
+    return 1;
+}
+
+fn default_link_attribute() -> String {
+    return "href".to_string();
+}
+
+fn default_image_attribute() -> String {
+    return "src".to_string();
+}
+
+impl SiteDefinition {
+    pub fn from_toml(text: &str) -> Result<SiteDefinition, String> {
+        return toml::from_str(text).map_err(|e| e.to_string());
+    }
+
+    pub fn from_yaml(text: &str) -> Result<SiteDefinition, String> {
+        return serde_yaml::from_str(text).map_err(|e| e.to_string());
+    }
+
+    /// Reads the definition in `path`, toml or yaml from the extension
+    pub fn load(path: &Path) -> Result<SiteDefinition, SiteDownloaderError> {
+        let text = fs::read_to_string(path).map_err(|e| SiteDownloaderError::file_system(path, e))?;
+        let definition = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => SiteDefinition::from_toml(&text),
+            Some("yaml" | "yml") => SiteDefinition::from_yaml(&text),
+            _ => Err("the file must end with .toml, .yaml or .yml".to_string()),
+        };
+        return definition.map_err(|reason| SiteDownloaderError::SiteDefinition { path: path.to_path_buf(), reason });
+    }
+}
+
+/// The generic strategy that downloads from a website following its definition
+#[derive(Debug, Clone)]
+pub struct DeclarativeStrategy {
+    pub definition: SiteDefinition,
+    comic_name: Option<Regex>,
+    name_pattern: Option<Regex>,
+    link_pattern: Option<Regex>,
+    image_pattern: Option<Regex>,
+}
+
+impl DeclarativeStrategy {
+    /// Checks the selectors and the regexes of the definition
+    pub fn new(definition: SiteDefinition) -> Result<DeclarativeStrategy, String> {
+        if definition.domains.is_empty() {
+            return Err("there are no domains".to_string());
+        }
+        Selector::parse(&definition.issues.selector)
+            .map_err(|e| format!("invalid selector \"{}\": {e}", definition.issues.selector))?;
+        match (&definition.pages.selector, &definition.pages.pattern) {
+            (Some(selector), None) => {
+                Selector::parse(selector).map_err(|e| format!("invalid selector \"{selector}\": {e}"))?;
+            },
+            (None, Some(_)) => {},
+            _ => return Err("the pages need either a selector or a pattern".to_string()),
+        }
+        if definition.pages.url.as_ref().is_some_and(|e| !e.contains("{page}")) {
+            return Err("the link of the pages needs {page}".to_string());
+        }
+        let regex = |pattern: &Option<String>| {
+            return pattern.as_deref()
+                .map(|e| Regex::new(e).map_err(|error| format!("invalid regex \"{e}\": {error}")))
+                .transpose();
+        };
+        return Ok(DeclarativeStrategy {
+            comic_name: regex(&definition.comic_name)?,
+            name_pattern: regex(&definition.issues.name_pattern)?,
+            link_pattern: regex(&definition.issues.link_pattern)?,
+            image_pattern: regex(&definition.pages.pattern)?,
+            definition,
+        });
+    }
+
+    /// Reads and checks the definition in `path`
+    pub fn load(path: &Path) -> Result<DeclarativeStrategy, SiteDownloaderError> {
+        return DeclarativeStrategy::new(SiteDefinition::load(path)?)
+            .map_err(|reason| SiteDownloaderError::SiteDefinition { path: path.to_path_buf(), reason });
+    }
+
+    /// The website of the definition for the registry, when `comic_name`
+    /// is given the links to the comics must match it
+    pub fn site(self) -> Site {
+        let domains: Vec<&str> = self.definition.domains.iter().map(|e| e.as_str()).collect();
+        let example = format!("https://{}/", domains[0].trim_start_matches("*."));
+        let mut site = Site::new(&self.definition.name, &domains, &example, {
+            let strategy = self.clone();
+            move || Box::new(strategy.clone())
+        });
+        if let Some(pattern) = self.comic_name.clone() {
+            site.change_validator(move |url| pattern.is_match(url.as_str()));
+        }
+        if let Some(canary) = &self.definition.canary {
+            site.change_canary(canary);
+        }
+        return site;
+    }
+
+    /// The issues in a page of the list
+    fn issues_in(&self, body: &str, page_url: &str) -> Result<Vec<Issue>, SiteDownloaderError> {
+        let base = reqwest::Url::parse(page_url)
+            .map_err(|_| SiteDownloaderError::InvalidUrl { url: page_url.to_string() })?;
+        let document = Html::parse_document(body);
+        let selector = Selector::parse(&self.definition.issues.selector).unwrap();
+        let mut issues = Vec::new();
+        for link in document.select(&selector) {
+            let Some(issue_link) = link.value().attr(&self.definition.issues.attribute).and_then(|e| base.join(e.trim()).ok()) else {
+                continue;
+            };
+            let issue_link = issue_link.to_string();
+            if self.link_pattern.as_ref().is_some_and(|e| !e.is_match(&issue_link)) {
+                continue;
+            }
+            let Some(text) = element_text(link) else {
+                continue;
+            };
+            let name = match self.name_pattern.as_ref().and_then(|e| e.captures(&text)) {
+                Some(captures) => captures.get(1).or(captures.get(0)).map(|e| e.as_str().to_string()).unwrap_or(text),
+                None => text,
+            };
+            // the name is the directory of the issue
+            let name = name.replace(['/', '\\'], "-").trim().to_string();
+            if check_name(&issue_link, &name).is_err() {
+                continue;
+            }
+            issues.push(Issue { name, link: issue_link });
+        }
+        return Ok(issues);
+    }
+
+    /// The links of the images in a page of the issue
+    fn images_in(&self, body: &str, page_url: &str) -> Result<Vec<String>, SiteDownloaderError> {
+        let base = reqwest::Url::parse(page_url)
+            .map_err(|_| SiteDownloaderError::InvalidUrl { url: page_url.to_string() })?;
+        let links: Vec<String> = match (&self.definition.pages.selector, &self.image_pattern) {
+            (Some(selector), _) => {
+                let document = Html::parse_document(body);
+                let selector = Selector::parse(selector).unwrap();
+                document.select(&selector)
+                    .filter_map(|e| e.value().attr(&self.definition.pages.attribute))
+                    .map(|e| e.trim().to_string())
+                    .collect()
+            },
+            (None, Some(pattern)) => pattern.captures_iter(body)
+                .filter_map(|e| e.get(1).or(e.get(0)))
+                .map(|e| e.as_str().to_string())
+                .collect(),
+            (None, None) => Vec::new(),
+        };
+        let mut images: Vec<String> = Vec::new();
+        for link in links.iter().filter_map(|e| base.join(e).ok()) {
+            if !images.contains(&link.to_string()) {
+                images.push(link.to_string());
+            }
+        }
+        return Ok(images);
+    }
+}
+
+impl ComicDownloader for DeclarativeStrategy {
+    fn download_issue(&self, client: &HttpClient, download_path: &Path, issue: &Issue) -> Result<(), SiteDownloaderError> {
+        check_name(&issue.link, &issue.name)?;
+        create_dir_if_missing(download_path)?;
+        println!("downloading {}", issue.name);
+        let issue_path = download_path.join(&issue.name);
+        create_dir_if_missing(&issue_path)?;
+        let downloaded = DownloadedPages::load(&issue_path);
+        let Some(url_template) = &self.definition.pages.url else {
+            let body = client.get_text(&issue.link)?;
+            let images = self.images_in(&body, &issue.link)?;
+            if images.is_empty() {
+                return Err(SiteDownloaderError::parsing(&issue.link, "no images in the issue"));
+            }
+            for (index, image) in images.iter().enumerate() {
+                let page_number = index as u32 + 1;
+                if downloaded.contains(page_number) {
+                    continue;
+                }
+                self.download_page(client, image, &issue_path, page_number)
+                    .map_err(|e| e.in_page(&issue.name, page_number))?;
+            }
+            return Ok(());
+        };
+        for page_number in 1..=MAX_ISSUE_PAGES {
+            if downloaded.contains(page_number) {
+                continue;
+            }
+            let link = url_template.replace("{issue}", issue.link.trim_end_matches('/')).replace("{page}", &page_number.to_string());
+            let response = client.send(&link)?;
+            // the pages after the last one are missing or redirect elsewhere
+            let redirected = reqwest::Url::parse(&link).is_ok_and(|e| &e != response.url());
+            if !response.status().is_success() || redirected {
+                break;
+            }
+            let body = response.text()
+                .map_err(|source| SiteDownloaderError::Request { url: link.clone(), source })?;
+            let Some(image) = self.images_in(&body, &link)?.into_iter().next() else {
+                break;
+            };
+            self.download_page(client, &image, &issue_path, page_number)
+                .map_err(|e| e.in_page(&issue.name, page_number))?;
+        }
+        return Ok(());
+    }
+
+    fn download_page(&self, client: &HttpClient, link: &str, issue_path: &Path, page_number: u32) -> Result<(), SiteDownloaderError> {
+        save_page(client, link, issue_path, page_number)?;
+        return Ok(());
+    }
+
+    fn get_issues_list(&self, client: &HttpClient, url: &str) -> Result<Vec<Issue>, SiteDownloaderError> {
+        let template = self.definition.issues.url.replace("{url}", url.trim_end_matches('/'));
+        let mut issues: Vec<Issue> = Vec::new();
+        if !template.contains("{page}") {
+            issues = self.issues_in(&client.get_text(&template)?, &template)?;
+        } else {
+            let first_page = self.definition.issues.first_page;
+            for page in first_page..first_page.saturating_add(MAX_LIST_PAGES) {
+                let link = template.replace("{page}", &page.to_string());
+                let response = client.send(&link)?;
+                if !response.status().is_success() {
+                    break;
+                }
+                let body = response.text()
+                    .map_err(|source| SiteDownloaderError::Request { url: link.clone(), source })?;
+                let new_issues: Vec<Issue> = self.issues_in(&body, &link)?.into_iter()
+                    .filter(|issue| issues.iter().all(|e| e.link != issue.link))
+                    .collect();
+                if new_issues.is_empty() {
+                    break;
+                }
+                issues.extend(new_issues);
+            }
+        }
+        let mut unique: Vec<Issue> = Vec::new();
+        for issue in issues {
+            if unique.iter().all(|e| e.link != issue.link) {
+                unique.push(issue);
+            }
+        }
+        if self.definition.issues.newest_first {
+            unique.reverse();
+        }
+        return Ok(unique);
+    }
+
+    fn get_comic_name(&self, _client: &HttpClient, url: &str) -> Result<String, SiteDownloaderError> {
+        let name = match &self.comic_name {
+            Some(pattern) => pattern.captures(url)
+                .and_then(|e| e.get(1).or(e.get(0)))
+                .map(|e| e.as_str().to_string())
+                .ok_or_else(|| SiteDownloaderError::parsing(url, "the link doesn't match comic_name of the site definition"))?,
+            None => {
+                let parsed_url = reqwest::Url::parse(url).map_err(|_| SiteDownloaderError::InvalidUrl { url: url.to_string() })?;
+                parsed_url.path_segments()
+                    .and_then(|segments| segments.rev().find(|e| !e.is_empty()))
+                    .map(|e| e.to_string())
+                    .ok_or_else(|| SiteDownloaderError::parsing(url, "the link has no name of the comic"))?
+            },
+        };
+        check_name(url, &name)?;
+        return Ok(name);
+    }
+
+    fn get_issue_metadata(&self, _client: &HttpClient, comic_name: &str, issue: &Issue) -> Result<IssueMetadata, SiteDownloaderError> {
+        return Ok(IssueMetadata {
+            language: self.definition.language.clone(),
+            ..IssueMetadata::new(comic_name, issue)
+        });
+    }
+}
+
+/// The strategies of the definitions in `dir`, the files that can't be
+/// used are returned as errors
+pub fn load_site_definitions(dir: &Path) -> Vec<Result<DeclarativeStrategy, SiteDownloaderError>> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|path| matches!(path.extension().and_then(|e| e.to_str()), Some("toml" | "yaml" | "yml")))
+        .collect();
+    paths.sort();
+    return paths.iter().map(|path| DeclarativeStrategy::load(path)).collect();
+}
+
+/// The strategies of the user, in the sites directory of the configuration;
+/// the definitions that can't be used are reported and skipped
+pub fn user_site_definitions() -> Vec<DeclarativeStrategy> {
+    let Some(dir) = crate::config::sites_dir() else {
+        return Vec::new();
+    };
+    return load_site_definitions(&dir).into_iter()
+        .filter_map(|result| result.map_err(|e| eprintln!("{e}")).ok())
+        .collect();
+}
@@ -1,103 +1,114 @@
-use std::{fmt::Debug, fs::{self, File}, io, path::{Path, PathBuf}, str::FromStr};
+use std::{collections::VecDeque, fmt::Debug, fs, io, path::{Component, Path, PathBuf}, sync::Mutex, thread};
 
-#[cfg(feature = "pdf")]
-use std::io::{Cursor, Read};
-
-#[cfg(feature = "pdf")]
-extern crate printpdf;
-#[cfg(feature = "pdf")]
-extern crate image as img;
-
-#[cfg(feature = "pdf")]
-use printpdf::*;
+use serde::{Deserialize, Serialize};
 
 use readcomic_me::ReadcomicMeStrategy;
-use reqwest::blocking::Client;
 use scanita_org::ScanitaOrgStrategy;
 use zerocalcare_net::ZerocalcareNetStrategy;
 
-pub mod readcomic_me;
-pub mod zerocalcare_net;
-pub mod scanita_org;
-
-#[derive(Debug)]
-pub enum SiteDownloaderError{
-    ParsingError,
-    NotFound,
-    FileSystemError,
-    ImageError,
-}
-
-impl std::fmt::Display for SiteDownloaderError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Error downloading")
-    }
-}
+use crate::{
+    http::{HttpClient, RateLimit, RetryPolicy},
+    metadata::{ComicMetadata, IssueMetadata},
+    omnibus::Omnibus,
+    pages,
+    pdf::PageSize,
+    pipeline::ImagePipeline,
+    search::SearchResult,
+    selection::{self, Selection},
+    state::{DownloadState, IssueListing, IssueRecord, IssueStatus},
+    volume::{self, Chapter},
+};
 
-impl std::error::Error for SiteDownloaderError {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        None
-    }
+pub use crate::error::SiteDownloaderError;
 
-    fn description(&self) -> &str {
-        "description() is deprecated; use Display"
-    }
+use crate::pages::{save_page, DownloadedPages};
 
-    fn cause(&self) -> Option<&dyn std::error::Error> {
-        self.source()
-    }
+use registry::{comic_slug, Capabilities, Site, SiteRegistry};
 
-}
+pub mod readcomic_me;
+pub mod zerocalcare_net;
+pub mod scanita_org;
+pub mod declarative;
+pub mod registry;
+#[cfg(feature = "plugins")]
+pub mod plugin;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Issue{
     pub name: String,
     pub link: String,
 }
 
-#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum OutputFormats{
     Pdf,
     Cbz,
+    Epub,
+    /// epub for the reader of the Kobo e-readers
+    Kepub,
 }
 
 impl OutputFormats {
-    fn format_string(&self) -> &str {
+    /// Extension of the files created with this format
+    pub fn format_string(&self) -> &str {
         match self{
             OutputFormats::Pdf => "pdf",
             OutputFormats::Cbz => "cbz",
+            OutputFormats::Epub => "epub",
+            OutputFormats::Kepub => "kepub.epub",
         }
     
     }
+
+    pub fn all() -> [OutputFormats; 4] {
+        return [OutputFormats::Pdf, OutputFormats::Cbz, OutputFormats::Epub, OutputFormats::Kepub];
+    }
 }
 
-#[allow(dead_code)]
+/// Name of every issue or volume downloaded with the result of its download
+pub type DownloadResults = Vec<(String, Result<(), SiteDownloaderError>)>;
+
 pub struct ComicUrl{
     pub url: String,
-    pub client: Client,
+    pub client: HttpClient,
     pub download_path: PathBuf,
     pub comic_name: String,
     pub format: OutputFormats,
     pub site_downloader: Box<dyn ComicDownloader>,
     pub skip_first: usize,
     pub skip_last: usize,
+    /// processing of the pages before packing them
+    pub pipeline: ImagePipeline,
+    /// size of the pages of the pdf volumes
+    pub page_size: PageSize,
+    /// issues packed in each omnibus volume, 0 for all of them, `None` for
+    /// a volume per issue
+    pub omnibus: Option<usize>,
+    /// the issues of the list are downloaded only if they match all of them
+    pub selection: Vec<Selection>,
+    /// issues already downloaded in `download_path`
+    pub state: Mutex<DownloadState>,
 }
 
-#[allow(dead_code)]
 impl ComicUrl {
 
     pub fn new(url: &str) -> Result<ComicUrl, SiteDownloaderError> {
         let url = url
             .to_string();
-        let client = reqwest::blocking::Client::new();
+        let client = HttpClient::new();
         let site_downloader = identify_website(&url)?;
-        let comic_name = site_downloader.get_comic_name(&client,&url)
-            .to_string();
-        let download_path = PathBuf::from_str(&comic_name)
-            .expect("cannot create download folder");
+        let comic_name = site_downloader.get_comic_name(&client,&url)?;
+        check_name(&url, &comic_name)?;
+        let download_path = PathBuf::from(&comic_name);
         let format = OutputFormats::Cbz;
         let skip_first = 0;
         let skip_last = 0;
+        let pipeline = ImagePipeline::default();
+        let page_size = PageSize::default();
+        let omnibus = None;
+        let selection = Vec::new();
+        let state = Mutex::new(DownloadState::load(&download_path)?);
 
         return Ok(ComicUrl{ 
             url,
@@ -107,73 +118,347 @@ impl ComicUrl {
             format,
             site_downloader,
             skip_first,
-            skip_last, });
+            skip_last,
+            pipeline,
+            page_size,
+            omnibus,
+            selection,
+            state, });
     }
 
+    /// Downloads every new issue one after the other, an issue that fails is
+    /// reported and skipped so the others are still downloaded
     pub fn download_all(&self) -> Result<(), SiteDownloaderError> {
-        let issues = self.get_issues_list()?;
-        issues.iter().for_each(|e| {
-            self.site_downloader.download_issue(&self.client, &self.download_path, &e).unwrap();
-            self.create_volume(e, &self.download_path.join(e.name.clone())).expect("cannot create volume");
-        });
+        return finish_download(self.download_new(1)?);
+    }
+
+    /// Downloads the new issues, or the omnibus volumes with new issues, and
+    /// returns the result of each by name
+    pub fn download_new(&self, jobs_quantity: usize) -> Result<DownloadResults, SiteDownloaderError> {
+        let listed = self.site_downloader.get_issues_list(&self.client, &self.url)?;
+        self.import_legacy_volumes(&listed)?;
+        if self.selection.iter().any(|e| e.has_new()) && self.state.lock().unwrap().listed.is_empty() {
+            println!("first download with new: the issues on the website are recorded, the ones that come out after them will be downloaded");
+        }
+        let issues = self.select_issues(listed.clone());
+        let mut failed = Vec::new();
+        let results: DownloadResults = match self.omnibus {
+            Some(size) => {
+                let volumes = self.new_volumes(&listed, &issues, size)?;
+                self.download_volumes(volumes, jobs_quantity).into_iter()
+                    .map(|(volume, result)| {
+                        if result.is_err() {
+                            failed.extend(volume.issues.iter().map(|e| e.link.clone()));
+                        }
+                        (volume.name(), result)
+                    })
+                    .collect()
+            },
+            None => {
+                let issues = self.new_issues(issues)?;
+                self.download_issues(issues, jobs_quantity).into_iter()
+                    .map(|(issue, result)| {
+                        if result.is_err() {
+                            failed.push(issue.link.clone());
+                        }
+                        (issue.name, result)
+                    })
+                    .collect()
+            },
+        };
+        self.remember_listed(&listed, &failed)?;
+        return Ok(results);
+    }
+
+    /// Downloads a single issue and creates its volume
+    pub fn download_issue(&self, issue: &Issue) -> Result<(), SiteDownloaderError> {
+        let issue_path = self.fetch_issue(issue)?;
+        let page_count = pages::page_files(&issue_path)?.len();
+        let out_path = self.create_volume(issue, &issue_path)
+            .map_err(|e| e.in_issue(&issue.name))?;
+        IssueRecord::new(issue, page_count, self.format, &out_path)
+            .and_then(|record| self.record_issue(record))
+            .map_err(|e| e.in_issue(&issue.name))?;
         return Ok(());
     }
 
-    pub fn create_volume(&self, issue_name: &Issue, issue_path: &PathBuf) -> Result<(), SiteDownloaderError> {
-        let out_filename = format!("{}-{}.{}", self.comic_name, issue_name.name, self.format.format_string());
-        let files: Vec<_> = fs::read_dir(&issue_path).expect("could not read files")
-            .filter_map(|entry| entry.ok())
-            .filter_map(|entry| entry.path().to_str().map(|s| s.to_string()))
+    /// Downloads the pages of the issue and checks them, returns the
+    /// directory they are in
+    fn fetch_issue(&self, issue: &Issue) -> Result<PathBuf, SiteDownloaderError> {
+        check_name(&issue.link, &issue.name).map_err(|e| e.in_issue(&issue.name))?;
+        self.site_downloader
+            .download_issue(&self.client, &self.download_path, issue)
+            .map_err(|e| e.in_issue(&issue.name))?;
+        let issue_path = self.download_path.join(&issue.name);
+        pages::verify_issue(&issue_path).map_err(|e| e.in_issue(&issue.name))?;
+        return Ok(issue_path);
+    }
+
+    /// The omnibus volumes of `size` issues with at least an issue that
+    /// wasn't downloaded yet, the volumes are numbered from the first issue
+    /// of the list of the website so the skipped issues don't change them
+    pub fn get_new_volumes(&self, size: usize) -> Result<Vec<Omnibus>, SiteDownloaderError> {
+        let listed = self.site_downloader.get_issues_list(&self.client, &self.url)?;
+        let selected = self.select_issues(listed.clone());
+        return self.new_volumes(&listed, &selected, size);
+    }
+
+    /// The volumes of all the `listed` issues that have one of the `selected`
+    /// ones, with all their issues, and aren't completely downloaded
+    fn new_volumes(&self, listed: &[Issue], selected: &[Issue], size: usize) -> Result<Vec<Omnibus>, SiteDownloaderError> {
+        let mut new_volumes = Vec::new();
+        for volume in Omnibus::group(listed, size) {
+            if !volume.issues.iter().any(|e| selected.contains(e)) {
+                continue;
+            }
+            let mut downloaded = true;
+            for issue in &volume.issues {
+                downloaded &= self.is_downloaded(issue)?;
+            }
+            if downloaded {
+                println!("{} was already downloaded", volume.name());
+            } else {
+                new_volumes.push(volume);
+            }
+        }
+        return Ok(new_volumes);
+    }
+
+    /// Downloads every issue of the volume with up to `jobs_quantity`
+    /// threads and packs them together, each issue is a chapter; the old
+    /// version of the volume, with less issues, is replaced
+    pub fn download_volume(&self, volume: &Omnibus, jobs_quantity: usize) -> Result<(), SiteDownloaderError> {
+        let failed: Vec<String> = run_jobs(volume.issues.clone(), jobs_quantity, |issue| self.fetch_issue(issue).map(|_| ()))
+            .into_iter()
+            .filter(|(_, result)| result.is_err())
+            .map(|(issue, _)| issue.name)
             .collect();
-        let out_path = self.download_path.join(out_filename);
-        match self.format{
-            OutputFormats::Pdf => {
-                #[allow(unused_assignments, unused_mut)]
-                let mut result: Option<()> = None;
-                #[cfg(feature = "pdf")] {
-                    let doc =  PdfDocument::empty(&self.comic_name);
-                    for filename in files {
-                        let image = read_image(filename)?;
-                        let w = image.image.width.0 as f32;
-                        let h = image.image.height.0 as f32;
-                        let w_mm = w/300.0 * 25.4;
-                        let h_mm = h/300.0 * 25.4;
-
-                        let (page1, layer1) = doc.add_page(Mm(w_mm), Mm(h_mm), "Layer 1");
-                        let current_layer = doc.get_page(page1).get_layer(layer1);
-                        image.add_to_layer(current_layer.clone(), ImageTransform::default());
+        if !failed.is_empty() {
+            return Err(SiteDownloaderError::IncompleteDownload { failed });
+        }
+        let mut chapters = Vec::new();
+        let mut page_counts = Vec::new();
+        for issue in &volume.issues {
+            let issue_path = self.download_path.join(&issue.name);
+            let files = pages::page_files(&issue_path)?;
+            page_counts.push(files.len());
+            let files = self.pipeline.process_pages(&issue_path, &files)
+                .map_err(|e| e.in_issue(&issue.name))?;
+            chapters.push(Chapter { title: issue.name.clone(), files });
+        }
+        let page_count = chapters.iter().map(|e| e.files.len()).sum();
+        let metadata = volume.metadata(self.issue_metadata(&volume.issues[0], 0), page_count);
+        let out_path = self.download_path
+            .join(format!("{}.{}", volume.file_stem(&self.comic_name), self.format.format_string()));
+        volume::create_omnibus(self.format, &metadata, &chapters, &out_path, self.page_size)?;
+
+        let out_name = out_path.file_name().and_then(|e| e.to_str()).unwrap_or_default().to_string();
+        let prefix = volume.file_prefix(&self.comic_name);
+        for (issue, pages) in volume.issues.iter().zip(page_counts) {
+            let old_record = self.state.lock().unwrap().get(issue).cloned();
+            if let Some(old_record) = old_record.filter(|e| e.file != out_name && e.file.starts_with(&prefix)) {
+                let old_path = old_record.path(&self.download_path);
+                if let Err(e) = fs::remove_file(&old_path) {
+                    if e.kind() != io::ErrorKind::NotFound {
+                        return Err(SiteDownloaderError::file_system(&old_path, e));
                     }
-                    doc.save(&mut io::BufWriter::new(File::create(out_path).unwrap()))
-                        .expect("error creating pdf");
-                    result = Some(());
                 }
-                if result.is_none() {
-                    println!("pdf feature is not enabled");
-                    return Err(SiteDownloaderError::ImageError);
+            }
+            let issue_path = self.download_path.join(&issue.name);
+            fs::remove_dir_all(&issue_path)
+                .map_err(|e| SiteDownloaderError::file_system(&issue_path, e))?;
+            self.record_issue(IssueRecord::new(issue, pages, self.format, &out_path)?)?;
+        }
+        return Ok(());
+    }
+
+    /// Downloads the omnibus volumes one after the other, reporting the
+    /// errors as they happen, and returns the result of every volume in order
+    pub fn download_volumes(&self, volumes: Vec<Omnibus>, jobs_quantity: usize) -> Vec<(Omnibus, Result<(), SiteDownloaderError>)> {
+        return volumes.into_iter()
+            .map(|volume| {
+                let result = self.download_volume(&volume, jobs_quantity);
+                if let Err(e) = &result {
+                    eprintln!("{}: {e}", volume.name());
                 }
+                (volume, result)
+            })
+            .collect();
+    }
+
+    /// Whether the issue was already downloaded, also by versions of
+    /// comic-dl without the state; it doesn't change the state, the volumes
+    /// of those versions are added to it by `import_legacy_volumes`
+    pub fn is_downloaded(&self, issue: &Issue) -> Result<bool, SiteDownloaderError> {
+        if self.state.lock().unwrap().is_downloaded(issue) {
+            return Ok(true);
+        }
+        return Ok(self.legacy_volume(issue).is_some());
+    }
+
+    /// Adds to the state the volumes of `issues` made by versions of comic-dl
+    /// without it
+    pub fn import_legacy_volumes(&self, issues: &[Issue]) -> Result<(), SiteDownloaderError> {
+        for issue in issues {
+            if self.state.lock().unwrap().is_downloaded(issue) {
+                continue;
+            }
+            if let Some((format, path)) = self.legacy_volume(issue) {
+                self.record_issue(IssueRecord::new(issue, 0, format, &path)?)?;
+            }
+        }
+        return Ok(());
+    }
+
+    /// The volume of the issue with the names used before the state
+    fn legacy_volume(&self, issue: &Issue) -> Option<(OutputFormats, PathBuf)> {
+        return OutputFormats::all().into_iter()
+            .map(|format| (format, format!("{}-{}.{}", self.comic_name, issue.name, format.format_string())))
+            .chain([(OutputFormats::Cbz, format!("{}.cbz", issue.name))])
+            .map(|(format, name)| (format, self.download_path.join(name)))
+            .find(|(_, path)| path.is_file());
+    }
+
+    /// Details of the comic from its website
+    pub fn get_comic_metadata(&self) -> Result<ComicMetadata, SiteDownloaderError> {
+        return self.site_downloader.get_comic_metadata(&self.client, &self.url);
+    }
+
+    /// Whether the issue is packed in a volume, was started or was never
+    /// downloaded
+    pub fn issue_status(&self, issue: &Issue) -> Result<IssueStatus, SiteDownloaderError> {
+        if self.is_downloaded(issue)? {
+            return Ok(IssueStatus::Downloaded);
+        }
+        if self.download_path.join(&issue.name).is_dir() {
+            return Ok(IssueStatus::Partial);
+        }
+        return Ok(IssueStatus::Missing);
+    }
+
+    /// The issues of `get_issues_list` with their position in the list of
+    /// the website and their status
+    pub fn list_issues(&self) -> Result<Vec<IssueListing>, SiteDownloaderError> {
+        let listed = self.site_downloader.get_issues_list(&self.client, &self.url)?;
+        let selected = self.select_issues(listed.clone());
+        let mut listing = Vec::new();
+        for (index, issue) in listed.into_iter().enumerate() {
+            if !selected.contains(&issue) {
+                continue;
+            }
+            listing.push(IssueListing {
+                index: index + 1,
+                status: self.issue_status(&issue)?,
+                name: issue.name,
+                link: issue.link,
+            });
+        }
+        return Ok(listing);
+    }
+
+    /// The issues of `get_issues_list` that weren't downloaded yet
+    pub fn get_new_issues(&self) -> Result<Vec<Issue>, SiteDownloaderError> {
+        return self.new_issues(self.get_issues_list()?);
+    }
+
+    fn new_issues(&self, issues: Vec<Issue>) -> Result<Vec<Issue>, SiteDownloaderError> {
+        let mut new_issues = Vec::new();
+        for issue in issues {
+            if self.is_downloaded(&issue)? {
+                println!("{} was already downloaded", issue.name);
+            } else {
+                new_issues.push(issue);
+            }
+        }
+        return Ok(new_issues);
+    }
+
+    /// Metadata of the issue from the strategy, if the website can't give
+    /// it the one from the names is used
+    pub fn issue_metadata(&self, issue: &Issue, page_count: usize) -> IssueMetadata {
+        let mut metadata = self.site_downloader
+            .get_issue_metadata(&self.client, &self.comic_name, issue)
+            .unwrap_or_else(|e| {
+                eprintln!("couldn't get the metadata of issue {}: {e}", issue.name);
+                IssueMetadata::new(&self.comic_name, issue)
+            });
+        metadata.page_count = page_count;
+        return metadata;
+    }
+
+    /// Saves the links on the website for `new`, the ones of the issues that
+    /// `failed` stay new until they are downloaded
+    fn remember_listed(&self, listed: &[Issue], failed: &[String]) -> Result<(), SiteDownloaderError> {
+        create_dir_if_missing(&self.download_path)?;
+        let mut state = self.state.lock().unwrap();
+        let old_listed = std::mem::take(&mut state.listed);
+        state.listed = listed.iter()
+            .map(|e| e.link.clone())
+            .filter(|link| !failed.contains(link) || old_listed.contains(link))
+            .collect();
+        state.save(&self.download_path)?;
+        return Ok(());
+    }
+
+    fn record_issue(&self, record: IssueRecord) -> Result<(), SiteDownloaderError> {
+        create_dir_if_missing(&self.download_path)?;
+        let mut state = self.state.lock().unwrap();
+        state.insert(record);
+        state.save(&self.download_path)?;
+        return Ok(());
+    }
+
+    /// Packs the pages in `issue_path` in the format chosen and returns the
+    /// path of the volume
+    pub fn create_volume(&self, issue_name: &Issue, issue_path: &Path) -> Result<PathBuf, SiteDownloaderError> {
+        let out_filename = format!("{}-{}.{}", self.comic_name, issue_name.name, self.format.format_string());
+        let files = self.pipeline.process_pages(issue_path, &pages::page_files(issue_path)?)?;
+        let out_path = self.download_path.join(out_filename);
+        match self.format{
+            OutputFormats::Pdf => {
+                let metadata = self.issue_metadata(issue_name, files.len());
+                volume::create_pdf(&metadata, &files, &out_path, self.page_size)?
             },
             OutputFormats::Cbz => {
-                let file = File::create(&out_path).expect("error creating output file");
-                let mut zip = zip::ZipWriter::new(file);
-                let options = zip::write::SimpleFileOptions::default()
-                    .compression_method(zip::CompressionMethod::Deflated);
-                for filename in files {
-                    let name = Path::new(&filename).file_name().unwrap().to_str().unwrap();
-                    let mut f = File::open(&filename).expect("error creating cbz");
-                    zip.start_file(name, options).expect("error creating cbz");
-                    io::copy(&mut f, &mut zip).expect("error creating cbz");
-                }
-                zip.finish().expect("error creating cbz");
+                let metadata = self.issue_metadata(issue_name, files.len());
+                volume::create_cbz(&files, &out_path, Some(&metadata))?
+            },
+            OutputFormats::Epub => {
+                let metadata = self.issue_metadata(issue_name, files.len());
+                volume::create_epub(&metadata, &files, &out_path)?
+            },
+            OutputFormats::Kepub => {
+                let metadata = self.issue_metadata(issue_name, files.len());
+                volume::create_kepub(&metadata, &files, &out_path)?
             },
         }
-        fs::remove_dir_all(&issue_path).expect("couldn't clean source directory");
-        return Ok(());
+        fs::remove_dir_all(issue_path)
+            .map_err(|e| SiteDownloaderError::file_system(issue_path, e))?;
+        return Ok(out_path);
+    }
+
+    /// Downloads every new issue using `jobs_quantity` threads, each thread
+    /// downloads a whole issue and creates its volume
+    pub fn download_all_multithread(&self, jobs_quantity: usize) -> Result<(), SiteDownloaderError> {
+        println!("starting download with {jobs_quantity} threads" );
+        return finish_download(self.download_new(jobs_quantity)?);
+    }
+
+    /// Downloads `issues` with up to `jobs_quantity` threads, reporting the
+    /// errors as they happen, and returns the result of every issue in order
+    pub fn download_issues(&self, issues: Vec<Issue>, jobs_quantity: usize) -> Vec<(Issue, Result<(), SiteDownloaderError>)> {
+        return run_jobs(issues, jobs_quantity, |issue| self.download_issue(issue));
     }
 
     pub fn change_path(&mut self, new_path_str: &str) -> Result<(), SiteDownloaderError> {
         let new_path = Path::new(new_path_str);
-        if !new_path.exists() { return Err(SiteDownloaderError::FileSystemError) };
+        if !new_path.exists() {
+            return Err(SiteDownloaderError::file_system(
+                new_path,
+                io::Error::new(io::ErrorKind::NotFound, "download path doesn't exist")));
+        };
         self.download_path = new_path.to_path_buf();
+        self.state = Mutex::new(DownloadState::load(&self.download_path)?);
         return Ok(());
     }
 
@@ -181,6 +466,19 @@ impl ComicUrl {
         self.format = new_format;
     }
 
+    pub fn change_retry_policy(&mut self, retry: RetryPolicy) {
+        self.client.change_retry_policy(retry);
+    }
+
+    /// Limit of every host without one of its own
+    pub fn change_rate_limit(&mut self, limit: RateLimit) {
+        self.client.change_default_rate_limit(limit);
+    }
+
+    pub fn change_host_rate_limit(&mut self, host: &str, limit: RateLimit) {
+        self.client.change_host_rate_limit(host, limit);
+    }
+
     pub fn change_skip_first(&mut self, skip: usize) {
         self.skip_first = skip;
     }
@@ -189,87 +487,227 @@ impl ComicUrl {
         self.skip_last = skip;
     }
 
+    pub fn change_pipeline(&mut self, pipeline: ImagePipeline) {
+        self.pipeline = pipeline;
+    }
+
+    pub fn change_page_size(&mut self, page_size: PageSize) {
+        self.page_size = page_size;
+    }
+
+    /// Downloads only the issues that match all the selections
+    pub fn change_selection(&mut self, selection: Vec<Selection>) {
+        self.selection = selection;
+    }
+
+    /// Packs `issues` issues in each volume, 0 for all of them, `None` for
+    /// a volume per issue
+    pub fn change_omnibus(&mut self, issues: Option<usize>) {
+        self.omnibus = issues;
+    }
+
+    /// The issues on the website without the skipped ones and the ones
+    /// that don't match the selection
     pub fn get_issues_list(&self) -> Result<Vec<Issue>, SiteDownloaderError> {
         let result: Vec<Issue> = self.site_downloader.get_issues_list(&self.client, &self.url)?;
+        return Ok(self.select_issues(result));
+    }
+
+    fn select_issues(&self, result: Vec<Issue>) -> Vec<Issue> {
         let total_issues = result.len();
 
         if total_issues == 0 {
-            return Ok(vec![]);
+            return vec![];
         }
         let start = self.skip_first.min(total_issues); 
         let end = total_issues.saturating_sub(self.skip_last); 
         if start >= end {
-            return Ok(vec![]);
+            return vec![];
         }
         let sliced_result = result[start..end].to_vec();
 
-        Ok(sliced_result)
+        let listed = self.state.lock().unwrap().listed.clone();
+        return selection::select(&self.selection, sliced_result, &listed);
     }
 }
 
-#[cfg(feature = "pdf")]
-fn read_image(filename: String) -> Result<Image,SiteDownloaderError> {
-    let mut image_file = File::open(filename).expect("error opening file");
-    let mut buffer = Vec::new();
-    image_file.read_to_end(&mut buffer).unwrap();
-    let format = img::guess_format(&buffer).unwrap();
-    let cursor = Cursor::new(buffer);
-    let image = match format{
-        ::image::ImageFormat::Png => {
-            Image::try_from( 
-                image_crate::codecs::png::PngDecoder::new(cursor)
-                    .expect("couldn't decode image"))
-                .unwrap()
-        },
-        ::image::ImageFormat::Jpeg => {
-            Image::try_from( 
-                image_crate::codecs::jpeg::JpegDecoder::new(cursor)
-                    .expect("couldn't decode image"))
-                .unwrap()
-        },
-        ::image::ImageFormat::WebP => {
-            let webp_image = img::load(cursor, img::ImageFormat::WebP).unwrap();
-            let mut png_bytes: Vec<u8> = Vec::new();
-            webp_image.write_to(
-                &mut Cursor::new(&mut png_bytes),
-                img::ImageFormat::Png)
-                .unwrap();
-            let png_cursor = Cursor::new(png_bytes);
-            Image::try_from( 
-                image_crate::codecs::png::PngDecoder::new(png_cursor)
-                    .expect("couldn't decode image"))
-                .unwrap()
-        },
-        _ => return Err(SiteDownloaderError::ImageError),
-    };
-    Ok(image)
+/// Chooses the strategy to download from the website of `url`, among the
+/// builtin websites and the site definitions of the user
+pub fn identify_website(url: &str) -> Result<Box<dyn ComicDownloader>, SiteDownloaderError> {
+    return SiteRegistry::load().identify(url);
 }
 
-fn identify_website(url: &str) -> Result<Box<dyn ComicDownloader>, SiteDownloaderError> {
-    match reqwest::Url::parse(url){
-        Ok(parsed_url) => {
-            match parsed_url.domain().unwrap() {
-                "readcomic.me" => { return Ok(Box::new(ReadcomicMeStrategy))},
-                "www.zerocalcare.net" => { return Ok(Box::new(ZerocalcareNetStrategy))},
-                "scanita.org" => { return Ok(Box::new(ScanitaOrgStrategy))},
-                _ => {return Err(SiteDownloaderError::ParsingError)} 
+/// List of the supported websites, one per line
+pub fn print_supported_websites() -> String {
+    return SiteRegistry::load().describe();
+}
+
+/// Runs `job` on every item with up to `jobs_quantity` threads, reporting
+/// the errors as they happen, and returns the result of every item in order
+fn run_jobs<T: Send>(
+    items: Vec<T>,
+    jobs_quantity: usize,
+    job: impl Fn(&T) -> Result<(), SiteDownloaderError> + Sync,
+) -> Vec<(T, Result<(), SiteDownloaderError>)> {
+    let jobs_quantity = jobs_quantity.max(1);
+    let job = &job;
+    let mut results = Vec::new();
+    thread::scope(|scope| {
+        let mut handles = VecDeque::new();
+        for item in items {
+            if handles.len() == jobs_quantity {
+                results.push(join_job(handles.pop_front().unwrap()));
             }
-        }, 
-        Err(_) => {return Err(SiteDownloaderError::ParsingError)},
+            handles.push_back(scope.spawn(move || {
+                let result = job(&item);
+                (item, result)
+            }));
+        }
+        for handle in handles{
+            results.push(join_job(handle));
+        }
+    });
+    return results;
+}
+
+/// Waits for the thread of a job, reporting its error if it failed
+fn join_job<T>(handle: thread::ScopedJoinHandle<(T, Result<(), SiteDownloaderError>)>) -> (T, Result<(), SiteDownloaderError>) {
+    let (item, result) = handle.join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+    if let Err(e) = &result {
+        eprintln!("{e}");
     }
+    return (item, result);
 }
 
-pub fn print_supported_websites() -> String {
-return r#"
-- https://readcomic.me
-- https://www.zerocalcare.net
-- https://scanita.org
-"#.to_string();
+fn finish_download(results: DownloadResults) -> Result<(), SiteDownloaderError> {
+    let failed: Vec<String> = results.into_iter()
+        .filter(|(_, result)| result.is_err())
+        .map(|(name, _)| name)
+        .collect();
+    if failed.is_empty() {
+        return Ok(());
+    }
+    return Err(SiteDownloaderError::IncompleteDownload { failed });
+}
+
+/// Checks that the name of a comic or an issue from the website of `url` is
+/// a single directory of the download path: not empty, not `.` or `..` and
+/// without separators, so its directory can't be outside of it
+pub(crate) fn check_name(url: &str, name: &str) -> Result<(), SiteDownloaderError> {
+    let mut components = Path::new(name).components();
+    let single = match (components.next(), components.next()) {
+        (Some(Component::Normal(component)), None) => component == name,
+        _ => false,
+    };
+    if !single || name.trim().is_empty() || name.contains(['/', '\\']) {
+        return Err(SiteDownloaderError::parsing(url, format!("{name:?} can't be the name of a directory")));
+    }
+    return Ok(());
+}
+
+/// Creates `path` unless it's already there, also when another thread creates it first
+pub(crate) fn create_dir_if_missing(path: &Path) -> Result<(), SiteDownloaderError> {
+    if let Err(e) = fs::create_dir_all(path) {
+        if !path.exists() {
+            return Err(SiteDownloaderError::file_system(path, e));
+        }
+    }
+    return Ok(());
 }
 
 pub trait ComicDownloader: Send + Sync + Debug {
-    fn download_issue(&self, client: &Client, download_path: &PathBuf, issue: &Issue) -> Result<(), SiteDownloaderError>;
-    fn download_page(&self, client: &Client, link: &str, issue_path: &Path, page_number: u32) -> Result<(), SiteDownloaderError>;
-    fn get_issues_list(&self, client: &Client, url: &str) -> Result<Vec<Issue>, SiteDownloaderError>;
-    fn get_comic_name(&self, client: &Client,  url: &str) -> String;
+    fn download_issue(&self, client: &HttpClient, download_path: &Path, issue: &Issue) -> Result<(), SiteDownloaderError>;
+    fn download_page(&self, client: &HttpClient, link: &str, issue_path: &Path, page_number: u32) -> Result<(), SiteDownloaderError>;
+    fn get_issues_list(&self, client: &HttpClient, url: &str) -> Result<Vec<Issue>, SiteDownloaderError>;
+    fn get_comic_name(&self, client: &HttpClient,  url: &str) -> Result<String, SiteDownloaderError>;
+
+    /// Metadata of the issue for the volume, by default only what can be
+    /// known from the names
+    fn get_issue_metadata(&self, _client: &HttpClient, comic_name: &str, issue: &Issue) -> Result<IssueMetadata, SiteDownloaderError> {
+        return Ok(IssueMetadata::new(comic_name, issue));
+    }
+
+    /// Whether the website can search comics by title
+    fn supports_search(&self) -> bool {
+        return false;
+    }
+
+    /// The comics of the website whose title matches `query`, only for the
+    /// websites that support search
+    fn search(&self, _client: &HttpClient, _query: &str) -> Result<Vec<SearchResult>, SiteDownloaderError> {
+        return Ok(Vec::new());
+    }
+
+    /// Details of the comic, by default the name and the number of issues
+    fn get_comic_metadata(&self, client: &HttpClient, url: &str) -> Result<ComicMetadata, SiteDownloaderError> {
+        return Ok(ComicMetadata {
+            title: self.get_comic_name(client, url)?,
+            url: url.to_string(),
+            issue_count: Some(self.get_issues_list(client, url)?.len()),
+            ..ComicMetadata::default()
+        });
+    }
+}
+
+/// The details of the comic in the OpenGraph tags of its page, that most
+/// websites have for the previews of the links
+pub(crate) fn open_graph_metadata(document: &scraper::Html, url: &str) -> ComicMetadata {
+    let property = |name: &str| {
+        let selector = scraper::Selector::parse(&format!("meta[property='{name}']")).unwrap();
+        return document.select(&selector).next()
+            .and_then(|e| e.value().attr("content"))
+            .map(|e| e.trim().to_string())
+            .filter(|e| !e.is_empty());
+    };
+    return ComicMetadata {
+        title: property("og:title").unwrap_or_default(),
+        url: url.to_string(),
+        cover_url: property("og:image"),
+        description: property("og:description"),
+        last_update: property("article:modified_time").or_else(|| property("og:updated_time")),
+        ..ComicMetadata::default()
+    };
+}
+
+/// The links in the page at `page_url` to the comics of `site`: the ones
+/// to the same host whose path is `prefix` followed by a single name, like
+/// `/comic/batman`
+pub(crate) fn comic_links(body: &str, page_url: &str, site: &str, prefix: &str) -> Result<Vec<SearchResult>, SiteDownloaderError> {
+    let page_url = reqwest::Url::parse(page_url)
+        .map_err(|_| SiteDownloaderError::InvalidUrl { url: page_url.to_string() })?;
+    let document = scraper::Html::parse_document(body);
+    let selector = scraper::Selector::parse("a[href]").unwrap();
+    let mut results: Vec<SearchResult> = Vec::new();
+    for link in document.select(&selector) {
+        let Some(url) = link.value().attr("href").and_then(|e| page_url.join(e).ok()) else {
+            continue;
+        };
+        if url.host() != page_url.host() || url.port_or_known_default() != page_url.port_or_known_default() {
+            continue;
+        }
+        let Some(name) = url.path().strip_prefix(prefix) else {
+            continue;
+        };
+        let name = name.trim_end_matches('/');
+        if name.is_empty() || name.contains('/') {
+            continue;
+        }
+        let title = link.value().attr("title")
+            .map(|e| e.trim().to_string())
+            .filter(|e| !e.is_empty())
+            .or_else(|| element_text(link));
+        let url = format!("{}{prefix}{name}", url.origin().ascii_serialization());
+        if let Some(title) = title.filter(|_| results.iter().all(|e| e.url != url)) {
+            results.push(SearchResult { site: site.to_string(), title, url });
+        }
+    }
+    return Ok(results);
+}
+
+/// The text of the element with the spaces collapsed, `None` if it's empty
+pub(crate) fn element_text(element: scraper::ElementRef) -> Option<String> {
+    let text = element.text().collect::<Vec<_>>().join(" ");
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    return Some(text).filter(|e| !e.is_empty());
 }
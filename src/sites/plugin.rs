@@ -0,0 +1,547 @@
+//! strategies in WebAssembly modules, for the websites that can't be
+//! described by a site definition and will never be part of comic-dl
+//!
+//! a plugin is a `.wasm` file in the `plugins` directory of the configuration,
+//! it runs in a sandbox: it can only call the functions of the host below,
+//! every call has a limited amount of fuel and the memory is limited. Strings
+//! are passed as a pointer and a length in the memory of the plugin, the
+//! results as an i64 with the pointer in the high 32 bits and the length in
+//! the low ones
+//!
+//! the plugin exports:
+//! - `memory` and `alloc(len: i32) -> i32`, used by the host to write strings
+//! - `api_version() -> i32`, [`API_VERSION`]
+//! - `site() -> i64`, json like `{"name": "example", "domains": ["example.com"]}`,
+//!   with the optional `example`, `hosts` (other domains it can request, like
+//!   the one of the images), `language` and `comic_pattern` (regex the links
+//!   to the comics must match)
+//! - `issues(url) -> i64`, the issues of the comic as `[{"name": "1", "link": "..."}]`
+//! - `pages(issue_link) -> i64`, the links of the images of the issue
+//! - optionally `comic_name(url)`, `search(query)` returning
+//!   `[{"title": "...", "url": "..."}]` and `metadata(url)` returning the
+//!   details of the comic like in `comic-dl info --json`
+//!
+//! every export except `site` returns `{"ok": <result>}` or `{"error": "<reason>"}`
+//!
+//! the host functions, in the module `comic_dl`:
+//! - `http_get(url_ptr, url_len) -> i64`, the body of the page, only from the
+//!   domains and the hosts of the plugin, also after the redirects; the
+//!   images of `pages` must be on them too
+//! - `html_select(html_ptr, html_len, selector_ptr, selector_len, attribute_ptr, attribute_len) -> i64`,
+//!   json array with the attribute of the matching elements, or their text
+//!   when the attribute is empty
+//! - `regex_captures(pattern_ptr, pattern_len, text_ptr, text_len) -> i64`, json
+//!   array with an object for every match, with the named groups and the
+//!   others by number
+//! - `last_error() -> i64`, why the last host function returned -1
+//! - `log(ptr, len)`, prints the message
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use regex::Regex;
+use scraper::{Html, Selector};
+use serde_json::{Map, Value};
+use wasmi::{Caller, Config, Engine, Extern, Instance, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+use super::{registry::domain_matches, *};
+
+/// Version of the interface between comic-dl and the plugins
+pub const API_VERSION: i32 = 1;
+
+/// Instructions a plugin can run in a single call
+const FUEL_PER_CALL: u64 = 2_000_000_000;
+
+/// Most memory a plugin can use
+const MAX_MEMORY: usize = 256 * 1024 * 1024;
+
+/// Redirects followed by a request of a plugin
+const MAX_REDIRECTS: usize = 10;
+
+/// Longest string a plugin can pass to the host or return
+const MAX_STRING: usize = 64 * 1024 * 1024;
+
+/// The website of a plugin as returned by its `site` export
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginManifest {
+    pub name: String,
+    pub domains: Vec<String>,
+    #[serde(default)]
+    pub example: Option<String>,
+    /// other domains the plugin can request, like the one of the images
+    #[serde(default)]
+    pub hosts: Vec<String>,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub comic_pattern: Option<String>,
+    /// link of a comic that `doctor` checks the plugin on
+    #[serde(default)]
+    pub canary: Option<String>,
+}
+
+/// What the host functions can use while the plugin runs
+struct HostState {
+    client: HttpClient,
+    allowed_hosts: Vec<String>,
+    last_error: String,
+    limits: StoreLimits,
+}
+
+struct Runtime {
+    store: Store<HostState>,
+    instance: Instance,
+    fuel: u64,
+}
+
+/// A strategy in a WebAssembly module, the copies share the same instance
+#[derive(Clone)]
+pub struct PluginStrategy {
+    pub path: PathBuf,
+    pub manifest: PluginManifest,
+    comic_pattern: Option<Regex>,
+    runtime: Arc<Mutex<Runtime>>,
+    /// the client for the requests of the plugin, built once
+    sandbox: HttpClient,
+}
+
+impl Debug for PluginStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return f.debug_struct("PluginStrategy")
+            .field("path", &self.path)
+            .field("manifest", &self.manifest)
+            .finish_non_exhaustive();
+    }
+}
+
+impl PluginStrategy {
+    /// Loads the plugin in `path`
+    pub fn load(path: &Path) -> Result<PluginStrategy, SiteDownloaderError> {
+        let wasm = fs::read(path).map_err(|e| SiteDownloaderError::file_system(path, e))?;
+        return PluginStrategy::from_bytes(path, &wasm);
+    }
+
+    /// Instantiates the module in `wasm` and reads its website, `path` is
+    /// used in the errors
+    pub fn from_bytes(path: &Path, wasm: &[u8]) -> Result<PluginStrategy, SiteDownloaderError> {
+        let error = |reason: String| SiteDownloaderError::Plugin { path: path.to_path_buf(), reason };
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, wasm).map_err(|e| error(format!("invalid module: {e}")))?;
+        let state = HostState {
+            client: HttpClient::new(),
+            allowed_hosts: Vec::new(),
+            last_error: String::new(),
+            limits: StoreLimitsBuilder::new().memory_size(MAX_MEMORY).instances(1).build(),
+        };
+        let mut store = Store::new(&engine, state);
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(FUEL_PER_CALL).map_err(|e| error(e.to_string()))?;
+        let linker = host_functions(&engine).map_err(|e| error(e.to_string()))?;
+        let instance = linker.instantiate(&mut store, &module)
+            .and_then(|e| e.start(&mut store))
+            .map_err(|e| error(format!("couldn't start the plugin: {e}")))?;
+        for export in ["memory", "alloc", "api_version", "site", "issues", "pages"] {
+            if instance.get_export(&store, export).is_none() {
+                return Err(error(format!("the plugin doesn't export {export}")));
+            }
+        }
+        let version = instance.get_typed_func::<(), i32>(&store, "api_version")
+            .and_then(|e| e.call(&mut store, ()))
+            .map_err(|e| error(format!("api_version failed: {e}")))?;
+        if version != API_VERSION {
+            return Err(error(format!("the plugin is for version {version} of the interface, comic-dl has version {API_VERSION}")));
+        }
+        let mut runtime = Runtime { store, instance, fuel: FUEL_PER_CALL };
+        let site = runtime.call("site", None).map_err(error)?;
+        let manifest: PluginManifest = serde_json::from_str(&site).map_err(|e| error(format!("invalid site: {e}")))?;
+        if manifest.domains.is_empty() {
+            return Err(error("the site has no domains".to_string()));
+        }
+        let comic_pattern = manifest.comic_pattern.as_deref()
+            .map(|e| Regex::new(e).map_err(|reason| error(format!("invalid comic_pattern \"{e}\": {reason}"))))
+            .transpose()?;
+        let hosts: Vec<String> = manifest.domains.iter().chain(&manifest.hosts).cloned().collect();
+        let sandbox = sandboxed(hosts.clone()).map_err(|e| error(format!("couldn't build the http client: {e}")))?;
+        runtime.store.data_mut().allowed_hosts = hosts;
+        return Ok(PluginStrategy {
+            path: path.to_path_buf(),
+            manifest,
+            comic_pattern,
+            runtime: Arc::new(Mutex::new(runtime)),
+            sandbox,
+        });
+    }
+
+    /// The website of the plugin for the registry
+    pub fn site(self) -> Site {
+        let domains: Vec<&str> = self.manifest.domains.iter().map(|e| e.as_str()).collect();
+        let example = self.manifest.example.clone()
+            .unwrap_or_else(|| format!("https://{}/", domains[0].trim_start_matches("*.")));
+        let mut site = Site::new(&self.manifest.name, &domains, &example, {
+            let strategy = self.clone();
+            move || Box::new(strategy.clone())
+        });
+        site.change_capabilities(Capabilities { search: self.exports("search"), metadata: self.exports("metadata") });
+        if let Some(pattern) = self.comic_pattern.clone() {
+            site.change_validator(move |url| pattern.is_match(url.as_str()));
+        }
+        if let Some(canary) = &self.manifest.canary {
+            site.change_canary(canary);
+        }
+        return site;
+    }
+
+    /// Instructions the plugin can run in a single call, by default two billions
+    pub fn change_fuel(&mut self, fuel: u64) {
+        self.runtime.lock().unwrap().fuel = fuel;
+    }
+
+    fn exports(&self, name: &str) -> bool {
+        let runtime = self.runtime.lock().unwrap();
+        return runtime.instance.get_export(&runtime.store, name).is_some();
+    }
+
+    /// Calls `export` with `argument` and returns the result in its envelope
+    fn call(&self, client: &HttpClient, export: &str, argument: &str) -> Result<Value, SiteDownloaderError> {
+        let error = |reason: String| SiteDownloaderError::Plugin { path: self.path.clone(), reason: format!("{export}: {reason}") };
+        let client = self.sandbox.with_limits_of(client);
+        let mut runtime = self.runtime.lock().unwrap();
+        runtime.store.data_mut().client = client;
+        let result = runtime.call(export, Some(argument)).map_err(error)?;
+        let mut envelope: Map<String, Value> = serde_json::from_str(&result)
+            .map_err(|e| error(format!("invalid result: {e}")))?;
+        if let Some(value) = envelope.remove("ok") {
+            return Ok(value);
+        }
+        let reason = envelope.remove("error")
+            .map(|e| e.as_str().map(|e| e.to_string()).unwrap_or_else(|| e.to_string()))
+            .unwrap_or_else(|| "the result has neither ok nor error".to_string());
+        return Err(error(reason));
+    }
+
+    /// Calls `export` and reads its result as `T`
+    fn call_as<T: serde::de::DeserializeOwned>(&self, client: &HttpClient, export: &str, argument: &str) -> Result<T, SiteDownloaderError> {
+        let value = self.call(client, export, argument)?;
+        return serde_json::from_value(value).map_err(|e| SiteDownloaderError::Plugin {
+            path: self.path.clone(),
+            reason: format!("{export}: unexpected result: {e}"),
+        });
+    }
+}
+
+impl Runtime {
+    /// Calls `export`, with a string argument when given, and reads the
+    /// string it returns
+    fn call(&mut self, export: &str, argument: Option<&str>) -> Result<String, String> {
+        self.store.set_fuel(self.fuel).map_err(|e| e.to_string())?;
+        let result = match argument {
+            Some(argument) => {
+                let memory = self.instance.get_memory(&self.store, "memory").ok_or("the plugin doesn't export memory")?;
+                let alloc = self.instance.get_typed_func::<i32, i32>(&self.store, "alloc").map_err(|e| e.to_string())?;
+                let pointer = alloc.call(&mut self.store, argument.len() as i32).map_err(|e| e.to_string())?;
+                memory.write(&mut self.store, pointer as u32 as usize, argument.as_bytes()).map_err(|e| e.to_string())?;
+                self.instance.get_typed_func::<(i32, i32), i64>(&self.store, export)
+                    .and_then(|e| e.call(&mut self.store, (pointer, argument.len() as i32)))
+            },
+            None => self.instance.get_typed_func::<(), i64>(&self.store, export)
+                .and_then(|e| e.call(&mut self.store, ())),
+        };
+        let result = result.map_err(|e| e.to_string())?;
+        let memory = self.instance.get_memory(&self.store, "memory").ok_or("the plugin doesn't export memory")?;
+        let (pointer, length) = unpack(result);
+        let bytes = memory_slice(memory.data(&self.store), pointer, length).map_err(|e| format!("invalid result: {e}"))?;
+        return String::from_utf8(bytes.to_vec()).map_err(|e| format!("invalid result: {e}"));
+    }
+}
+
+impl ComicDownloader for PluginStrategy {
+    fn download_issue(&self, client: &HttpClient, download_path: &Path, issue: &Issue) -> Result<(), SiteDownloaderError> {
+        check_name(&issue.link, &issue.name)?;
+        create_dir_if_missing(download_path)?;
+        println!("downloading {}", issue.name);
+        let issue_path = download_path.join(&issue.name);
+        create_dir_if_missing(&issue_path)?;
+        let downloaded = DownloadedPages::load(&issue_path);
+        let images: Vec<String> = self.call_as(client, "pages", &issue.link)?;
+        if images.is_empty() {
+            return Err(SiteDownloaderError::parsing(&issue.link, "no images in the issue"));
+        }
+        let hosts = self.runtime.lock().unwrap().store.data().allowed_hosts.clone();
+        let images: Vec<String> = images.iter().map(|e| join_link(&issue.link, e)).collect();
+        if let Some(image) = images.iter().find(|e| !is_allowed(&hosts, e)) {
+            return Err(SiteDownloaderError::Plugin {
+                path: self.path.clone(),
+                reason: format!("pages: {image} is not one of the domains or the hosts of the plugin"),
+            });
+        }
+        let client = self.sandbox.with_limits_of(client);
+        for (index, image) in images.iter().enumerate() {
+            let page_number = index as u32 + 1;
+            if downloaded.contains(page_number) {
+                continue;
+            }
+            self.download_page(&client, image, &issue_path, page_number)
+                .map_err(|e| e.in_page(&issue.name, page_number))?;
+        }
+        return Ok(());
+    }
+
+    fn download_page(&self, client: &HttpClient, link: &str, issue_path: &Path, page_number: u32) -> Result<(), SiteDownloaderError> {
+        save_page(client, link, issue_path, page_number)?;
+        return Ok(());
+    }
+
+    fn get_issues_list(&self, client: &HttpClient, url: &str) -> Result<Vec<Issue>, SiteDownloaderError> {
+        #[derive(Deserialize)]
+        struct PluginIssue {
+            name: String,
+            link: String,
+        }
+        let found: Vec<PluginIssue> = self.call_as(client, "issues", url)?;
+        let mut issues = Vec::new();
+        for issue in found {
+            let issue = Issue { name: issue.name.replace(['/', '\\'], "-").trim().to_string(), link: join_link(url, &issue.link) };
+            check_name(&issue.link, &issue.name)?;
+            issues.push(issue);
+        }
+        return Ok(issues);
+    }
+
+    fn get_comic_name(&self, client: &HttpClient, url: &str) -> Result<String, SiteDownloaderError> {
+        let name: String = if self.exports("comic_name") {
+            self.call_as(client, "comic_name", url)?
+        } else {
+            let parsed_url = reqwest::Url::parse(url).map_err(|_| SiteDownloaderError::InvalidUrl { url: url.to_string() })?;
+            parsed_url.path_segments()
+                .and_then(|mut segments| segments.rfind(|e| !e.is_empty()))
+                .map(|e| e.to_string())
+                .ok_or_else(|| SiteDownloaderError::parsing(url, "the link has no name of the comic"))?
+        };
+        check_name(url, &name)?;
+        return Ok(name);
+    }
+
+    fn get_issue_metadata(&self, _client: &HttpClient, comic_name: &str, issue: &Issue) -> Result<IssueMetadata, SiteDownloaderError> {
+        return Ok(IssueMetadata {
+            language: self.manifest.language.clone(),
+            ..IssueMetadata::new(comic_name, issue)
+        });
+    }
+
+    fn supports_search(&self) -> bool {
+        return self.exports("search");
+    }
+
+    fn search(&self, client: &HttpClient, query: &str) -> Result<Vec<SearchResult>, SiteDownloaderError> {
+        #[derive(Deserialize)]
+        struct PluginResult {
+            title: String,
+            url: String,
+        }
+        if !self.exports("search") {
+            return Ok(Vec::new());
+        }
+        let results: Vec<PluginResult> = self.call_as(client, "search", query)?;
+        return Ok(results.into_iter()
+            .map(|e| SearchResult { site: self.manifest.name.clone(), title: e.title, url: e.url })
+            .collect());
+    }
+
+    fn get_comic_metadata(&self, client: &HttpClient, url: &str) -> Result<ComicMetadata, SiteDownloaderError> {
+        if !self.exports("metadata") {
+            return Ok(ComicMetadata {
+                title: self.get_comic_name(client, url)?,
+                url: url.to_string(),
+                issue_count: Some(self.get_issues_list(client, url)?.len()),
+                ..ComicMetadata::default()
+            });
+        }
+        let mut metadata: Value = self.call(client, "metadata", url)?;
+        // the plugin can leave out the link, it's the one given
+        if let Some(fields) = metadata.as_object_mut() {
+            fields.entry("url").or_insert_with(|| Value::String(url.to_string()));
+        }
+        return serde_json::from_value(metadata).map_err(|e| SiteDownloaderError::Plugin {
+            path: self.path.clone(),
+            reason: format!("metadata: unexpected result: {e}"),
+        });
+    }
+}
+
+/// The functions of the host the plugins can import
+fn host_functions(engine: &Engine) -> Result<Linker<HostState>, wasmi::Error> {
+    let mut linker = Linker::new(engine);
+    linker.func_wrap("comic_dl", "http_get", |mut caller: Caller<'_, HostState>, pointer: i32, length: i32| -> Result<i64, wasmi::Error> {
+        let url = read_string(&caller, pointer, length)?;
+        if !is_allowed(&caller.data().allowed_hosts, &url) {
+            return fail(&mut caller, format!("{url} is not one of the domains or the hosts of the plugin"));
+        }
+        match caller.data().client.get_text(&url) {
+            Ok(body) => return write_string(&mut caller, &body),
+            Err(e) => return fail(&mut caller, e.to_string()),
+        }
+    })?;
+    linker.func_wrap("comic_dl", "html_select", |mut caller: Caller<'_, HostState>,
+        html_pointer: i32, html_length: i32, selector_pointer: i32, selector_length: i32, attribute_pointer: i32, attribute_length: i32|
+        -> Result<i64, wasmi::Error> {
+        let html = read_string(&caller, html_pointer, html_length)?;
+        let selector = read_string(&caller, selector_pointer, selector_length)?;
+        let attribute = read_string(&caller, attribute_pointer, attribute_length)?;
+        let Ok(parsed_selector) = Selector::parse(&selector) else {
+            return fail(&mut caller, format!("invalid selector \"{selector}\""));
+        };
+        let document = Html::parse_document(&html);
+        let found: Vec<String> = document.select(&parsed_selector)
+            .filter_map(|element| match attribute.as_str() {
+                "" => element_text(element),
+                attribute => element.value().attr(attribute).map(|e| e.to_string()),
+            })
+            .collect();
+        return write_string(&mut caller, &Value::from(found).to_string());
+    })?;
+    linker.func_wrap("comic_dl", "regex_captures", |mut caller: Caller<'_, HostState>,
+        pattern_pointer: i32, pattern_length: i32, text_pointer: i32, text_length: i32| -> Result<i64, wasmi::Error> {
+        let pattern = read_string(&caller, pattern_pointer, pattern_length)?;
+        let text = read_string(&caller, text_pointer, text_length)?;
+        let regex = match Regex::new(&pattern) {
+            Ok(regex) => regex,
+            Err(e) => return fail(&mut caller, format!("invalid regex \"{pattern}\": {e}")),
+        };
+        let names: Vec<Option<&str>> = regex.capture_names().collect();
+        let matches: Vec<Value> = regex.captures_iter(&text)
+            .map(|captures| {
+                let mut groups = Map::new();
+                for (index, name) in names.iter().enumerate() {
+                    if let Some(group) = captures.get(index) {
+                        let key = name.map(|e| e.to_string()).unwrap_or_else(|| index.to_string());
+                        groups.insert(key, Value::String(group.as_str().to_string()));
+                    }
+                }
+                return Value::Object(groups);
+            })
+            .collect();
+        return write_string(&mut caller, &Value::from(matches).to_string());
+    })?;
+    linker.func_wrap("comic_dl", "last_error", |mut caller: Caller<'_, HostState>| -> Result<i64, wasmi::Error> {
+        let error = caller.data().last_error.clone();
+        return write_string(&mut caller, &error);
+    })?;
+    linker.func_wrap("comic_dl", "log", |caller: Caller<'_, HostState>, pointer: i32, length: i32| -> Result<(), wasmi::Error> {
+        eprintln!("plugin: {}", read_string(&caller, pointer, length)?);
+        return Ok(());
+    })?;
+    return Ok(linker);
+}
+
+/// Remembers why a host function failed and returns -1 to the plugin
+fn fail(caller: &mut Caller<'_, HostState>, reason: String) -> Result<i64, wasmi::Error> {
+    caller.data_mut().last_error = reason;
+    return Ok(-1);
+}
+
+fn plugin_memory(caller: &Caller<'_, HostState>) -> Result<wasmi::Memory, wasmi::Error> {
+    return caller.get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| wasmi::Error::new("the plugin doesn't export memory"));
+}
+
+fn read_string(caller: &Caller<'_, HostState>, pointer: i32, length: i32) -> Result<String, wasmi::Error> {
+    let memory = plugin_memory(caller)?;
+    let bytes = memory_slice(memory.data(caller), pointer as u32 as usize, length as u32 as usize).map_err(wasmi::Error::new)?;
+    return String::from_utf8(bytes.to_vec()).map_err(|e| wasmi::Error::new(e.to_string()));
+}
+
+/// The `length` bytes at `pointer` of the memory of the plugin, checked
+/// before anything is allocated for them
+fn memory_slice(memory: &[u8], pointer: usize, length: usize) -> Result<&[u8], String> {
+    if length > MAX_STRING {
+        return Err(format!("a string of {length} bytes is longer than the limit of {MAX_STRING}"));
+    }
+    return pointer.checked_add(length)
+        .and_then(|end| memory.get(pointer..end))
+        .ok_or_else(|| format!("{length} bytes at {pointer} are outside of the memory"));
+}
+
+/// Copies `text` in memory allocated by the plugin and returns where it is
+fn write_string(caller: &mut Caller<'_, HostState>, text: &str) -> Result<i64, wasmi::Error> {
+    if text.len() > MAX_STRING {
+        return Err(wasmi::Error::new(format!("a string of {} bytes is longer than the limit of {MAX_STRING}", text.len())));
+    }
+    let alloc = caller.get_export("alloc")
+        .and_then(Extern::into_func)
+        .ok_or_else(|| wasmi::Error::new("the plugin doesn't export alloc"))?
+        .typed::<i32, i32>(&*caller)?;
+    let pointer = alloc.call(&mut *caller, text.len() as i32)?;
+    plugin_memory(caller)?.write(&mut *caller, pointer as u32 as usize, text.as_bytes()).map_err(|e| wasmi::Error::new(e.to_string()))?;
+    return Ok(pack(pointer as u32 as usize, text.len()));
+}
+
+fn pack(pointer: usize, length: usize) -> i64 {
+    return ((pointer as i64) << 32) | (length as u32 as i64);
+}
+
+fn unpack(value: i64) -> (usize, usize) {
+    return ((value as u64 >> 32) as usize, (value as u64 & 0xffff_ffff) as usize);
+}
+
+/// The client for the requests of a plugin, the redirects are followed
+/// only to its domains and `hosts`
+fn sandboxed(hosts: Vec<String>) -> Result<HttpClient, reqwest::Error> {
+    return HttpClient::new().with_redirect_policy(reqwest::redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            return attempt.error("too many redirects");
+        }
+        if !is_allowed(&hosts, attempt.url().as_str()) {
+            let reason = format!("redirect to {}, that is not one of the domains or the hosts of the plugin", attempt.url());
+            return attempt.error(reason);
+        }
+        return attempt.follow();
+    }));
+}
+
+/// Whether the host of `url` is one of `hosts`, the domains and the hosts of
+/// the plugin
+fn is_allowed(hosts: &[String], url: &str) -> bool {
+    return reqwest::Url::parse(url).ok()
+        .filter(|e| matches!(e.scheme(), "http" | "https"))
+        .and_then(|e| e.host_str().map(|e| e.to_string()))
+        .is_some_and(|host| hosts.iter().any(|pattern| domain_matches(pattern, &host)));
+}
+
+/// `link` as given by the plugin, relative to `page_url`
+fn join_link(page_url: &str, link: &str) -> String {
+    return reqwest::Url::parse(page_url).ok()
+        .and_then(|base| base.join(link.trim()).ok())
+        .map(|e| e.to_string())
+        .unwrap_or_else(|| link.to_string());
+}
+
+/// The plugins in `dir`, the ones that can't be loaded are returned as errors
+pub fn load_plugins(dir: &Path) -> Vec<Result<PluginStrategy, SiteDownloaderError>> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|path| path.extension().is_some_and(|e| e == "wasm"))
+        .collect();
+    paths.sort();
+    return paths.iter().map(|path| PluginStrategy::load(path)).collect();
+}
+
+/// The plugins of the user, in the plugins directory of the configuration;
+/// the ones that can't be loaded are reported and skipped
+pub fn user_plugins() -> Vec<PluginStrategy> {
+    let Some(dir) = crate::config::plugins_dir() else {
+        return Vec::new();
+    };
+    return load_plugins(&dir).into_iter()
+        .filter_map(|result| result.map_err(|e| eprintln!("{e}")).ok())
+        .collect();
+}
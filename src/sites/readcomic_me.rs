@@ -1,7 +1,6 @@
 use regex::Regex;
-use reqwest::blocking::Client;
 use scraper::{selectable::Selectable, Html, Selector};
-use std::{fs::{self, File}, io, path::{Path, PathBuf}};
+use std::path::Path;
 
 use super::*;
 
@@ -9,104 +8,105 @@ use super::*;
 pub struct ReadcomicMeStrategy ;
 
 impl ReadcomicMeStrategy{
-    fn get_page_with_issues(&self, client: &Client, page_link: String) -> Option<String> {
-        let response = client.get(page_link).send().unwrap();
-        let body = response.text().unwrap();
+    pub fn site() -> Site {
+        let mut site = Site::new("readcomic.me", &["readcomic.me"], "https://readcomic.me/comic/<name>", || Box::new(ReadcomicMeStrategy));
+        site.change_validator(|url| comic_slug(url.as_str(), "comic").is_some());
+        site.change_capabilities(Capabilities { search: true, metadata: true });
+        return site;
+    }
+
+    /// Searches `query` on the website at `base_url`
+    pub fn search_at(&self, client: &HttpClient, base_url: &str, query: &str) -> Result<Vec<SearchResult>, SiteDownloaderError> {
+        let mut search_url = reqwest::Url::parse(&format!("{base_url}/search"))
+            .map_err(|_| SiteDownloaderError::InvalidUrl { url: base_url.to_string() })?;
+        search_url.query_pairs_mut().append_pair("keyword", query);
+        let body = client.get_text(search_url.as_str())?;
+        return comic_links(&body, search_url.as_str(), "readcomic.me", "/comic/");
+    }
+
+    fn get_page_with_issues(&self, client: &HttpClient, page_link: &str) -> Result<Option<String>, SiteDownloaderError> {
+        let body = client.get_text(page_link)?;
         let document = Html::parse_document(&body);
         let selector = Selector::parse("#nt_listchapter").unwrap();
-        let list = document.select(&selector).next();
-        if list.is_none(){
-            return None;
-        }
+        let Some(list) = document.select(&selector).next() else {
+            return Ok(None);
+        };
         let link_selector = Selector::parse("a").unwrap();
-        let links: Vec<_> = list.unwrap().select(&link_selector).collect();
+        let links: Vec<_> = list.select(&link_selector).collect();
         if links.iter().filter(|e| e.inner_html().contains("Issue #")).count() ==0 {
-            return None;
+            return Ok(None);
         }
-        return Some(body);
+        return Ok(Some(body));
     }
 }
 
-#[allow(unused_variables)]
 impl ComicDownloader for ReadcomicMeStrategy{
-    fn download_issue(&self, client: &Client, download_path: &PathBuf, issue: &Issue) -> Result<(), SiteDownloaderError> {
-        if !download_path.exists() {
-            if fs::create_dir(download_path).is_err() {
-                if !download_path.exists(){
-                    return Err(SiteDownloaderError::FileSystemError);
-                }
-            };
-        }
+    fn download_issue(&self, client: &HttpClient, download_path: &Path, issue: &Issue) -> Result<(), SiteDownloaderError> {
+        create_dir_if_missing(download_path)?;
         let issue_link = &issue.link;
         let issue_number = &issue.name;
         println!("Downloading issue number {}", issue_number);
         let issue_path = download_path.join(issue_number);
-        let out_filename = format!("{}-{}.cbz", self.get_comic_name(client, &issue_link), issue.name);
-        let out_path = download_path.join(&out_filename);
-        if Path::new(&out_path).exists() {
-            println!("Was already downloaded");
-            return Ok(());
-        }
-        if !issue_path.exists() {
-            fs::create_dir(&issue_path).unwrap();
-        }
+        create_dir_if_missing(&issue_path)?;
+        let downloaded = DownloadedPages::load(&issue_path);
 
-        let page = client.get(issue_link).send().unwrap();
-        let page_body = page.text().unwrap();
+        let page_body = client.get_text(issue_link)?;
         let document = Html::parse_document(&page_body);
         let total_pages_selector = Selector::parse("span.total-pages").unwrap();
-        let total_pages_text = document.select(&total_pages_selector).next().unwrap().inner_html();
+        let total_pages_text = document.select(&total_pages_selector).next()
+            .ok_or_else(|| SiteDownloaderError::parsing(issue_link, "no span.total-pages"))?
+            .inner_html();
         let second_pattern = Regex::new(r"(\d+)").unwrap();
-        let number_of_pages = second_pattern.captures(&total_pages_text).unwrap().get(1).unwrap().as_str().parse::<i32>().unwrap();
+        let number_of_pages = second_pattern.captures(&total_pages_text)
+            .and_then(|e| e.get(1))
+            .and_then(|e| e.as_str().parse::<u32>().ok())
+            .ok_or_else(|| SiteDownloaderError::parsing(issue_link, "no number of pages in span.total-pages"))?;
         println!("There are {} pages", number_of_pages);
 
-        for n in 0..number_of_pages {
-            let page_link = format!("{}/{}", issue_link, n + 1);
-            self.download_page(client, &page_link, &issue_path, (n+1).try_into().unwrap()).unwrap();
+        for n in 1..=number_of_pages {
+            if downloaded.contains(n) {
+                continue;
+            }
+            let page_link = format!("{}/{}", issue_link, n);
+            self.download_page(client, &page_link, &issue_path, n)
+                .map_err(|e| e.in_page(issue_number, n))?;
         }
 
         return Ok(());
     }
 
-    fn download_page(&self, client: &Client, link: &str, issue_path: &Path, page_number: u32) -> Result<(), SiteDownloaderError> {
-        let page = client.get(link).send().unwrap();
-        let page_body = page.text().unwrap();
+    fn download_page(&self, client: &HttpClient, link: &str, issue_path: &Path, page_number: u32) -> Result<(), SiteDownloaderError> {
+        let page_body = client.get_text(link)?;
         let document = Html::parse_document(&page_body);
         let img_selector = Selector::parse("img.single-page").unwrap();
-        let page_image_link = document.select(&img_selector).next().unwrap().value().attr("src").unwrap();
-        let page_name = format!("{:04}.jpg", page_number);
-        let dw_path =  issue_path.join(page_name);
-        let mut response = client.get(page_image_link).send().unwrap();
-        if response.status().is_success() {
-            let mut file = File::create(dw_path).unwrap();
-            io::copy(&mut response, &mut file).unwrap();
-        } else {
-            eprintln!("Couldn't download page");
-        }
+        let page_image_link = document.select(&img_selector).next()
+            .and_then(|e| e.value().attr("src"))
+            .ok_or_else(|| SiteDownloaderError::parsing(link, "no img.single-page with a src"))?;
+        save_page(client, page_image_link, issue_path, page_number)?;
         return Ok(());
     }
 
-    fn get_issues_list(&self, client: &Client, url: &str) -> Result<Vec<Issue>, SiteDownloaderError> {
+    fn get_issues_list(&self, client: &HttpClient, url: &str) -> Result<Vec<Issue>, SiteDownloaderError> {
         let mut vec = Vec::new();
         let link = url.to_string();
         let mut page_number = 1;
         let mut page_link = link.to_string() + "?page="+&page_number.to_string();
-        while let Some(page_with_link) = self.get_page_with_issues(client, page_link) {
+        while let Some(page_with_link) = self.get_page_with_issues(client, &page_link)? {
             let document = Html::parse_document(&page_with_link);
             let selector = Selector::parse("#nt_listchapter").unwrap();
-            let list = document.select(&selector).next();
-            if list.is_none(){
-                return Err(SiteDownloaderError::NotFound);
-            }
+            let list = document.select(&selector).next()
+                .ok_or_else(|| SiteDownloaderError::parsing(&page_link, "no #nt_listchapter"))?;
             let link_selector = Selector::parse("a").unwrap();
-            let links: Vec<_> = list.unwrap().select(&link_selector).collect();
+            let links: Vec<_> = list.select(&link_selector).collect();
             for link in links {
                 let link_number: String = match link.inner_html()
                     .lines().nth(1){
                     Some(inner_line) => inner_line.replace("Issue #", "") ,
                     None => break,
                 };
-                let issue_url: String = link.value().attr("href").unwrap().to_owned();
+                let issue_url: String = link.value().attr("href")
+                    .ok_or_else(|| SiteDownloaderError::parsing(&page_link, "issue link without href"))?
+                    .to_owned();
                 let issue: Issue = Issue { name: link_number, link: issue_url };
                 vec.push(issue);
             }
@@ -117,7 +117,59 @@ impl ComicDownloader for ReadcomicMeStrategy{
         return Ok(vec);
     }
 
-    fn get_comic_name(&self, _client: &Client,  url: &str) -> String {
-        return url.replace("https://readcomic.me/comic/", "");
+    fn get_comic_name(&self, _client: &HttpClient,  url: &str) -> Result<String, SiteDownloaderError> {
+        return comic_slug(url, "comic")
+            .ok_or_else(|| SiteDownloaderError::parsing(url, "couldn't find comic name, the link should be like https://readcomic.me/comic/<name>"));
+    }
+
+    fn get_issue_metadata(&self, _client: &HttpClient, comic_name: &str, issue: &Issue) -> Result<IssueMetadata, SiteDownloaderError> {
+        return Ok(IssueMetadata {
+            language: Some("en".to_string()),
+            ..IssueMetadata::new(comic_name, issue)
+        });
+    }
+
+    fn supports_search(&self) -> bool {
+        return true;
+    }
+
+    fn search(&self, client: &HttpClient, query: &str) -> Result<Vec<SearchResult>, SiteDownloaderError> {
+        return self.search_at(client, "https://readcomic.me", query);
+    }
+
+    fn get_comic_metadata(&self, client: &HttpClient, url: &str) -> Result<ComicMetadata, SiteDownloaderError> {
+        let body = client.get_text(url)?;
+        let document = Html::parse_document(&body);
+        let mut metadata = open_graph_metadata(&document, url);
+        let first_text = |selector: &str| {
+            let selector = Selector::parse(selector).unwrap();
+            return document.select(&selector).next().and_then(element_text);
+        };
+        let all_texts = |selector: &str| {
+            let selector = Selector::parse(selector).unwrap();
+            return document.select(&selector).filter_map(element_text).collect::<Vec<_>>();
+        };
+        if let Some(title) = first_text("#item-detail h1.title-detail") {
+            metadata.title = title;
+        }
+        if metadata.title.is_empty() {
+            metadata.title = self.get_comic_name(client, url)?;
+        }
+        let cover_selector = Selector::parse("#item-detail .col-image img").unwrap();
+        if let Some(cover) = document.select(&cover_selector).next().and_then(|e| e.value().attr("src")) {
+            metadata.cover_url = Some(cover.to_string());
+        }
+        metadata.authors = all_texts(".list-info li.author p.col-xs-8 a");
+        if metadata.authors.is_empty() {
+            metadata.authors = first_text(".list-info li.author p.col-xs-8").into_iter().collect();
+        }
+        metadata.status = first_text(".list-info li.status p.col-xs-8").or(metadata.status);
+        metadata.genres = all_texts(".list-info li.kind p.col-xs-8 a");
+        metadata.description = first_text("#item-detail .detail-content p").or(metadata.description);
+        metadata.last_update = first_text("#item-detail time.small")
+            .map(|e| e.trim_matches(|c| c == '[' || c == ']').trim().to_string())
+            .or(metadata.last_update);
+        metadata.issue_count = Some(self.get_issues_list(client, url)?.len());
+        return Ok(metadata);
     }
 }
@@ -0,0 +1,251 @@
+//! the websites comic-dl can download from: every strategy declares its
+//! domains, the shape of the links to its comics and what it can do, the
+//! strategy of a link and `--list-websites` come from here
+
+use reqwest::Url;
+
+use super::*;
+
+/// What a website can do besides downloading the issues
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// comics can be searched by title
+    pub search: bool,
+    /// the page of the comic has details like the authors and the genres
+    pub metadata: bool,
+}
+
+type Validator = Box<dyn Fn(&Url) -> bool + Send + Sync>;
+type Factory = Box<dyn Fn() -> Box<dyn ComicDownloader> + Send + Sync>;
+
+/// A website in the registry with the strategy to download from it
+pub struct Site {
+    pub name: String,
+    /// domains of the website: `example.com` is also `www.example.com`,
+    /// `*.example.com` is any subdomain and `example.*` any mirror with
+    /// another top level domain
+    pub domains: Vec<String>,
+    /// a link to a comic, shown when a link has the wrong shape
+    pub example: String,
+    pub capabilities: Capabilities,
+    /// a comic that is always on the website, `doctor` checks the strategy
+    /// on it
+    pub canary: Option<String>,
+    validator: Validator,
+    downloader: Factory,
+}
+
+impl Site {
+    /// A website that takes any link of its domains, it can search when the
+    /// strategy supports it
+    pub fn new(
+        name: &str,
+        domains: &[&str],
+        example: &str,
+        downloader: impl Fn() -> Box<dyn ComicDownloader> + Send + Sync + 'static,
+    ) -> Site {
+        return Site {
+            name: name.to_string(),
+            domains: domains.iter().map(|e| e.to_string()).collect(),
+            example: example.to_string(),
+            capabilities: Capabilities { search: downloader().supports_search(), metadata: false },
+            canary: None,
+            validator: Box::new(|_| true),
+            downloader: Box::new(downloader),
+        };
+    }
+
+    /// Only the links accepted by `validator` are comics of the website
+    pub fn change_validator(&mut self, validator: impl Fn(&Url) -> bool + Send + Sync + 'static) {
+        self.validator = Box::new(validator);
+    }
+
+    pub fn change_capabilities(&mut self, capabilities: Capabilities) {
+        self.capabilities = capabilities;
+    }
+
+    pub fn change_canary(&mut self, url: &str) {
+        self.canary = Some(url.to_string());
+    }
+
+    /// Whether `host` is one of the domains of the website
+    pub fn handles(&self, host: &str) -> bool {
+        return self.domains.iter().any(|pattern| domain_matches(pattern, host));
+    }
+
+    /// Whether `url` has the shape of a link to a comic of the website
+    pub fn is_comic_url(&self, url: &Url) -> bool {
+        return (self.validator)(url);
+    }
+
+    /// A new strategy to download from the website
+    pub fn downloader(&self) -> Box<dyn ComicDownloader> {
+        return (self.downloader)();
+    }
+}
+
+impl Debug for Site {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return f.debug_struct("Site")
+            .field("name", &self.name)
+            .field("domains", &self.domains)
+            .field("example", &self.example)
+            .field("capabilities", &self.capabilities)
+            .field("canary", &self.canary)
+            .finish_non_exhaustive();
+    }
+}
+
+/// The websites comic-dl knows, the first one with the domain of a link
+/// downloads it
+#[derive(Debug, Default)]
+pub struct SiteRegistry {
+    sites: Vec<Site>,
+}
+
+impl SiteRegistry {
+    /// A registry without any website
+    pub fn new() -> SiteRegistry {
+        return SiteRegistry::default();
+    }
+
+    /// The websites supported by comic-dl itself
+    pub fn builtin() -> SiteRegistry {
+        let mut registry = SiteRegistry::new();
+        registry.register(ReadcomicMeStrategy::site());
+        registry.register(ZerocalcareNetStrategy::site());
+        registry.register(ScanitaOrgStrategy::site());
+        return registry;
+    }
+
+    /// The builtin websites followed by the ones of the site definitions and
+    /// of the plugins of the user
+    pub fn load() -> SiteRegistry {
+        let mut registry = SiteRegistry::builtin();
+        for strategy in declarative::user_site_definitions() {
+            registry.register(strategy.site());
+        }
+        #[cfg(feature = "plugins")]
+        for plugin in plugin::user_plugins() {
+            registry.register(plugin.site());
+        }
+        #[cfg(not(feature = "plugins"))]
+        if crate::config::plugins_dir().and_then(|e| fs::read_dir(e).ok()).is_some_and(|mut e| e.next().is_some()) {
+            eprintln!("the plugins are skipped: {}", SiteDownloaderError::FeatureDisabled { feature: "plugins" });
+        }
+        return registry;
+    }
+
+    pub fn register(&mut self, site: Site) {
+        self.sites.push(site);
+    }
+
+    pub fn sites(&self) -> &[Site] {
+        return &self.sites;
+    }
+
+    /// The website of `url`, with the websites of similar domains in the
+    /// error when there is none
+    pub fn find(&self, url: &str) -> Result<&Site, SiteDownloaderError> {
+        let parsed_url = Url::parse(url).map_err(|_| SiteDownloaderError::InvalidUrl { url: url.to_string() })?;
+        let Some(host) = parsed_url.host_str().filter(|_| matches!(parsed_url.scheme(), "http" | "https")) else {
+            return Err(SiteDownloaderError::InvalidUrl { url: url.to_string() });
+        };
+        let Some(site) = self.sites.iter().find(|e| e.handles(host)) else {
+            return Err(SiteDownloaderError::UnsupportedWebsite { url: url.to_string(), suggestions: self.similar_sites(host) });
+        };
+        if !site.is_comic_url(&parsed_url) {
+            return Err(SiteDownloaderError::NotAComic { url: url.to_string(), site: site.name.clone(), example: site.example.clone() });
+        }
+        return Ok(site);
+    }
+
+    /// The strategy to download the comic at `url`
+    pub fn identify(&self, url: &str) -> Result<Box<dyn ComicDownloader>, SiteDownloaderError> {
+        return Ok(self.find(url)?.downloader());
+    }
+
+    /// Names of the websites with a domain close to `host`, like a typo or
+    /// another top level domain
+    fn similar_sites(&self, host: &str) -> Vec<String> {
+        let host = host.trim_start_matches("www.").to_lowercase();
+        let host_name = host.split('.').next().unwrap_or_default().to_string();
+        return self.sites.iter()
+            .filter(|site| site.domains.iter().any(|pattern| {
+                let domain = pattern.trim_start_matches("*.").trim_start_matches("www.").to_lowercase();
+                let name = domain.split('.').next().unwrap_or_default();
+                return (name != "*" && name == host_name) || edit_distance(&domain, &host) <= 2.max(domain.len() / 5);
+            }))
+            .map(|site| site.name.clone())
+            .collect();
+    }
+
+    /// The websites one per line, with their domains, a link to a comic and
+    /// what they can do
+    pub fn describe(&self) -> String {
+        let mut text = String::new();
+        for site in &self.sites {
+            let mut features = Vec::new();
+            if site.capabilities.search {
+                features.push("search");
+            }
+            if site.capabilities.metadata {
+                features.push("info");
+            }
+            text.push_str(&format!("- {}: {}\n", site.name, site.example));
+            text.push_str(&format!("  domains: {}\n", site.domains.join(", ")));
+            if !features.is_empty() {
+                text.push_str(&format!("  supports: {}\n", features.join(", ")));
+            }
+        }
+        if let (Some(sites), Some(plugins)) = (crate::config::sites_dir(), crate::config::plugins_dir()) {
+            text.push_str(&format!("\nmore websites can be added with site definitions in {} and plugins in {}\n", sites.display(), plugins.display()));
+        }
+        return text;
+    }
+}
+
+/// Whether `host` is the domain of `pattern`: `example.com` is also
+/// `www.example.com`, `*.example.com` is example.com and any subdomain and a
+/// `*` elsewhere is any single part, like `example.*` for the mirrors
+pub fn domain_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim().trim_end_matches('.').to_lowercase();
+    let host = host.trim_end_matches('.').to_lowercase();
+    if let Some(domain) = pattern.strip_prefix("*.") {
+        return host == domain || host.ends_with(&format!(".{domain}"));
+    }
+    let host = match host.strip_prefix("www.") {
+        Some(rest) if !pattern.starts_with("www.") => rest,
+        _ => &host,
+    };
+    let pattern_parts: Vec<&str> = pattern.split('.').collect();
+    let host_parts: Vec<&str> = host.split('.').collect();
+    return pattern_parts.len() == host_parts.len()
+        && pattern_parts.iter().zip(&host_parts).all(|(pattern, host)| *pattern == "*" || pattern == host);
+}
+
+/// The name of the comic in a link like `https://example.com/<section>/<name>/`,
+/// `None` when the link has another shape
+pub(crate) fn comic_slug(url: &str, section: &str) -> Option<String> {
+    let parsed_url = Url::parse(url).ok()?;
+    let segments: Vec<&str> = parsed_url.path_segments()?.filter(|e| !e.is_empty()).collect();
+    match segments.as_slice() {
+        [first, name] if *first == section => return Some(name.to_string()),
+        _ => return None,
+    }
+}
+
+/// Number of characters to add, remove or change to go from `a` to `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let cost = if a_char == *b_char { 0 } else { 1 };
+            current.push((previous[j] + cost).min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    return previous[b.len()];
+}
@@ -1,6 +1,3 @@
-use std::{fs::{self, File}, path::PathBuf};
-
-use reqwest::blocking::Client;
 use scraper::{selectable::Selectable, Html, Selector};
 
 use super::*;
@@ -8,84 +5,149 @@ use super::*;
 #[derive(Debug, Clone)]
 pub struct ScanitaOrgStrategy;
 
+impl ScanitaOrgStrategy {
+    pub fn site() -> Site {
+        let mut site = Site::new("scanita.org", &["scanita.org", "scanita.*"], "https://scanita.org/manga/<name>", || Box::new(ScanitaOrgStrategy));
+        site.change_validator(|url| comic_slug(url.as_str(), "manga").is_some());
+        site.change_capabilities(Capabilities { search: true, metadata: true });
+        return site;
+    }
+
+    /// Searches `query` on the website at `base_url`
+    pub fn search_at(&self, client: &HttpClient, base_url: &str, query: &str) -> Result<Vec<SearchResult>, SiteDownloaderError> {
+        let mut search_url = reqwest::Url::parse(&format!("{base_url}/search"))
+            .map_err(|_| SiteDownloaderError::InvalidUrl { url: base_url.to_string() })?;
+        search_url.query_pairs_mut().append_pair("q", query);
+        let body = client.get_text(search_url.as_str())?;
+        return comic_links(&body, search_url.as_str(), "scanita.org", "/manga/");
+    }
+}
+
 impl ComicDownloader for ScanitaOrgStrategy{
-    fn download_issue(&self, client: &Client, download_path: &PathBuf, issue: &Issue) -> Result<(), SiteDownloaderError> {
-        if !download_path.exists() {
-            if fs::create_dir(&download_path).is_err() {
-                if !download_path.exists(){
-                    return Err(SiteDownloaderError::FileSystemError);
-                }
-            };
-        }
+    fn download_issue(&self, client: &HttpClient, download_path: &Path, issue: &Issue) -> Result<(), SiteDownloaderError> {
+        create_dir_if_missing(download_path)?;
         println!("downloading {}", issue.name);
         let issue_path = download_path.join(issue.name.clone());
-        if !issue_path.exists(){
-            fs::create_dir(&issue_path).unwrap();
-        }
+        create_dir_if_missing(&issue_path)?;
+        let downloaded = DownloadedPages::load(&issue_path);
         let mut page_number: u32 = 1;
         loop {
+            if downloaded.contains(page_number) {
+                page_number += 1;
+                continue;
+            }
             let link = issue.link.clone() + "/" + &page_number.to_string();
-            let request = client.get(link).send().expect("couldn't donwload page");
+            let request = client.send(&link)?;
             if request.status() == reqwest::StatusCode::FOUND {break;}
-            let page =  request.text().unwrap();
+            if !request.status().is_success() {
+                return Err(SiteDownloaderError::HttpStatus { url: link, status: request.status() });
+            }
+            let page =  request.text()
+                .map_err(|source| SiteDownloaderError::Request { url: link.clone(), source })?;
             let document = Html::parse_document(&page);
             let page_selector = Selector::parse(".book-page").unwrap();
             let img_selector = Selector::parse("img").unwrap();
-            let page_div = document.select(&page_selector).next();
-            if page_div.is_none() {break;}
-            let page_img = page_div.unwrap().select(&img_selector).next().unwrap();
-            let page_img_link = page_img.attr("src").unwrap();
-            self.download_page(client, page_img_link, &issue_path , page_number)?;
+            let Some(page_div) = document.select(&page_selector).next() else {break;};
+            let page_img_link = page_div.select(&img_selector).next()
+                .and_then(|e| e.attr("src"))
+                .ok_or_else(|| SiteDownloaderError::parsing(&link, "no img with a src in .book-page"))?;
+            self.download_page(client, page_img_link, &issue_path , page_number)
+                .map_err(|e| e.in_page(&issue.name, page_number))?;
             page_number += 1;
         }
         return Ok(());
     }
 
-    fn download_page(&self, client: &Client, link: &str, issue_path: &Path, page_number: u32) -> Result<(), SiteDownloaderError> {
-        let response = client.get(link).send().expect("can't download a page");
-        let file_path = issue_path.join(format!("{:04}.jpg", page_number));
-        let mut destination = File::create(file_path).unwrap();
-        let content = response.bytes().unwrap();
-        std::io::copy(&mut content.as_ref(), &mut destination).unwrap();
+    fn download_page(&self, client: &HttpClient, link: &str, issue_path: &Path, page_number: u32) -> Result<(), SiteDownloaderError> {
+        save_page(client, link, issue_path, page_number)?;
         return Ok(());
     }
 
-    fn get_issues_list(&self, client: &Client, url: &str) -> Result<Vec<Issue>, SiteDownloaderError> {
+    fn get_issues_list(&self, client: &HttpClient, url: &str) -> Result<Vec<Issue>, SiteDownloaderError> {
         let mut list_of_issues: Vec<Issue> = Vec::new();
-        let base_url = "https://".to_string() +  reqwest::Url::parse(url).unwrap().domain().unwrap();
-        let link = url; 
-        let body = client.get(link).send().unwrap()
-            .text().unwrap();
+        // the mirrors and the links with a port have the same paths
+        let base_url = reqwest::Url::parse(url).ok()
+            .map(|e| e.origin().ascii_serialization())
+            .filter(|e| e != "null")
+            .ok_or_else(|| SiteDownloaderError::InvalidUrl { url: url.to_string() })?;
+        let link = url;
+        let body = client.get_text(link)?;
         let document = Html::parse_document(&body);
         let selector = Selector::parse("[data-show-more='#more-chapter']").unwrap();
         let list = document.select(&selector).next();
         match list {
             Some(button) => {
-                let link_to_list = base_url.clone() + button.attr("data-path").unwrap();
-                let chapters_body = client.get(link_to_list).send().unwrap().text().unwrap();
+                let data_path = button.attr("data-path")
+                    .ok_or_else(|| SiteDownloaderError::parsing(link, "chapters button without data-path"))?;
+                let link_to_list = base_url.clone() + data_path;
+                let chapters_body = client.get_text(&link_to_list)?;
                 let chapters_document = Html::parse_document(&chapters_body);
 
                 let a_selector = Selector::parse("a[href]").unwrap();
                 let h5_selector = Selector::parse("h5").unwrap();
                 for a_chapter in chapters_document.select(&a_selector) {
-                    let href= a_chapter.value().attr("href").unwrap();
+                    let href= a_chapter.value().attr("href").unwrap_or_default();
                     let issue_link = base_url.clone() + href;
                     let chapter_text= a_chapter.select(&h5_selector)
-                        .next().unwrap().text().collect::<Vec<_>>().concat();
-                    let chapter_name = chapter_text.lines().nth(1).unwrap().trim();
+                        .next()
+                        .ok_or_else(|| SiteDownloaderError::parsing(&link_to_list, "chapter without h5"))?
+                        .text().collect::<Vec<_>>().concat();
+                    let chapter_name = chapter_text.lines().nth(1)
+                        .ok_or_else(|| SiteDownloaderError::parsing(&link_to_list, "chapter without a name"))?
+                        .trim();
                     let issue: Issue = Issue { name: chapter_name.to_owned(), link: issue_link };
                     list_of_issues.push(issue);
                 }
             },
             None => { //few chapters, no dedicated button
-                todo!()
+                return Err(SiteDownloaderError::parsing(link, "comics without the chapters button are not supported yet"));
             },
         }
         list_of_issues.reverse();
         return Ok(list_of_issues);
     }
 
-    fn get_comic_name(&self, _client: &Client, url: &str) -> String {
-        return url.replace("https://scanita.org/manga/", "").to_string();
+    fn get_comic_name(&self, _client: &HttpClient, url: &str) -> Result<String, SiteDownloaderError> {
+        return comic_slug(url, "manga")
+            .ok_or_else(|| SiteDownloaderError::parsing(url, "couldn't find comic name, the link should be like https://scanita.org/manga/<name>"));
+    }
+
+    fn get_issue_metadata(&self, _client: &HttpClient, comic_name: &str, issue: &Issue) -> Result<IssueMetadata, SiteDownloaderError> {
+        return Ok(IssueMetadata {
+            language: Some("it".to_string()),
+            ..IssueMetadata::new(comic_name, issue)
+        });
+    }
+
+    fn supports_search(&self) -> bool {
+        return true;
+    }
+
+    fn search(&self, client: &HttpClient, query: &str) -> Result<Vec<SearchResult>, SiteDownloaderError> {
+        return self.search_at(client, "https://scanita.org", query);
+    }
+
+    fn get_comic_metadata(&self, client: &HttpClient, url: &str) -> Result<ComicMetadata, SiteDownloaderError> {
+        let body = client.get_text(url)?;
+        let document = Html::parse_document(&body);
+        let mut metadata = open_graph_metadata(&document, url);
+        if metadata.title.is_empty() {
+            metadata.title = self.get_comic_name(client, url)?;
+        }
+        // the authors and the genres link to the pages with their other manga
+        let links = |path: &str| {
+            let selector = Selector::parse(&format!("a[href*='{path}']")).unwrap();
+            let mut texts: Vec<String> = Vec::new();
+            for text in document.select(&selector).filter_map(element_text) {
+                if !texts.contains(&text) {
+                    texts.push(text);
+                }
+            }
+            return texts;
+        };
+        metadata.authors = links("/author");
+        metadata.genres = links("/genre");
+        metadata.issue_count = Some(self.get_issues_list(client, url)?.len());
+        return Ok(metadata);
     }
 }
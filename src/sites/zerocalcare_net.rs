@@ -1,6 +1,4 @@
-use std::{collections::HashSet, fs::{self, File}, io::copy};
-
-use reqwest::blocking::Client;
+use std::collections::HashSet;
 
 use super::*;
 
@@ -8,70 +6,99 @@ use super::*;
 #[derive(Debug, Clone)]
 pub struct ZerocalcareNetStrategy;
 
+impl ZerocalcareNetStrategy {
+    pub fn site() -> Site {
+        let mut site = Site::new("zerocalcare.net", &["zerocalcare.net"], "https://www.zerocalcare.net/storie-a-fumetti/<name>/", || Box::new(ZerocalcareNetStrategy));
+        site.change_validator(|url| comic_slug(url.as_str(), "storie-a-fumetti").is_some());
+        site.change_capabilities(Capabilities { search: false, metadata: true });
+        return site;
+    }
+}
+
 impl ComicDownloader for ZerocalcareNetStrategy{
-    fn download_issue(&self, client: &Client, download_path: &PathBuf, issue: &Issue) -> Result<(), SiteDownloaderError> {
-        if !download_path.exists() {
-            if fs::create_dir(&download_path).is_err() {
-                if !download_path.exists(){
-                    return Err(SiteDownloaderError::FileSystemError);
-                }
-            };
-        }
+    fn download_issue(&self, client: &HttpClient, download_path: &Path, issue: &Issue) -> Result<(), SiteDownloaderError> {
+        create_dir_if_missing(download_path)?;
         let issue_link = &issue.link;
         let issue_name = &issue.name;
         println!("Downloading {}", issue.name);
         let issue_path = download_path.join(issue_name);
-        let out_filename = format!("{}.cbz", issue_name);
-        let out_path = download_path.join(&out_filename);
-        if Path::new(&out_path).exists() {
-            println!("Was already downloaded");
-            return Ok(());
-        }
-        fs::create_dir(&issue_path).unwrap();
+        create_dir_if_missing(&issue_path)?;
+        let downloaded = DownloadedPages::load(&issue_path);
 
-        let regex_find_pages = regex::Regex::new(r"https://www.zerocalcare.net/wp-content/uploads/\d{4}/\d{2}/(\d+)-(\d+).jpg").unwrap();
-        let page = client.get(issue_link).send().unwrap();
-        let page_body = page.text().unwrap();
+        // the images are on the same website, wherever it is, the links to
+        // other hosts are not downloaded
+        let regex_find_pages = regex::Regex::new(r#"https?://[^/"'\s]+/wp-content/uploads/\d{4}/\d{2}/(\d+)-(\d+)\.jpg"#).unwrap();
+        let page_body = client.get_text(issue_link)?;
         let mut pages = HashSet::new();
         for line in page_body.lines(){
-            for mat in regex_find_pages.find_iter(&line) {
+            for mat in regex_find_pages.find_iter(line) {
+                if !is_on_the_website(issue_link, mat.as_str()) {
+                    eprintln!("{} is skipped, it is not on zerocalcare.net", mat.as_str());
+                    continue;
+                }
                 pages.insert(mat.as_str().to_string());
             }
         }
 
+        let regex_find_page_number = regex::Regex::new(r"/wp-content/uploads/\d{4}/\d{2}/(\d+)-\d+\.jpg").unwrap();
         for page in pages {
-            let regex_find_page_number = regex::Regex::new(r"https://www\.zerocalcare\.net/wp-content/uploads/\d{4}/\d{2}/(\d+)-\d+\.jpg").unwrap();
-            let page_number = regex_find_page_number.captures_iter(&page).nth(0).unwrap().get(1).unwrap().as_str().parse::<u32>().unwrap();
-            //println!("{page} cap {:04}", page_number);
-
-            self.download_page(&client, &page, &issue_path, page_number).unwrap();
+            let page_number = regex_find_page_number.captures(&page)
+                .and_then(|e| e.get(1))
+                .and_then(|e| e.as_str().parse::<u32>().ok())
+                .ok_or_else(|| SiteDownloaderError::parsing(&page, "no page number in the image link"))?;
+            if downloaded.contains(page_number) {
+                continue;
+            }
+            self.download_page(client, &page, &issue_path, page_number)
+                .map_err(|e| e.in_page(issue_name, page_number))?;
         }
         return Ok(());
     }
 
-    fn download_page(&self, client: &Client, link: &str, issue_path: &Path, page_number: u32) -> Result<(), SiteDownloaderError> {
-        //println!("downloading page {}, {}, {:04}", link, issue_path.to_str().unwrap(), page_number);
-        let response = client.get(link).send().expect("can't download a page");
-        let file_path = issue_path.join(format!("{:04}.jpg", page_number));
-        let mut destination = File::create(file_path).unwrap();
-        let content = response.bytes().unwrap();
-        copy(&mut content.as_ref(), &mut destination).unwrap();
+    fn download_page(&self, client: &HttpClient, link: &str, issue_path: &Path, page_number: u32) -> Result<(), SiteDownloaderError> {
+        save_page(client, link, issue_path, page_number)?;
         return Ok(());
     }
 
-    fn get_issues_list(&self, client: &Client, url: &str) -> Result<Vec<Issue>, SiteDownloaderError> {
+    fn get_issues_list(&self, client: &HttpClient, url: &str) -> Result<Vec<Issue>, SiteDownloaderError> {
         //there is a single issue for comic
-        let name = self.get_comic_name(client, url);
+        let name = self.get_comic_name(client, url)?;
         let link = url.to_string();
         let the_issue: Issue = Issue { name, link };
         let vec = vec![the_issue];
         return Ok(vec);
     }
 
-    fn get_comic_name(&self, _client: &Client,  url: &str) -> String {
-        return url
-            .replace("https://www.zerocalcare.net/storie-a-fumetti/", "")
-            .strip_suffix("/")
-            .expect("couldn't find comic name").to_string();
+    fn get_comic_name(&self, _client: &HttpClient,  url: &str) -> Result<String, SiteDownloaderError> {
+        return comic_slug(url, "storie-a-fumetti")
+            .ok_or_else(|| SiteDownloaderError::parsing(url, "couldn't find comic name, the link should be like https://www.zerocalcare.net/storie-a-fumetti/<name>/"));
+    }
+
+    fn get_comic_metadata(&self, client: &HttpClient, url: &str) -> Result<ComicMetadata, SiteDownloaderError> {
+        let body = client.get_text(url)?;
+        let mut metadata = open_graph_metadata(&scraper::Html::parse_document(&body), url);
+        if metadata.title.is_empty() {
+            metadata.title = self.get_comic_name(client, url)?;
+        }
+        metadata.authors = vec!["Zerocalcare".to_string()];
+        metadata.issue_count = Some(1);
+        return Ok(metadata);
+    }
+
+    fn get_issue_metadata(&self, _client: &HttpClient, comic_name: &str, issue: &Issue) -> Result<IssueMetadata, SiteDownloaderError> {
+        return Ok(IssueMetadata {
+            writer: Some("Zerocalcare".to_string()),
+            language: Some("it".to_string()),
+            ..IssueMetadata::new(comic_name, issue)
+        });
     }
 }
+
+/// Whether the image is on zerocalcare.net, or on the host of the issue
+fn is_on_the_website(issue_link: &str, image: &str) -> bool {
+    let host = |link: &str| reqwest::Url::parse(link).ok().and_then(|e| e.host_str().map(|e| e.to_string()));
+    let Some(image_host) = host(image) else {
+        return false;
+    };
+    return host(issue_link).as_deref() == Some(image_host.as_str()) || registry::domain_matches("*.zerocalcare.net", &image_host);
+}
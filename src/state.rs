@@ -0,0 +1,156 @@
+//! record of the issues already downloaded, kept as json in the download
+//! directory of every comic so it doesn't depend on the names of the files
+
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{error::SiteDownloaderError, sites::{Issue, OutputFormats}};
+
+/// Name of the file with the state, in the download directory of the comic
+pub const STATE_FILE: &str = ".comic-dl.json";
+
+const STATE_VERSION: u32 = 1;
+
+/// An issue that was downloaded and packed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IssueRecord {
+    pub link: String,
+    pub name: String,
+    pub pages: usize,
+    pub format: OutputFormats,
+    /// name of the volume in the download directory
+    pub file: String,
+    /// sha256 of the volume, in hex
+    pub sha256: String,
+    /// seconds since the unix epoch
+    pub downloaded_at: u64,
+}
+
+/// Where an issue of the list is on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueStatus {
+    /// packed in a volume
+    Downloaded,
+    /// some pages were downloaded but there is no volume yet
+    Partial,
+    Missing,
+}
+
+impl IssueStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            IssueStatus::Downloaded => return "downloaded",
+            IssueStatus::Partial => return "partial",
+            IssueStatus::Missing => return "missing",
+        }
+    }
+}
+
+/// An issue of the list of a comic with its position, counting from 1, and
+/// its status
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IssueListing {
+    pub index: usize,
+    pub name: String,
+    pub link: String,
+    pub status: IssueStatus,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DownloadState {
+    pub version: u32,
+    pub issues: Vec<IssueRecord>,
+    /// links of the issues on the website at the last download, to know
+    /// which ones are new
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub listed: Vec<String>,
+}
+
+impl Default for DownloadState {
+    fn default() -> Self {
+        return DownloadState { version: STATE_VERSION, issues: Vec::new(), listed: Vec::new() };
+    }
+}
+
+impl DownloadState {
+    /// Reads the state in `download_path`, empty if there isn't one yet
+    pub fn load(download_path: &Path) -> Result<DownloadState, SiteDownloaderError> {
+        let path = download_path.join(STATE_FILE);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(DownloadState::default()),
+            Err(e) => return Err(SiteDownloaderError::file_system(&path, e)),
+        };
+        return serde_json::from_str(&content)
+            .map_err(|e| SiteDownloaderError::file_system(&path, io::Error::new(io::ErrorKind::InvalidData, e)));
+    }
+
+    /// Writes the state in `download_path`, replacing the old one only when
+    /// the new one is completely written
+    pub fn save(&self, download_path: &Path) -> Result<(), SiteDownloaderError> {
+        let path = download_path.join(STATE_FILE);
+        let partial_path = download_path.join(format!("{STATE_FILE}.part"));
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| SiteDownloaderError::file_system(&path, io::Error::new(io::ErrorKind::InvalidData, e)))?;
+        fs::write(&partial_path, content).map_err(|e| SiteDownloaderError::file_system(&partial_path, e))?;
+        fs::rename(&partial_path, &path).map_err(|e| SiteDownloaderError::file_system(&path, e))?;
+        return Ok(());
+    }
+
+    /// The record of `issue`, looked up by link
+    pub fn get(&self, issue: &Issue) -> Option<&IssueRecord> {
+        return self.issues.iter().find(|record| record.link == issue.link);
+    }
+
+    pub fn is_downloaded(&self, issue: &Issue) -> bool {
+        return self.get(issue).is_some();
+    }
+
+    /// Adds the record, replacing the one of the same issue if there was one
+    pub fn insert(&mut self, record: IssueRecord) {
+        self.issues.retain(|e| e.link != record.link);
+        self.issues.push(record);
+    }
+}
+
+impl IssueRecord {
+    /// Record of the volume at `volume_path` just created for `issue`
+    pub fn new(issue: &Issue, pages: usize, format: OutputFormats, volume_path: &Path) -> Result<IssueRecord, SiteDownloaderError> {
+        return Ok(IssueRecord {
+            link: issue.link.clone(),
+            name: issue.name.clone(),
+            pages,
+            format,
+            file: volume_path.file_name()
+                .and_then(|e| e.to_str())
+                .unwrap_or_default()
+                .to_string(),
+            sha256: file_sha256(volume_path)?,
+            downloaded_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|e| e.as_secs())
+                .unwrap_or_default(),
+        });
+    }
+
+    /// Where the volume is, if it wasn't moved
+    pub fn path(&self, download_path: &Path) -> PathBuf {
+        return download_path.join(&self.file);
+    }
+}
+
+/// sha256 of the file, in hex
+pub fn file_sha256(path: &Path) -> Result<String, SiteDownloaderError> {
+    let mut file = fs::File::open(path).map_err(|e| SiteDownloaderError::file_system(path, e))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).map_err(|e| SiteDownloaderError::file_system(path, e))?;
+    return Ok(hasher.finalize().iter().map(|byte| format!("{byte:02x}")).collect());
+}
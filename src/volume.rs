@@ -0,0 +1,233 @@
+//! builders that pack the downloaded pages of an issue into a single file
+
+use std::{fs::{self, File}, io::{BufWriter, Write}, path::{Path, PathBuf}, time::SystemTime};
+
+use crate::{
+    epub::{self, EpubImage, PACKAGE_DIR},
+    image_info::{self, ImageKind},
+    metadata::{comic_info_xml, IssueMetadata, PageInfo},
+    pdf::{DocumentInfo, PageSize, PdfImage, PdfWriter},
+    sites::{OutputFormats, SiteDownloaderError},
+};
+
+/// An issue inside an omnibus volume
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    pub title: String,
+    pub files: Vec<PathBuf>,
+}
+
+/// A page of the volume: its file, its name in the archive and the title of
+/// the chapter it starts, if it's the first of one
+struct VolumePage<'a> {
+    path: &'a Path,
+    name: String,
+    chapter: Option<&'a str>,
+}
+
+/// The pages of a single issue, with the names they were saved with
+fn issue_pages(files: &[PathBuf]) -> Result<Vec<VolumePage<'_>>, SiteDownloaderError> {
+    let mut pages = Vec::new();
+    for path in files {
+        pages.push(VolumePage { path, name: file_name(path)?.to_string(), chapter: None });
+    }
+    return Ok(pages);
+}
+
+/// The pages of every chapter, the names start with the number of the
+/// chapter so they don't collide and the readers sort them in order
+fn chapter_pages(chapters: &[Chapter]) -> Result<Vec<VolumePage<'_>>, SiteDownloaderError> {
+    let mut pages = Vec::new();
+    for (index, chapter) in chapters.iter().enumerate() {
+        for (page, path) in chapter.files.iter().enumerate() {
+            pages.push(VolumePage {
+                path,
+                name: format!("{:03}-{}", index + 1, file_name(path)?),
+                chapter: Some(chapter.title.as_str()).filter(|_| page == 0),
+            });
+        }
+    }
+    return Ok(pages);
+}
+
+fn file_name(path: &Path) -> Result<&str, SiteDownloaderError> {
+    return path.file_name()
+        .and_then(|e| e.to_str())
+        .ok_or_else(|| SiteDownloaderError::image(path, "the file name is not valid unicode"));
+}
+
+/// Creates a cbz archive at `out_path` containing `files` in the given
+/// order, with a ComicInfo.xml when there is the metadata
+pub fn create_cbz(files: &[PathBuf], out_path: &Path, metadata: Option<&IssueMetadata>) -> Result<(), SiteDownloaderError> {
+    return write_cbz(&issue_pages(files)?, out_path, metadata);
+}
+
+/// Creates a fixed-layout epub 3 at `out_path` with a page for every image
+/// in `files`, the first one is the cover
+pub fn create_epub(metadata: &IssueMetadata, files: &[PathBuf], out_path: &Path) -> Result<(), SiteDownloaderError> {
+    return write_epub(metadata, &issue_pages(files)?, out_path, false);
+}
+
+/// Creates a fixed-layout kepub at `out_path`, an epub that the Kobo
+/// e-readers open full screen in their own reader
+pub fn create_kepub(metadata: &IssueMetadata, files: &[PathBuf], out_path: &Path) -> Result<(), SiteDownloaderError> {
+    return write_epub(metadata, &issue_pages(files)?, out_path, true);
+}
+
+/// Creates a pdf document at `out_path`, one page per image in `files`,
+/// writing every page as soon as it's read
+pub fn create_pdf(metadata: &IssueMetadata, files: &[PathBuf], out_path: &Path, size: PageSize) -> Result<(), SiteDownloaderError> {
+    return write_pdf(metadata, &issue_pages(files)?, out_path, size);
+}
+
+/// Creates a volume at `out_path` with the pages of every chapter, each one
+/// with a bookmark or an entry in the table of contents
+pub fn create_omnibus(
+    format: OutputFormats,
+    metadata: &IssueMetadata,
+    chapters: &[Chapter],
+    out_path: &Path,
+    size: PageSize,
+) -> Result<(), SiteDownloaderError> {
+    let pages = chapter_pages(chapters)?;
+    match format {
+        OutputFormats::Cbz => return write_cbz(&pages, out_path, Some(metadata)),
+        OutputFormats::Epub => return write_epub(metadata, &pages, out_path, false),
+        OutputFormats::Kepub => return write_epub(metadata, &pages, out_path, true),
+        OutputFormats::Pdf => return write_pdf(metadata, &pages, out_path, size),
+    }
+}
+
+fn write_cbz(files: &[VolumePage], out_path: &Path, metadata: Option<&IssueMetadata>) -> Result<(), SiteDownloaderError> {
+    let file = File::create(out_path).map_err(|e| SiteDownloaderError::file_system(out_path, e))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+    let mut pages = Vec::new();
+    for page in files {
+        let content = fs::read(page.path).map_err(|e| SiteDownloaderError::file_system(page.path, e))?;
+        zip.start_file(page.name.as_str(), options).map_err(|e| SiteDownloaderError::zip(out_path, e))?;
+        zip.write_all(&content).map_err(|e| SiteDownloaderError::file_system(out_path, e))?;
+        let dimensions = image_info::image_dimensions(&content);
+        pages.push(PageInfo {
+            width: dimensions.map(|e| e.0),
+            height: dimensions.map(|e| e.1),
+            size: content.len() as u64,
+            bookmark: page.chapter.map(|e| e.to_string()),
+        });
+    }
+    if let Some(metadata) = metadata {
+        zip.start_file("ComicInfo.xml", options).map_err(|e| SiteDownloaderError::zip(out_path, e))?;
+        zip.write_all(comic_info_xml(metadata, &pages).as_bytes())
+            .map_err(|e| SiteDownloaderError::file_system(out_path, e))?;
+    }
+    zip.finish().map_err(|e| SiteDownloaderError::zip(out_path, e))?;
+    return Ok(());
+}
+
+fn write_epub(metadata: &IssueMetadata, files: &[VolumePage], out_path: &Path, kobo: bool) -> Result<(), SiteDownloaderError> {
+    let file = File::create(out_path).map_err(|e| SiteDownloaderError::file_system(out_path, e))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let stored = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Stored);
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+    let mut add_file = |name: &str, content: &[u8], options: zip::write::SimpleFileOptions| {
+        zip.start_file(name, options).map_err(|e| SiteDownloaderError::zip(out_path, e))?;
+        zip.write_all(content).map_err(|e| SiteDownloaderError::file_system(out_path, e))?;
+        return Ok::<(), SiteDownloaderError>(());
+    };
+    // the mimetype must be the first file and not compressed
+    add_file("mimetype", b"application/epub+zip", stored)?;
+    add_file("META-INF/container.xml", epub::CONTAINER_XML.as_bytes(), options)?;
+    if kobo {
+        add_file("META-INF/com.apple.ibooks.display-options.xml", epub::DISPLAY_OPTIONS_XML.as_bytes(), options)?;
+    }
+    let mut images = Vec::new();
+    let mut toc = Vec::new();
+    for (index, page) in files.iter().enumerate() {
+        let content = fs::read(page.path).map_err(|e| SiteDownloaderError::file_system(page.path, e))?;
+        let (kind, content) = epub_image(page.path, content)?;
+        let file_name = match page.name.rsplit_once('.') {
+            Some((stem, _)) => format!("{stem}.{}", kind.extension()),
+            None => format!("{}.{}", page.name, kind.extension()),
+        };
+        add_file(&format!("{PACKAGE_DIR}/images/{file_name}"), &content, options)?;
+        let dimensions = image_info::image_dimensions(&content);
+        images.push(EpubImage {
+            file_name,
+            media_type: kind.mime_type().to_string(),
+            width: dimensions.map(|e| e.0),
+            height: dimensions.map(|e| e.1),
+        });
+        if let Some(chapter) = page.chapter {
+            toc.push((chapter.to_string(), index));
+        }
+    }
+    if toc.is_empty() {
+        toc.push((epub::book_title(metadata), 0));
+    }
+    for (index, image) in images.iter().enumerate() {
+        let page = epub::page_xhtml(metadata, index, image, kobo);
+        add_file(&format!("{PACKAGE_DIR}/{}", epub::page_file_name(index)), page.as_bytes(), options)?;
+    }
+    add_file(&format!("{PACKAGE_DIR}/style.css"), epub::STYLE_CSS.as_bytes(), options)?;
+    add_file(&format!("{PACKAGE_DIR}/nav.xhtml"), epub::nav_xhtml(metadata, &toc).as_bytes(), options)?;
+    let opf = epub::content_opf(metadata, &images, SystemTime::now());
+    add_file(&format!("{PACKAGE_DIR}/content.opf"), opf.as_bytes(), options)?;
+    zip.finish().map_err(|e| SiteDownloaderError::zip(out_path, e))?;
+    return Ok(());
+}
+
+/// The page in a format every EPUB reader shows: jpeg, png and gif are kept,
+/// webp is converted to jpeg and avif, that this build can't decode, is an error
+fn epub_image(path: &Path, content: Vec<u8>) -> Result<(ImageKind, Vec<u8>), SiteDownloaderError> {
+    let Some(kind) = image_info::image_kind(&content) else {
+        return Err(SiteDownloaderError::image(path, "unknown image format"));
+    };
+    match kind {
+        ImageKind::Jpeg | ImageKind::Png | ImageKind::Gif => return Ok((kind, content)),
+        ImageKind::WebP => return Ok((ImageKind::Jpeg, to_jpeg(path, &content)?)),
+        ImageKind::Avif => return Err(SiteDownloaderError::image(path, "AVIF is not supported in this build, the page can't be put in an EPUB")),
+    }
+}
+
+#[cfg(feature = "images")]
+fn to_jpeg(path: &Path, content: &[u8]) -> Result<Vec<u8>, SiteDownloaderError> {
+    let image = image::load_from_memory(content).map_err(|e| SiteDownloaderError::image(path, e))?;
+    let mut buffer = Vec::new();
+    let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buffer, crate::pipeline::JPEG_QUALITY);
+    image.to_rgb8().write_with_encoder(encoder).map_err(|e| SiteDownloaderError::image(path, e))?;
+    return Ok(buffer);
+}
+
+/// Without the images feature the pages can't be converted
+#[cfg(not(feature = "images"))]
+fn to_jpeg(_path: &Path, _content: &[u8]) -> Result<Vec<u8>, SiteDownloaderError> {
+    return Err(SiteDownloaderError::FeatureDisabled { feature: "images" });
+}
+
+fn write_pdf(metadata: &IssueMetadata, files: &[VolumePage], out_path: &Path, size: PageSize) -> Result<(), SiteDownloaderError> {
+    let file = File::create(out_path).map_err(|e| SiteDownloaderError::file_system(out_path, e))?;
+    let mut pdf = PdfWriter::new(BufWriter::new(file), size).map_err(|e| SiteDownloaderError::file_system(out_path, e))?;
+    let title = epub::book_title(metadata);
+    if files.iter().all(|e| e.chapter.is_none()) {
+        pdf.add_bookmark(&title);
+    }
+    for page in files {
+        if let Some(chapter) = page.chapter {
+            pdf.add_bookmark(chapter);
+        }
+        let content = fs::read(page.path).map_err(|e| SiteDownloaderError::file_system(page.path, e))?;
+        let image = PdfImage::from_bytes(content).map_err(|e| SiteDownloaderError::image(page.path, e))?;
+        pdf.add_page(image).map_err(|e| SiteDownloaderError::file_system(out_path, e))?;
+    }
+    let info = DocumentInfo {
+        title,
+        author: metadata.writer.clone(),
+        subject: Some(metadata.series.clone()),
+        creation_date: Some(epub::utc_timestamp(SystemTime::now())),
+    };
+    pdf.finish(&info).map_err(|e| SiteDownloaderError::file_system(out_path, e))?;
+    return Ok(());
+}
@@ -0,0 +1,154 @@
+//! responses of a website in `tests/fixtures/<site>`, served by a local
+//! server so the strategies are tested without the network; the ones in the
+//! repository are written by hand with the markup of the websites
+//!
+//! `fixtures.json` lists the origins of the website, the first is the
+//! website itself and the others its hosts like the one of the images, and
+//! every response with its link and file. The origins in the responses are
+//! replaced with the server, the first one by its root and the others by
+//! `/_/<host>`, so the links found by the strategies come back to it.
+
+use std::{
+    fs,
+    net::TcpListener,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use serde::Deserialize;
+
+use super::{read_request, write_reply, Reply};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct Manifest {
+    origins: Vec<String>,
+    responses: Vec<Fixture>,
+}
+
+/// A response of the website, `url` is its link on the website
+#[derive(Debug, Clone, Deserialize)]
+struct Fixture {
+    url: String,
+    #[serde(default = "default_status")]
+    status: u16,
+    #[serde(default)]
+    content_type: Option<String>,
+    #[serde(default)]
+    location: Option<String>,
+    /// file with the body, none for an empty one
+    #[serde(default)]
+    file: Option<String>,
+}
+
+fn default_status() -> u16 {
+    return 200;
+}
+
+struct State {
+    dir: PathBuf,
+    manifest: Manifest,
+    url: String,
+    requests: Vec<String>,
+    missing: Vec<String>,
+}
+
+/// Local server with the fixtures of a website
+pub struct FixtureServer {
+    pub url: String,
+    state: Arc<Mutex<State>>,
+}
+
+impl FixtureServer {
+    /// Serves the fixtures in `tests/fixtures/<site>`
+    pub fn start(site: &str) -> FixtureServer {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(site);
+        let manifest_text = fs::read_to_string(dir.join("fixtures.json"))
+            .unwrap_or_else(|e| panic!("no fixtures for {site}: {e}"));
+        let manifest: Manifest = serde_json::from_str(&manifest_text).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(State { dir, manifest, url: url.clone(), requests: Vec::new(), missing: Vec::new() }));
+        let state_thread = state.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue; };
+                let path = read_request(&stream);
+                let reply = state_thread.lock().unwrap().reply(&path);
+                write_reply(&mut stream, &reply);
+            }
+        });
+        return FixtureServer { url, state };
+    }
+
+    /// Link on the server of `url` of the website
+    pub fn link(&self, url: &str) -> String {
+        return self.state.lock().unwrap().to_local(url);
+    }
+
+    /// Paths requested so far
+    pub fn requests(&self) -> Vec<String> {
+        return self.state.lock().unwrap().requests.clone();
+    }
+
+    /// Links of the website requested without a fixture
+    pub fn missing(&self) -> Vec<String> {
+        return self.state.lock().unwrap().missing.clone();
+    }
+}
+
+impl State {
+    fn reply(&mut self, path: &str) -> Reply {
+        self.requests.push(path.to_string());
+        let url = self.to_live(path);
+        let Some(fixture) = self.manifest.responses.iter().find(|e| e.url == url).cloned() else {
+            self.missing.push(url);
+            return Reply::status(404);
+        };
+        let mut body = match &fixture.file {
+            Some(file) => fs::read(self.dir.join(file)).unwrap(),
+            None => Vec::new(),
+        };
+        if fixture.content_type.as_deref().is_none_or(is_text) {
+            body = self.to_local(&String::from_utf8_lossy(&body)).into_bytes();
+        }
+        let mut reply = Reply { status: fixture.status, headers: vec![], body };
+        if let Some(content_type) = &fixture.content_type {
+            reply = reply.header("Content-Type", content_type);
+        }
+        if let Some(location) = &fixture.location {
+            reply = reply.header("Location", &self.to_local(location));
+        }
+        return reply;
+    }
+
+    /// Replaces the origins of the website with the server
+    fn to_local(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for (index, origin) in self.manifest.origins.iter().enumerate().rev() {
+            text = text.replace(origin.as_str(), &self.local_origin(index, origin));
+        }
+        return text;
+    }
+
+    /// The link on the website of a path of the server
+    fn to_live(&self, path: &str) -> String {
+        for (index, origin) in self.manifest.origins.iter().enumerate().skip(1) {
+            if let Some(rest) = path.strip_prefix(self.local_origin(index, origin).trim_start_matches(&self.url)) {
+                return format!("{origin}{rest}");
+            }
+        }
+        return format!("{}{path}", self.manifest.origins[0]);
+    }
+
+    fn local_origin(&self, index: usize, origin: &str) -> String {
+        if index == 0 {
+            return self.url.clone();
+        }
+        return format!("{}/_/{}", self.url, origin.split("://").nth(1).unwrap_or(origin));
+    }
+}
+
+fn is_text(content_type: &str) -> bool {
+    return ["text/", "json", "javascript", "xml"].iter().any(|e| content_type.contains(e));
+}
@@ -0,0 +1,116 @@
+#![allow(dead_code)]
+
+pub mod fixtures;
+
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+/// An empty directory for the test, `name` must be unique in the file
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("comic-dl-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    return dir;
+}
+
+/// The start of a png, enough to read its size
+pub fn png_header(width: u32, height: u32) -> Vec<u8> {
+    let mut bytes = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+    bytes.extend_from_slice(&width.to_be_bytes());
+    bytes.extend_from_slice(&height.to_be_bytes());
+    return bytes;
+}
+
+/// The header of a png of 800x1200 pixels, the page the fake websites serve
+pub fn png() -> Vec<u8> {
+    let mut bytes = png_header(800, 1200);
+    bytes.extend([8, 2, 0, 0, 0]);
+    return bytes;
+}
+
+/// An answer of the test server
+#[derive(Debug, Clone)]
+pub struct Reply {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Reply {
+    pub fn ok(body: impl Into<Vec<u8>>) -> Reply {
+        return Reply { status: 200, headers: vec![], body: body.into() };
+    }
+
+    pub fn status(status: u16) -> Reply {
+        return Reply { status, headers: vec![], body: vec![] };
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Reply {
+        self.headers.push((name.to_string(), value.to_string()));
+        return self;
+    }
+}
+
+/// Minimal http server on localhost, every request gets the next reply of
+/// the list, the last one is repeated when the list is over
+pub struct TestServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl TestServer {
+    pub fn start(replies: Vec<Reply>) -> TestServer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let requests_thread = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue; };
+                let path = read_request(&stream);
+                let reply = {
+                    let mut requests = requests_thread.lock().unwrap();
+                    requests.push(path);
+                    replies.get(requests.len() - 1).or(replies.last()).cloned().unwrap()
+                };
+                write_reply(&mut stream, &reply);
+            }
+        });
+        return TestServer { url, requests };
+    }
+
+    /// Paths requested so far
+    pub fn requests(&self) -> Vec<String> {
+        return self.requests.lock().unwrap().clone();
+    }
+}
+
+/// Reads the request and returns its path
+fn read_request(stream: &TcpStream) -> String {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+            break;
+        }
+    }
+    return request_line.split_whitespace().nth(1).unwrap_or_default().to_string();
+}
+
+fn write_reply(stream: &mut TcpStream, reply: &Reply) {
+    let mut head = format!("HTTP/1.1 {} Test\r\nContent-Length: {}\r\nConnection: close\r\n", reply.status, reply.body.len());
+    for (name, value) in &reply.headers {
+        head += &format!("{name}: {value}\r\n");
+    }
+    head += "\r\n";
+    let _ = stream.write_all(head.as_bytes());
+    let _ = stream.write_all(&reply.body);
+}
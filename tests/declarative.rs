@@ -0,0 +1,134 @@
+#![allow(clippy::needless_return)]
+mod common;
+
+use std::fs;
+
+use comic_dl::{
+    sites::{declarative::{load_site_definitions, DeclarativeStrategy, SiteDefinition}, ComicDownloader, Issue},
+    HttpClient, SiteDownloaderError,
+};
+use common::{png, temp_dir, Reply, TestServer};
+
+const TOML_DEFINITION: &str = r#"
+name = "example"
+domains = ["example.com", "*.example.org"]
+language = "en"
+
+[issues]
+url = "{url}/chapters?page={page}"
+selector = "ul.chapters a"
+name_pattern = "Chapter (\\d+)"
+newest_first = true
+
+[pages]
+selector = "div.reader img"
+attribute = "data-src"
+"#;
+
+#[test]
+fn toml_and_yaml_definitions_are_the_same() {
+    let yaml = r#"
+name: example
+domains: ["example.com", "*.example.org"]
+language: en
+issues:
+  url: "{url}/chapters?page={page}"
+  selector: ul.chapters a
+  name_pattern: "Chapter (\\d+)"
+  newest_first: true
+pages:
+  selector: div.reader img
+  attribute: data-src
+"#;
+
+    let definition = SiteDefinition::from_toml(TOML_DEFINITION).unwrap();
+
+    assert_eq!(definition, SiteDefinition::from_yaml(yaml).unwrap());
+    assert_eq!(definition.issues.attribute, "href");
+    assert_eq!(definition.issues.first_page, 1);
+    assert!(SiteDefinition::from_toml("name = \"example\"\ndomains = []\ncolour = \"red\"").is_err());
+}
+
+#[test]
+fn invalid_definitions_are_reported_and_skipped() {
+    let dir = temp_dir("load");
+    fs::write(dir.join("good.toml"), TOML_DEFINITION).unwrap();
+    fs::write(dir.join("selector.toml"), TOML_DEFINITION.replace("ul.chapters a", "ul..chapters")).unwrap();
+    fs::write(dir.join("regex.toml"), TOML_DEFINITION.replace("Chapter (\\\\d+)", "Chapter (")).unwrap();
+    fs::write(dir.join("notes.txt"), "not a definition").unwrap();
+
+    let loaded = load_site_definitions(&dir);
+
+    assert_eq!(loaded.len(), 3);
+    assert_eq!(loaded[0].as_ref().unwrap().definition.name, "example");
+    for (result, file) in loaded[1..].iter().zip(["regex.toml", "selector.toml"]) {
+        match result {
+            Err(SiteDownloaderError::SiteDefinition { path, .. }) => assert!(path.ends_with(file)),
+            other => panic!("{file} was loaded: {other:?}"),
+        }
+    }
+    let no_images = TOML_DEFINITION.replace("selector = \"div.reader img\"", "");
+    assert!(DeclarativeStrategy::new(SiteDefinition::from_toml(&no_images).unwrap()).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn the_issues_are_read_from_every_page_of_the_list() {
+    let server = TestServer::start(vec![
+        Reply::ok(r#"<ul class="chapters"><li><a href="/read/3">Chapter 3: The End</a></li><li><a href="/read/2">Chapter 2</a></li></ul>"#),
+        Reply::ok(r#"<ul class="chapters"><li><a href="/read/2">Chapter 2</a></li><li><a href="/read/1">Prologue</a></li></ul>"#),
+        Reply::ok(r#"<ul class="chapters"><li><a href="/read/1">Prologue</a></li></ul>"#),
+    ]);
+    let strategy = DeclarativeStrategy::new(SiteDefinition::from_toml(TOML_DEFINITION).unwrap()).unwrap();
+
+    let issues = strategy.get_issues_list(&HttpClient::new(), &format!("{}/comic/example/", server.url)).unwrap();
+
+    assert_eq!(server.requests(), ["/comic/example/chapters?page=1", "/comic/example/chapters?page=2", "/comic/example/chapters?page=3"]);
+    let found: Vec<(String, String)> = issues.into_iter().map(|e| (e.name, e.link)).collect();
+    assert_eq!(found, [
+        ("Prologue".to_string(), format!("{}/read/1", server.url)),
+        ("2".to_string(), format!("{}/read/2", server.url)),
+        ("3".to_string(), format!("{}/read/3", server.url)),
+    ]);
+    assert_eq!(strategy.get_comic_name(&HttpClient::new(), "https://example.com/comic/example/").unwrap(), "example");
+}
+
+#[test]
+fn the_images_of_the_issue_are_downloaded_as_pages() {
+    let server = TestServer::start(vec![
+        Reply::ok(r#"<div class="reader"><img data-src="/img/1.png"><img data-src=" img/2.png "><img data-src="/img/1.png"></div>"#),
+        Reply::ok(png()).header("Content-Type", "image/png"),
+    ]);
+    let strategy = DeclarativeStrategy::new(SiteDefinition::from_toml(TOML_DEFINITION).unwrap()).unwrap();
+    let dir = temp_dir("pages");
+    let issue = Issue { name: "1".to_string(), link: format!("{}/read/1/", server.url) };
+
+    strategy.download_issue(&HttpClient::new(), &dir, &issue).unwrap();
+
+    assert_eq!(server.requests(), ["/read/1/", "/img/1.png", "/read/1/img/2.png"]);
+    assert!(dir.join("1").join("0001.png").exists());
+    assert!(dir.join("1").join("0002.png").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn names_that_are_not_a_single_directory_are_refused() {
+    let server = TestServer::start(vec![
+        Reply::ok(r#"<a href="/read/1">..</a> <a href="/read/2">  </a> <a href="/read/3">.</a> <a href="/read/4">Chapter 4</a>"#),
+    ]);
+    let definition = "name = \"example\"\ndomains = [\"example.com\"]\ncomic_name = \"/comic/(.+)$\"\n[issues]\nselector = \"a\"\n[pages]\nselector = \"img\"\n";
+    let strategy = DeclarativeStrategy::new(SiteDefinition::from_toml(definition).unwrap()).unwrap();
+    let client = HttpClient::new();
+    let dir = temp_dir("names");
+
+    let issues = strategy.get_issues_list(&client, &format!("{}/comic/example", server.url)).unwrap();
+
+    assert_eq!(issues, [Issue { name: "Chapter 4".to_string(), link: format!("{}/read/4", server.url) }]);
+    assert!(strategy.get_comic_name(&client, "https://example.com/comic/..").is_err());
+    assert!(strategy.get_comic_name(&client, "https://example.com/comic/a/b").is_err());
+    let parent = Issue { name: "..".to_string(), link: format!("{}/read/1", server.url) };
+    assert!(matches!(strategy.download_issue(&client, &dir.join("comic"), &parent), Err(SiteDownloaderError::ParsingError { .. })));
+    assert!(!dir.join("comic").exists());
+    assert_eq!(server.requests().len(), 1);
+    fs::remove_dir_all(&dir).unwrap();
+}
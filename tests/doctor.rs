@@ -0,0 +1,96 @@
+#![allow(clippy::needless_return)]
+mod common;
+
+use comic_dl::{
+    doctor::{check_site, load_canaries, SiteStatus, Stage, StageStatus},
+    sites::{
+        readcomic_me::ReadcomicMeStrategy,
+        registry::{Capabilities, Site},
+        scanita_org::ScanitaOrgStrategy,
+        zerocalcare_net::ZerocalcareNetStrategy,
+    },
+    HttpClient,
+    SiteDownloaderError,
+};
+use common::{fixtures::FixtureServer, Reply, TestServer};
+
+/// The website without search, that always goes to the real website
+fn without_search(mut site: Site) -> Site {
+    site.change_capabilities(Capabilities { search: false, metadata: true });
+    return site;
+}
+
+#[test]
+fn the_comics_of_the_fixtures_are_healthy() {
+    let sites = [
+        ("readcomic.me", ReadcomicMeStrategy::site(), "https://readcomic.me/comic/batman-2016"),
+        ("scanita.org", ScanitaOrgStrategy::site(), "https://scanita.org/manga/one-piece"),
+        ("zerocalcare.net", ZerocalcareNetStrategy::site(), "https://www.zerocalcare.net/storie-a-fumetti/una-storia/"),
+    ];
+    for (name, site, comic) in sites {
+        let server = FixtureServer::start(name);
+        let site = without_search(site);
+        let canary = server.link(comic);
+
+        let report = check_site(&HttpClient::new(), &site, Some(&canary));
+
+        assert!(report.is_healthy(), "{report:#?}");
+        let statuses: Vec<(Stage, StageStatus)> = report.stages.iter().map(|e| (e.stage, e.status)).collect();
+        assert_eq!(statuses, [
+            (Stage::ComicName, StageStatus::Ok),
+            (Stage::IssueList, StageStatus::Ok),
+            (Stage::Metadata, StageStatus::Ok),
+            (Stage::Pages, StageStatus::Ok),
+            (Stage::Search, StageStatus::Skipped),
+        ]);
+        assert!(server.missing().is_empty(), "no fixtures for {:?}", server.missing());
+    }
+}
+
+#[test]
+fn a_selector_that_stops_matching_is_reported_with_its_stage() {
+    let server = TestServer::start(vec![
+        Reply::ok("<html><body><ul id='chapters'><li><a href='/issue-1'>Issue #1</a></li></ul></body></html>"),
+        Reply::ok("<html><head><meta property='og:title' content='Batman'></head></html>"),
+    ]);
+    let site = without_search(ReadcomicMeStrategy::site());
+
+    let report = check_site(&HttpClient::new(), &site, Some(&format!("{}/comic/batman", server.url)));
+
+    let broken = report.broken_stage().unwrap();
+    assert_eq!(broken.stage, Stage::IssueList);
+    assert_eq!(broken.detail, "no issues found");
+    let pages = report.stages.iter().find(|e| e.stage == Stage::Pages).unwrap();
+    assert_eq!(pages.status, StageStatus::Skipped);
+}
+
+#[test]
+fn websites_without_a_canary_are_not_checked() {
+    let site = ZerocalcareNetStrategy::site();
+
+    let report = check_site(&HttpClient::new(), &site, None);
+
+    assert!(report.url.is_none());
+    assert!(report.stages.is_empty());
+    assert_eq!(report.status(), SiteStatus::NotChecked);
+    assert!(!report.is_healthy());
+}
+
+#[test]
+fn the_canaries_of_the_user_are_read_by_website() {
+    let dir = std::env::temp_dir().join(format!("comic-dl-canaries-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("canaries.toml");
+
+    assert!(load_canaries(&path).unwrap().is_empty());
+
+    std::fs::write(&path, "\"zerocalcare.net\" = \"https://www.zerocalcare.net/storie-a-fumetti/a-comic/\"\n").unwrap();
+    let canaries = load_canaries(&path).unwrap();
+    assert_eq!(canaries["zerocalcare.net"], "https://www.zerocalcare.net/storie-a-fumetti/a-comic/");
+
+    std::fs::write(&path, "\"zerocalcare.net\" = 3\n").unwrap();
+    let error = load_canaries(&path).unwrap_err();
+    assert!(matches!(error, SiteDownloaderError::Canaries { .. }), "{error:?}");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
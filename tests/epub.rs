@@ -0,0 +1,127 @@
+#![allow(clippy::needless_return)]
+mod common;
+
+use std::{fs, io::Read, path::PathBuf, time::{Duration, UNIX_EPOCH}};
+
+use comic_dl::{epub, metadata::IssueMetadata, volume, Issue, OutputFormats};
+use common::{png_header, temp_dir};
+
+fn read_entry(archive: &mut zip::ZipArchive<fs::File>, name: &str) -> String {
+    let mut content = String::new();
+    archive.by_name(name).unwrap().read_to_string(&mut content).unwrap();
+    return content;
+}
+
+#[test]
+fn timestamps_are_in_utc() {
+    assert_eq!(epub::utc_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+    assert_eq!(epub::utc_timestamp(UNIX_EPOCH + Duration::from_secs(1709210096)), "2024-02-29T12:34:56Z");
+}
+
+#[test]
+fn epub_has_a_page_for_every_image() {
+    let dir = temp_dir("epub");
+    let pages: Vec<PathBuf> = (1..=3)
+        .map(|n| {
+            let path = dir.join(format!("{n:04}.png"));
+            fs::write(&path, png_header(800, 1200 + n)).unwrap();
+            path
+        })
+        .collect();
+    let issue = Issue { name: "Issue-2".to_string(), link: "https://readcomic.me/batman/issue-2".to_string() };
+    let metadata = IssueMetadata { language: Some("en".to_string()), ..IssueMetadata::new("batman", &issue) };
+    let out_path = dir.join(format!("batman-2.{}", OutputFormats::Epub.format_string()));
+
+    volume::create_epub(&metadata, &pages, &out_path).unwrap();
+
+    let mut archive = zip::ZipArchive::new(fs::File::open(&out_path).unwrap()).unwrap();
+    {
+        let mimetype = archive.by_index(0).unwrap();
+        assert_eq!(mimetype.name(), "mimetype");
+        assert_eq!(mimetype.compression(), zip::CompressionMethod::Stored);
+    }
+    assert!(read_entry(&mut archive, "META-INF/container.xml").contains("OEBPS/content.opf"));
+    let opf = read_entry(&mut archive, "OEBPS/content.opf");
+    assert!(opf.contains("<dc:title>batman - Issue-2</dc:title>"));
+    assert!(opf.contains("<dc:language>en</dc:language>"));
+    assert!(opf.contains("<meta property=\"rendition:layout\">pre-paginated</meta>"));
+    assert!(opf.contains("<meta refines=\"#series\" property=\"group-position\">2</meta>"));
+    assert!(opf.contains("href=\"images/0001.png\" media-type=\"image/png\" properties=\"cover-image\""));
+    let spine: Vec<&str> = opf.lines().filter(|e| e.contains("<itemref")).collect();
+    assert_eq!(spine.len(), 3);
+    assert!(read_entry(&mut archive, "OEBPS/nav.xhtml").contains("epub:type=\"toc\""));
+    let page = read_entry(&mut archive, "OEBPS/page-0003.xhtml");
+    assert!(page.contains("content=\"width=800, height=1203\""));
+    assert!(page.contains("src=\"images/0003.png\""));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn kepub_pages_have_the_kobo_markup() {
+    let dir = temp_dir("kepub");
+    let page = dir.join("0001.png");
+    fs::write(&page, png_header(1264, 1680)).unwrap();
+    let issue = Issue { name: "1".to_string(), link: "https://readcomic.me/batman/issue-1".to_string() };
+    let out_path = dir.join(format!("batman-1.{}", OutputFormats::Kepub.format_string()));
+    assert!(out_path.to_string_lossy().ends_with(".kepub.epub"));
+
+    volume::create_kepub(&IssueMetadata::new("batman", &issue), &[page], &out_path).unwrap();
+
+    let mut archive = zip::ZipArchive::new(fs::File::open(&out_path).unwrap()).unwrap();
+    let options = read_entry(&mut archive, "META-INF/com.apple.ibooks.display-options.xml");
+    assert!(options.contains("<option name=\"fixed-layout\">true</option>"));
+    let page = read_entry(&mut archive, "OEBPS/page-0001.xhtml");
+    assert!(page.contains("<span class=\"koboSpan\" id=\"kobo.1.1\"><img src=\"images/0001.png\""));
+    assert!(page.contains("content=\"width=1264, height=1680\""));
+    assert!(read_entry(&mut archive, "OEBPS/content.opf").contains("pre-paginated"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(feature = "images")]
+#[test]
+fn webp_pages_are_converted_to_jpeg() {
+    let dir = temp_dir("epub-webp");
+    let path = dir.join("0001.webp");
+    image::RgbImage::from_pixel(40, 60, image::Rgb([200, 30, 30])).save_with_format(&path, image::ImageFormat::WebP).unwrap();
+    let issue = Issue { name: "1".to_string(), link: "https://readcomic.me/batman/issue-1".to_string() };
+    let out_path = dir.join("batman-1.epub");
+
+    volume::create_epub(&IssueMetadata::new("batman", &issue), &[path], &out_path).unwrap();
+
+    let mut archive = zip::ZipArchive::new(fs::File::open(&out_path).unwrap()).unwrap();
+    let opf = read_entry(&mut archive, "OEBPS/content.opf");
+    assert!(opf.contains("href=\"images/0001.jpg\" media-type=\"image/jpeg\""), "{opf}");
+    assert!(!opf.contains("webp"));
+    let mut image = Vec::new();
+    archive.by_name("OEBPS/images/0001.jpg").unwrap().read_to_end(&mut image).unwrap();
+    assert_eq!(image::guess_format(&image).unwrap(), image::ImageFormat::Jpeg);
+    assert!(read_entry(&mut archive, "OEBPS/page-0001.xhtml").contains("src=\"images/0001.jpg\""));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn pages_of_unknown_formats_are_an_error() {
+    let dir = temp_dir("epub-unknown");
+    let path = dir.join("0001.jpg");
+    fs::write(&path, "<html>not found</html>").unwrap();
+    let issue = Issue { name: "1".to_string(), link: "https://readcomic.me/batman/issue-1".to_string() };
+
+    let error = volume::create_epub(&IssueMetadata::new("batman", &issue), &[path], &dir.join("batman-1.epub")).unwrap_err();
+
+    assert!(error.to_string().contains("unknown image format"), "{error}");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn avif_pages_are_an_error() {
+    let dir = temp_dir("epub-avif");
+    let path = dir.join("0001.avif");
+    fs::write(&path, b"\0\0\0\x1cftypavif\0\0\0\0avifmif1miaf").unwrap();
+    let issue = Issue { name: "1".to_string(), link: "https://readcomic.me/batman/issue-1".to_string() };
+    let out_path = dir.join("batman-1.epub");
+
+    let error = volume::create_epub(&IssueMetadata::new("batman", &issue), &[path], &out_path).unwrap_err();
+
+    assert!(error.to_string().contains("AVIF is not supported in this build"), "{error}");
+    fs::remove_dir_all(dir).unwrap();
+}
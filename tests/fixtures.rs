@@ -0,0 +1,95 @@
+#![allow(clippy::needless_return)]
+//! the strategies on pages written by hand with the markup of their
+//! websites, in `tests/fixtures`
+mod common;
+
+use std::{fs::{self, File}, path::Path};
+
+use comic_dl::{
+    sites::{readcomic_me::ReadcomicMeStrategy, scanita_org::ScanitaOrgStrategy, zerocalcare_net::ZerocalcareNetStrategy},
+    ComicDownloader, ComicUrl, HttpClient, Issue,
+};
+use common::{fixtures::FixtureServer, temp_dir};
+
+fn zip_entries(path: &Path) -> Vec<String> {
+    let mut archive = zip::ZipArchive::new(File::open(path).unwrap()).unwrap();
+    return (0..archive.len())
+        .map(|i| archive.by_index(i).unwrap().name().to_string())
+        .collect();
+}
+
+/// Downloads the issues of `url` matching `selection` like the command line
+/// does, with the website replaced by `server`
+fn download(server: &FixtureServer, url: &str, selection: &str, dir: &Path) {
+    let mut comic = ComicUrl::new(url).unwrap();
+    comic.url = server.link(url);
+    comic.change_path(dir.to_str().unwrap()).unwrap();
+    comic.change_selection(vec![selection.parse().unwrap()]);
+    comic.download_all().unwrap();
+}
+
+#[test]
+fn readcomic_me() {
+    let server = FixtureServer::start("readcomic.me");
+    let client = HttpClient::new();
+    let url = server.link("https://readcomic.me/comic/batman-2016");
+    let dir = temp_dir("readcomic");
+
+    assert_eq!(ReadcomicMeStrategy.get_comic_name(&client, &url).unwrap(), "batman-2016");
+    let issues = ReadcomicMeStrategy.get_issues_list(&client, &url).unwrap();
+    let names: Vec<&str> = issues.iter().map(|e| e.name.as_str()).collect();
+    assert_eq!(names, ["1", "2", "3"]);
+    assert_eq!(issues[0].link, server.link("https://readcomic.me/comic/batman-2016/issue-1"));
+
+    ReadcomicMeStrategy.download_issue(&client, &dir, &issues[0]).unwrap();
+    let pages: Vec<String> = fs::read_dir(dir.join("1")).unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+        .filter(|e| e.ends_with(".jpg"))
+        .collect();
+    assert_eq!(pages.len(), 2);
+    fs::remove_dir_all(&dir).unwrap();
+
+    let dir = temp_dir("readcomic-volume");
+    download(&server, "https://readcomic.me/comic/batman-2016", "1", &dir);
+    assert_eq!(zip_entries(&dir.join("batman-2016-1.cbz")), ["0001.jpg", "0002.jpg", "ComicInfo.xml"]);
+    assert!(server.missing().is_empty(), "no fixtures for {:?}", server.missing());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn scanita_org() {
+    let server = FixtureServer::start("scanita.org");
+    let client = HttpClient::new();
+    let url = server.link("https://scanita.org/manga/one-piece");
+    let dir = temp_dir("scanita");
+
+    assert_eq!(ScanitaOrgStrategy.get_comic_name(&client, &url).unwrap(), "one-piece");
+    let issues = ScanitaOrgStrategy.get_issues_list(&client, &url).unwrap();
+    assert_eq!(issues, [
+        Issue { name: "Capitolo 1".to_string(), link: server.link("https://scanita.org/scan/1001") },
+        Issue { name: "Capitolo 2".to_string(), link: server.link("https://scanita.org/scan/1002") },
+    ]);
+
+    download(&server, "https://scanita.org/manga/one-piece", "1", &dir);
+    assert_eq!(zip_entries(&dir.join("one-piece-Capitolo 1.cbz")), ["0001.png", "0002.png", "ComicInfo.xml"]);
+    assert!(!dir.join("Capitolo 1").exists());
+    assert!(server.missing().is_empty(), "no fixtures for {:?}", server.missing());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn zerocalcare_net() {
+    let server = FixtureServer::start("zerocalcare.net");
+    let client = HttpClient::new();
+    let url = server.link("https://www.zerocalcare.net/storie-a-fumetti/una-storia/");
+    let dir = temp_dir("zerocalcare");
+
+    assert_eq!(ZerocalcareNetStrategy.get_comic_name(&client, &url).unwrap(), "una-storia");
+    let issues = ZerocalcareNetStrategy.get_issues_list(&client, &url).unwrap();
+    assert_eq!(issues, [Issue { name: "una-storia".to_string(), link: url.clone() }]);
+
+    download(&server, "https://www.zerocalcare.net/storie-a-fumetti/una-storia/", "/.*/", &dir);
+    assert_eq!(zip_entries(&dir.join("una-storia-una-storia.cbz")), ["0001.jpg", "0002.jpg", "0003.jpg", "ComicInfo.xml"]);
+    assert!(server.missing().is_empty(), "no fixtures for {:?}", server.missing());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,113 @@
+#![allow(clippy::needless_return)]
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use comic_dl::{
+    library::{FollowedComic, Library, LIBRARY_FILE},
+    pdf::PageSize,
+    pipeline::{DeviceProfile, ImagePipeline},
+    ComicUrl,
+    OutputFormats,
+    SiteDownloaderError,
+};
+use common::temp_dir;
+
+fn followed(url: &str, format: OutputFormats) -> FollowedComic {
+    let mut comic = ComicUrl::new(url).unwrap();
+    comic.change_format(format);
+    return FollowedComic::new(&comic);
+}
+
+#[test]
+fn followed_comics_are_saved() {
+    let dir = temp_dir("saved");
+    assert_eq!(Library::load(&dir).unwrap(), Library::default());
+
+    let mut library = Library::default();
+    assert!(library.follow(followed("https://readcomic.me/comic/batman", OutputFormats::Cbz)));
+    assert!(library.follow(followed("https://scanita.org/manga/one-piece", OutputFormats::Pdf)));
+    library.save(&dir).unwrap();
+
+    let loaded = Library::load(&dir).unwrap();
+    assert_eq!(loaded, library);
+    assert_eq!(loaded.comics[0].name, "batman");
+    assert_eq!(loaded.comics[0].path, PathBuf::from("batman"));
+    assert!(dir.join(LIBRARY_FILE).exists());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn following_again_updates_the_settings() {
+    let mut library = Library::default();
+    library.follow(followed("https://readcomic.me/comic/batman", OutputFormats::Cbz));
+    assert!(!library.follow(followed("https://readcomic.me/comic/batman", OutputFormats::Pdf)));
+    assert_eq!(library.comics.len(), 1);
+    assert_eq!(library.comics[0].format, OutputFormats::Pdf);
+
+    assert!(library.unfollow("https://readcomic.me/comic/batman"));
+    assert!(!library.unfollow("https://readcomic.me/comic/batman"));
+    assert!(library.comics.is_empty());
+}
+
+#[test]
+fn opened_comics_download_inside_the_library() {
+    let dir = temp_dir("open");
+    let comic = followed("https://scanita.org/manga/one-piece", OutputFormats::Pdf)
+        .open(&dir)
+        .unwrap();
+    assert_eq!(comic.download_path, dir.join("one-piece"));
+    assert_eq!(comic.format, OutputFormats::Pdf);
+    assert!(comic.download_path.is_dir());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn a_broken_comic_does_not_stop_the_update() {
+    let dir = temp_dir("update");
+    let mut library = Library::default();
+    library.follow(FollowedComic {
+        url: "https://example.com/comic/gone".to_string(),
+        name: "gone".to_string(),
+        path: PathBuf::from("gone"),
+        format: OutputFormats::Cbz,
+        pipeline: ImagePipeline::default(),
+        page_size: PageSize::default(),
+        omnibus: None,
+        selection: Vec::new(),
+    });
+    library.follow(FollowedComic {
+        url: "https://example.com/comic/also-gone".to_string(),
+        name: "also-gone".to_string(),
+        path: PathBuf::from("also-gone"),
+        format: OutputFormats::Cbz,
+        pipeline: ImagePipeline::default(),
+        page_size: PageSize::default(),
+        omnibus: None,
+        selection: Vec::new(),
+    });
+
+    let updates = library.update(&dir, 1, |_| {});
+
+    assert_eq!(updates.len(), 2);
+    assert!(updates.iter().all(|e| matches!(e.error, Some(SiteDownloaderError::UnsupportedWebsite { .. }))));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn followed_comics_keep_their_page_processing() {
+    let dir = temp_dir("pipeline");
+    let mut comic = ComicUrl::new("https://readcomic.me/comic/batman").unwrap();
+    comic.change_pipeline(DeviceProfile::KoboLibra.pipeline());
+    let mut library = Library::default();
+    library.follow(FollowedComic::new(&comic));
+    library.follow(followed("https://scanita.org/manga/one-piece", OutputFormats::Cbz));
+    library.save(&dir).unwrap();
+
+    let content = fs::read_to_string(dir.join(LIBRARY_FILE)).unwrap();
+    assert_eq!(content.matches("\"pipeline\"").count(), 1);
+    let loaded = Library::load(&dir).unwrap();
+    assert_eq!(loaded.comics[0].open(&dir).unwrap().pipeline, DeviceProfile::KoboLibra.pipeline());
+    assert!(loaded.comics[1].pipeline.is_noop());
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,177 @@
+#![allow(clippy::needless_return)]
+mod common;
+
+use std::time::{Duration, Instant};
+
+use comic_dl::{HttpClient, RateLimit, RetryPolicy, SiteDownloaderError};
+use common::{Reply, TestServer};
+
+fn quick_retries(retries: u32) -> HttpClient {
+    let mut client = HttpClient::new();
+    client.change_retry_policy(RetryPolicy {
+        retries,
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(5),
+        jitter: 0.0,
+    });
+    return client;
+}
+
+#[test]
+fn backoff_doubles_up_to_the_maximum() {
+    let policy = RetryPolicy {
+        retries: 5,
+        initial_backoff: Duration::from_millis(100),
+        max_backoff: Duration::from_millis(350),
+        jitter: 0.0,
+    };
+    assert_eq!(policy.backoff(1), Duration::from_millis(100));
+    assert_eq!(policy.backoff(2), Duration::from_millis(200));
+    assert_eq!(policy.backoff(3), Duration::from_millis(350));
+    assert_eq!(policy.backoff(30), Duration::from_millis(350));
+}
+
+#[test]
+fn jitter_only_shortens_the_backoff() {
+    let policy = RetryPolicy { jitter: 0.5, ..RetryPolicy::default() };
+    for _ in 0..100 {
+        let backoff = policy.backoff(2);
+        assert!(backoff <= policy.initial_backoff * 2);
+        assert!(backoff >= policy.initial_backoff);
+    }
+}
+
+#[test]
+fn server_errors_and_throttling_are_retried() {
+    let server = TestServer::start(vec![
+        Reply::status(503),
+        Reply::status(429),
+        Reply::ok("finally"),
+    ]);
+    let text = quick_retries(3).get_text(&server.url).unwrap();
+    assert_eq!(text, "finally");
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[test]
+fn retries_run_out() {
+    let server = TestServer::start(vec![Reply::status(500)]);
+    let error = quick_retries(2).get_text(&server.url).unwrap_err();
+    assert!(matches!(error, SiteDownloaderError::HttpStatus { status, .. } if status.as_u16() == 500));
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[test]
+fn client_errors_are_not_retried() {
+    let server = TestServer::start(vec![Reply::status(404), Reply::ok("never")]);
+    let error = quick_retries(3).get_text(&server.url).unwrap_err();
+    assert!(matches!(error, SiteDownloaderError::HttpStatus { status, .. } if status.as_u16() == 404));
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[test]
+fn retry_after_is_honoured() {
+    let server = TestServer::start(vec![
+        Reply::status(429).header("Retry-After", "1"),
+        Reply::ok("done"),
+    ]);
+    let mut client = quick_retries(1);
+    client.change_retry_policy(RetryPolicy { max_backoff: Duration::from_secs(5), ..client.retry_policy().clone() });
+    let start = Instant::now();
+    client.get_text(&server.url).unwrap();
+    assert!(start.elapsed() >= Duration::from_secs(1));
+}
+
+#[test]
+fn retry_after_is_capped_by_the_maximum_backoff() {
+    let server = TestServer::start(vec![
+        Reply::status(503).header("Retry-After", "86400"),
+        Reply::ok("done"),
+    ]);
+    let start = Instant::now();
+    quick_retries(1).get_text(&server.url).unwrap();
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn connection_errors_are_retried() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    drop(listener);
+    let error = quick_retries(1).get_text(&url).unwrap_err();
+    assert!(matches!(error, SiteDownloaderError::Request { .. }));
+}
+
+#[test]
+fn the_redirect_policy_keeps_the_settings_of_the_builder() {
+    let server = TestServer::start(vec![Reply::ok("ok")]);
+    let client = HttpClient::with_builder(|| reqwest::blocking::Client::builder().https_only(true)).unwrap();
+
+    let client = client.with_redirect_policy(reqwest::redirect::Policy::none()).unwrap();
+
+    assert!(client.get_text(&server.url).is_err());
+    assert!(server.requests().is_empty());
+}
+
+fn limited(limit: RateLimit) -> HttpClient {
+    let mut client = quick_retries(0);
+    client.change_default_rate_limit(limit);
+    return client;
+}
+
+#[test]
+fn token_bucket_spaces_out_requests() {
+    let server = TestServer::start(vec![Reply::ok("page")]);
+    let client = limited(RateLimit { requests_per_second: 20.0, burst: 1, min_delay: Duration::ZERO });
+    let start = Instant::now();
+    for _ in 0..5 {
+        client.get_text(&server.url).unwrap();
+    }
+    assert!(start.elapsed() >= Duration::from_millis(200));
+}
+
+#[test]
+fn min_delay_is_respected() {
+    let server = TestServer::start(vec![Reply::ok("page")]);
+    let client = limited(RateLimit { requests_per_second: 0.0, burst: 1, min_delay: Duration::from_millis(100) });
+    let start = Instant::now();
+    for _ in 0..3 {
+        client.get_text(&server.url).unwrap();
+    }
+    assert!(start.elapsed() >= Duration::from_millis(200));
+}
+
+#[test]
+fn clones_share_the_limit_across_threads() {
+    let server = TestServer::start(vec![Reply::ok("page")]);
+    let client = limited(RateLimit { requests_per_second: 0.0, burst: 1, min_delay: Duration::from_millis(100) });
+    let start = Instant::now();
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let client = client.clone();
+            let url = server.url.clone();
+            std::thread::spawn(move || client.get_text(&url).unwrap())
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    assert!(start.elapsed() >= Duration::from_millis(300));
+}
+
+#[test]
+fn hosts_have_their_own_limits() {
+    let slow = TestServer::start(vec![Reply::ok("slow")]);
+    let fast = TestServer::start(vec![Reply::ok("fast")]);
+    let mut client = limited(RateLimit::unlimited());
+    let slow_host = slow.url.trim_start_matches("http://").to_string();
+    client.change_host_rate_limit(&slow_host, RateLimit { requests_per_second: 0.0, burst: 1, min_delay: Duration::from_secs(5) });
+
+    client.get_text(&slow.url).unwrap();
+    let start = Instant::now();
+    for _ in 0..5 {
+        client.get_text(&fast.url).unwrap();
+    }
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(client.rate_limit(&slow_host).min_delay, Duration::from_secs(5));
+}
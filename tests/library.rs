@@ -0,0 +1,119 @@
+#![allow(clippy::needless_return)]
+mod common;
+
+use std::{fs::{self, File}, io::Write, path::PathBuf};
+
+use std::error::Error;
+
+use comic_dl::{identify_website, volume, ComicUrl, Issue, OutputFormats, SiteDownloaderError};
+use common::temp_dir;
+
+fn write_pages(issue_path: &PathBuf, pages: &[&str]) -> Vec<PathBuf> {
+    fs::create_dir_all(issue_path).unwrap();
+    return pages.iter()
+        .map(|page| {
+            let path = issue_path.join(page);
+            File::create(&path).unwrap().write_all(page.as_bytes()).unwrap();
+            path
+        })
+        .collect();
+}
+
+fn zip_entries(path: &PathBuf) -> Vec<String> {
+    let mut archive = zip::ZipArchive::new(File::open(path).unwrap()).unwrap();
+    return (0..archive.len())
+        .map(|i| archive.by_index(i).unwrap().name().to_string())
+        .collect();
+}
+
+#[test]
+fn supported_websites_are_identified() {
+    assert!(identify_website("https://readcomic.me/comic/batman").is_ok());
+    assert!(identify_website("https://scanita.org/manga/one-piece").is_ok());
+    assert!(identify_website("https://www.zerocalcare.net/storie-a-fumetti/una-storia/").is_ok());
+    assert!(identify_website("https://example.com/comic/batman").is_err());
+    assert!(identify_website("not a link").is_err());
+}
+
+#[test]
+fn comic_name_comes_from_the_link() {
+    let comic = ComicUrl::new("https://readcomic.me/comic/batman").unwrap();
+    assert_eq!(comic.comic_name, "batman");
+    assert_eq!(comic.format, OutputFormats::Cbz);
+
+    let comic = ComicUrl::new("https://scanita.org/manga/one-piece").unwrap();
+    assert_eq!(comic.comic_name, "one-piece");
+}
+
+#[test]
+fn output_formats_extensions() {
+    assert_eq!(OutputFormats::Cbz.format_string(), "cbz");
+    assert_eq!(OutputFormats::Pdf.format_string(), "pdf");
+}
+
+#[test]
+fn cbz_keeps_the_order_of_the_pages() {
+    let dir = temp_dir("cbz-order");
+    let files = write_pages(&dir.join("pages"), &["0002.jpg", "0001.jpg"]);
+    let out_path = dir.join("out.cbz");
+
+    volume::create_cbz(&files, &out_path, None).unwrap();
+
+    assert_eq!(zip_entries(&out_path), vec!["0002.jpg", "0001.jpg"]);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn create_volume_packs_the_issue_and_cleans_up() {
+    let dir = temp_dir("create-volume");
+    let mut comic = ComicUrl::new("https://readcomic.me/comic/batman").unwrap();
+    comic.change_path(dir.to_str().unwrap()).unwrap();
+    let issue = Issue { name: "1".to_string(), link: "https://readcomic.me/comic/batman/issue-1".to_string() };
+    let issue_path = dir.join(&issue.name);
+    write_pages(&issue_path, &["0003.jpg", "0001.jpg", "0002.jpg"]);
+
+    comic.create_volume(&issue, &issue_path).unwrap();
+
+    let out_path = dir.join("batman-1.cbz");
+    assert_eq!(zip_entries(&out_path), vec!["0001.jpg", "0002.jpg", "0003.jpg", "ComicInfo.xml"]);
+    assert!(!issue_path.exists());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn change_path_rejects_missing_directories() {
+    let mut comic = ComicUrl::new("https://readcomic.me/comic/batman").unwrap();
+    assert!(comic.change_path("/this/path/does/not/exist").is_err());
+}
+
+#[test]
+fn unsupported_websites_are_reported_with_their_link() {
+    let error = identify_website("https://example.com/comic/batman").unwrap_err();
+    assert!(matches!(error, SiteDownloaderError::UnsupportedWebsite { .. }));
+    assert!(error.to_string().contains("https://example.com/comic/batman"));
+
+    let error = identify_website("not a link").unwrap_err();
+    assert!(matches!(error, SiteDownloaderError::InvalidUrl { .. }));
+}
+
+#[test]
+fn errors_keep_the_issue_and_page_context() {
+    let dir = temp_dir("error-context");
+    let comic = ComicUrl::new("https://readcomic.me/comic/batman").unwrap();
+    let missing = dir.join("missing");
+    let error = comic
+        .create_volume(&Issue { name: "7".to_string(), link: String::new() }, &missing)
+        .unwrap_err()
+        .in_page("7", 3)
+        .in_issue("7");
+
+    assert_eq!(error.to_string().lines().count(), 1);
+    assert!(error.to_string().starts_with("issue 7: page 3 of issue 7: "));
+    let page = error.source().unwrap();
+    let file_system = page.source().unwrap();
+    assert!(matches!(
+        file_system.downcast_ref::<SiteDownloaderError>(),
+        Some(SiteDownloaderError::FileSystemError { path, .. }) if *path == missing
+    ));
+    fs::remove_dir_all(dir).unwrap();
+}
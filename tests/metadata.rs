@@ -0,0 +1,112 @@
+#![allow(clippy::needless_return)]
+mod common;
+
+use std::{fs, io::Read};
+
+use comic_dl::{
+    image_info::{detect_kind, image_dimensions, image_kind, kind_from_mime_type, ImageKind},
+    metadata::{comic_info_xml, IssueMetadata, PageInfo},
+    volume,
+    Issue,
+};
+use common::{png_header, temp_dir};
+
+fn jpeg_header(width: u16, height: u16) -> Vec<u8> {
+    // start of image, an APP0 segment and then the start of frame
+    let mut bytes = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, 0xFF, 0xC0, 0x00, 0x11, 0x08];
+    bytes.extend_from_slice(&height.to_be_bytes());
+    bytes.extend_from_slice(&width.to_be_bytes());
+    bytes.push(3);
+    return bytes;
+}
+
+#[test]
+fn image_formats_are_recognized_from_the_magic_bytes() {
+    assert_eq!(image_kind(&jpeg_header(1, 1)), Some(ImageKind::Jpeg));
+    assert_eq!(image_kind(&png_header(1, 1)), Some(ImageKind::Png));
+    assert_eq!(image_kind(b"GIF89a\x01\0\x01\0"), Some(ImageKind::Gif));
+    assert_eq!(image_kind(b"RIFF\0\0\0\0WEBPVP8X"), Some(ImageKind::WebP));
+    assert_eq!(image_kind(b"\0\0\0\x1cftypavif"), Some(ImageKind::Avif));
+    assert_eq!(image_kind(b"<html>"), None);
+}
+
+#[test]
+fn the_content_type_is_used_when_the_magic_bytes_are_unknown() {
+    assert_eq!(kind_from_mime_type("image/JPEG"), Some(ImageKind::Jpeg));
+    assert_eq!(kind_from_mime_type("image/avif; q=1"), Some(ImageKind::Avif));
+    assert_eq!(kind_from_mime_type("application/octet-stream"), None);
+    assert_eq!(detect_kind(&png_header(1, 1), Some("image/jpeg")), Some(ImageKind::Png));
+    assert_eq!(detect_kind(b"????", Some("image/webp")), Some(ImageKind::WebP));
+    assert_eq!(detect_kind(b"????", None), None);
+}
+
+#[test]
+fn image_dimensions_are_read_from_the_header() {
+    assert_eq!(image_dimensions(&jpeg_header(800, 1200)), Some((800, 1200)));
+    assert_eq!(image_dimensions(&png_header(1072, 1448)), Some((1072, 1448)));
+    assert_eq!(image_dimensions(b"GIF89a\x20\x03\xb0\x04"), Some((800, 1200)));
+    let mut webp = b"RIFF\0\0\0\0WEBPVP8X\0\0\0\0\0\0\0\0".to_vec();
+    webp.extend_from_slice(&[0x1F, 0x03, 0x00, 0xAF, 0x04, 0x00]);
+    assert_eq!(image_dimensions(&webp), Some((800, 1200)));
+    assert_eq!(image_dimensions(&[0xFF, 0xD8, 0xFF]), None);
+}
+
+#[test]
+fn metadata_from_the_names_finds_the_number() {
+    let issue = Issue { name: "Issue-12".to_string(), link: "https://readcomic.me/batman/issue-12".to_string() };
+    let metadata = IssueMetadata::new("batman", &issue);
+    assert_eq!(metadata.series, "batman");
+    assert_eq!(metadata.number, "12");
+    assert_eq!(metadata.title.as_deref(), Some("Issue-12"));
+    assert_eq!(metadata.source_url.as_deref(), Some("https://readcomic.me/batman/issue-12"));
+
+    let issue = Issue { name: "7".to_string(), link: String::new() };
+    let metadata = IssueMetadata::new("batman", &issue);
+    assert_eq!(metadata.title, None);
+    assert_eq!(metadata.source_url, None);
+}
+
+#[test]
+fn comic_info_follows_the_schema_order_and_escapes_text() {
+    let metadata = IssueMetadata {
+        series: "Tom & Jerry".to_string(),
+        number: "3".to_string(),
+        writer: Some("<someone>".to_string()),
+        language: Some("en".to_string()),
+        ..IssueMetadata::default()
+    };
+    let pages = [
+        PageInfo { width: Some(800), height: Some(1200), size: 10, bookmark: None },
+        PageInfo { width: None, height: None, size: 20, bookmark: None },
+    ];
+    let xml = comic_info_xml(&metadata, &pages);
+
+    assert!(xml.contains("<Series>Tom &amp; Jerry</Series>"));
+    assert!(xml.contains("<Writer>&lt;someone&gt;</Writer>"));
+    assert!(!xml.contains("<Title>"));
+    let order: Vec<usize> = ["<Series>", "<Number>", "<Writer>", "<PageCount>2</PageCount>", "<LanguageISO>", "<Pages>"]
+        .iter()
+        .map(|e| xml.find(e).unwrap())
+        .collect();
+    assert!(order.windows(2).all(|e| e[0] < e[1]));
+    assert!(xml.contains(r#"<Page Image="0" Type="FrontCover" ImageSize="10" ImageWidth="800" ImageHeight="1200" />"#));
+    assert!(xml.contains(r#"<Page Image="1" ImageSize="20" />"#));
+}
+
+#[test]
+fn cbz_contains_comic_info_with_the_pages() {
+    let dir = temp_dir("comic-info");
+    let page = dir.join("0001.jpg");
+    fs::write(&page, png_header(640, 960)).unwrap();
+    let out_path = dir.join("batman-1.cbz");
+    let issue = Issue { name: "1".to_string(), link: "https://readcomic.me/batman/issue-1".to_string() };
+
+    volume::create_cbz(&[page], &out_path, Some(&IssueMetadata::new("batman", &issue))).unwrap();
+
+    let mut archive = zip::ZipArchive::new(fs::File::open(&out_path).unwrap()).unwrap();
+    let mut xml = String::new();
+    archive.by_name("ComicInfo.xml").unwrap().read_to_string(&mut xml).unwrap();
+    assert!(xml.contains("<Series>batman</Series>"));
+    assert!(xml.contains(r#"ImageWidth="640" ImageHeight="960""#));
+    fs::remove_dir_all(dir).unwrap();
+}
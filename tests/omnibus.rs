@@ -0,0 +1,163 @@
+#![allow(clippy::needless_return)]
+mod common;
+
+use std::{fs, io::Read, path::PathBuf};
+
+use comic_dl::{
+    metadata::IssueMetadata,
+    omnibus::Omnibus,
+    pdf::PageSize,
+    volume::{self, Chapter},
+    ComicUrl,
+    Issue,
+    OutputFormats,
+};
+use common::{fixtures::FixtureServer, temp_dir};
+
+fn issues(names: &[&str]) -> Vec<Issue> {
+    return names.iter()
+        .map(|name| Issue { name: name.to_string(), link: format!("https://scanita.org/manga/one-piece/{name}") })
+        .collect();
+}
+
+fn chapters(dir: &std::path::Path) -> Vec<Chapter> {
+    return ["Chapter 1", "Chapter 2"].iter()
+        .map(|title| {
+            let chapter_dir = dir.join(title);
+            fs::create_dir_all(&chapter_dir).unwrap();
+            let files: Vec<PathBuf> = (1..=2)
+                .map(|n| {
+                    let path = chapter_dir.join(format!("{n:04}.png"));
+                    let mut bytes = Vec::new();
+                    image::DynamicImage::new_rgb8(8, 12)
+                        .write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Png)
+                        .unwrap();
+                    fs::write(&path, bytes).unwrap();
+                    path
+                })
+                .collect();
+            Chapter { title: title.to_string(), files }
+        })
+        .collect();
+}
+
+fn read_entry(archive: &mut zip::ZipArchive<fs::File>, name: &str) -> String {
+    let mut content = String::new();
+    archive.by_name(name).unwrap().read_to_string(&mut content).unwrap();
+    return content;
+}
+
+#[test]
+fn issues_are_grouped_in_order() {
+    let names: Vec<String> = (1..=25).map(|n| format!("Chapter {n}")).collect();
+    let names: Vec<&str> = names.iter().map(|e| e.as_str()).collect();
+    let volumes = Omnibus::group(&issues(&names), 10);
+
+    assert_eq!(volumes.len(), 3);
+    assert_eq!(volumes[2].number, 3);
+    assert_eq!(volumes[2].issues.len(), 5);
+    assert_eq!(volumes[1].file_stem("one-piece"), "one-piece-vol02-(ch11-20)");
+    assert_eq!(volumes[2].chapters(), "ch21-25");
+
+    let all = Omnibus::group(&issues(&names), 0);
+    assert_eq!(all.len(), 1);
+    assert_eq!(all[0].file_stem("one-piece"), "one-piece-vol01-(ch1-25)");
+    assert!(Omnibus::group(&[], 10).is_empty());
+}
+
+#[test]
+fn skipped_issues_dont_change_the_volume_numbers() {
+    let server = FixtureServer::start("readcomic.me");
+    let dir = temp_dir("skip");
+    let mut comic = ComicUrl::new("https://readcomic.me/comic/batman-2016").unwrap();
+    comic.url = server.link("https://readcomic.me/comic/batman-2016");
+    comic.change_path(dir.to_str().unwrap()).unwrap();
+    comic.change_skip_first(2);
+
+    let volumes = comic.get_new_volumes(2).unwrap();
+
+    assert_eq!(volumes.len(), 1);
+    assert_eq!(volumes[0].number, 2);
+    assert_eq!(volumes[0].file_stem("batman-2016"), "batman-2016-vol02-(ch3)");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn issues_without_numbers_use_their_position() {
+    let volumes = Omnibus::group(&issues(&["prologue", "the end", "extra"]), 2);
+    assert_eq!(volumes[0].chapters(), "ch1-2");
+    assert_eq!(volumes[1].chapters(), "ch3");
+    assert_eq!(volumes[1].name(), "vol02 (ch3)");
+}
+
+#[test]
+fn volume_metadata_keeps_the_people_of_the_first_issue() {
+    let volume = &Omnibus::group(&issues(&["Chapter 21", "Chapter 30"]), 10)[0];
+    let first = IssueMetadata { writer: Some("Eiichiro Oda".to_string()), ..IssueMetadata::new("one-piece", &volume.issues[0]) };
+
+    let metadata = volume.metadata(first, 40);
+
+    assert_eq!(metadata.series, "one-piece");
+    assert_eq!(metadata.number, "1");
+    assert_eq!(metadata.title.as_deref(), Some("Volume 1 (ch21-30)"));
+    assert_eq!(metadata.writer.as_deref(), Some("Eiichiro Oda"));
+    assert_eq!(metadata.page_count, 40);
+    assert_eq!(metadata.source_url, None);
+}
+
+#[test]
+fn cbz_omnibus_has_a_bookmark_for_every_chapter() {
+    let dir = temp_dir("cbz");
+    let metadata = IssueMetadata { series: "one-piece".to_string(), number: "1".to_string(), ..IssueMetadata::default() };
+    let out_path = dir.join("one-piece-vol01-(ch1-2).cbz");
+
+    volume::create_omnibus(OutputFormats::Cbz, &metadata, &chapters(&dir), &out_path, PageSize::default()).unwrap();
+
+    let mut archive = zip::ZipArchive::new(fs::File::open(&out_path).unwrap()).unwrap();
+    let names: Vec<String> = archive.file_names().map(|e| e.to_string()).collect();
+    assert_eq!(names, ["001-0001.png", "001-0002.png", "002-0001.png", "002-0002.png", "ComicInfo.xml"]);
+    let comic_info = read_entry(&mut archive, "ComicInfo.xml");
+    assert!(comic_info.contains("<Page Image=\"0\" Type=\"FrontCover\" ImageSize="));
+    assert!(comic_info.contains("Bookmark=\"Chapter 1\""));
+    assert!(comic_info.contains("<Page Image=\"2\" ImageSize=\"") && comic_info.contains("Bookmark=\"Chapter 2\""));
+    assert_eq!(comic_info.matches("Bookmark=").count(), 2);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn epub_omnibus_has_a_table_of_contents() {
+    let dir = temp_dir("epub");
+    let metadata = IssueMetadata {
+        series: "one-piece".to_string(),
+        number: "1".to_string(),
+        title: Some("Volume 1 (ch1-2)".to_string()),
+        ..IssueMetadata::default()
+    };
+    let out_path = dir.join("one-piece-vol01-(ch1-2).epub");
+
+    volume::create_omnibus(OutputFormats::Epub, &metadata, &chapters(&dir), &out_path, PageSize::default()).unwrap();
+
+    let mut archive = zip::ZipArchive::new(fs::File::open(&out_path).unwrap()).unwrap();
+    let nav = read_entry(&mut archive, "OEBPS/nav.xhtml");
+    assert!(nav.contains("<li><a href=\"page-0001.xhtml\">Chapter 1</a></li>"));
+    assert!(nav.contains("<li><a href=\"page-0003.xhtml\">Chapter 2</a></li>"));
+    assert!(read_entry(&mut archive, "OEBPS/content.opf").contains("<dc:title>one-piece - Volume 1 (ch1-2)</dc:title>"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(feature = "pdf")]
+#[test]
+fn pdf_omnibus_has_an_outline_entry_for_every_chapter() {
+    let dir = temp_dir("pdf");
+    let metadata = IssueMetadata { series: "one-piece".to_string(), number: "1".to_string(), ..IssueMetadata::default() };
+    let out_path = dir.join("one-piece-vol01-(ch1-2).pdf");
+
+    volume::create_omnibus(OutputFormats::Pdf, &metadata, &chapters(&dir), &out_path, PageSize::default()).unwrap();
+
+    let pdf = fs::read(&out_path).unwrap();
+    let text = String::from_utf8_lossy(&pdf);
+    assert!(text.contains("/Type /Outlines"));
+    assert!(text.contains("/Count 2 >>"));
+    assert_eq!(text.matches("/Parent").count() - text.matches("/Type /Page ").count(), 2);
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,130 @@
+#![allow(clippy::needless_return)]
+mod common;
+
+use std::fs;
+
+use comic_dl::{pages, HttpClient, RateLimit, SiteDownloaderError};
+use common::{temp_dir, Reply, TestServer};
+
+fn client() -> HttpClient {
+    let mut client = HttpClient::new();
+    client.change_default_rate_limit(RateLimit::unlimited());
+    return client;
+}
+
+#[test]
+fn saved_pages_are_recognized_as_downloaded() {
+    let dir = temp_dir("saved");
+    let server = TestServer::start(vec![Reply::ok("image bytes")]);
+
+    assert!(!pages::is_page_downloaded(&dir, 1));
+    pages::save_page(&client(), &server.url, &dir, 1).unwrap();
+
+    assert!(pages::is_page_downloaded(&dir, 1));
+    assert_eq!(fs::read(pages::page_path(&dir, 1, "jpg")).unwrap(), b"image bytes");
+    assert_eq!(pages::page_files(&dir).unwrap(), vec![pages::page_path(&dir, 1, "jpg")]);
+    pages::verify_issue(&dir).unwrap();
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn the_downloaded_pages_are_read_once_for_the_issue() {
+    let dir = temp_dir("loaded");
+    let server = TestServer::start(vec![Reply::ok("image bytes")]);
+    pages::save_page(&client(), &server.url, &dir, 1).unwrap();
+    pages::save_page(&client(), &server.url, &dir, 2).unwrap();
+    fs::write(pages::page_path(&dir, 2, "jpg"), "image").unwrap();
+
+    let downloaded = pages::DownloadedPages::load(&dir);
+    pages::save_page(&client(), &server.url, &dir, 3).unwrap();
+
+    assert!(downloaded.contains(1));
+    assert!(!downloaded.contains(2));
+    assert!(!downloaded.contains(3));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn pages_with_a_different_size_are_downloaded_again() {
+    let dir = temp_dir("truncated");
+    let server = TestServer::start(vec![Reply::ok("image bytes")]);
+    pages::save_page(&client(), &server.url, &dir, 1).unwrap();
+
+    fs::write(pages::page_path(&dir, 1, "jpg"), "image").unwrap();
+
+    assert!(!pages::is_page_downloaded(&dir, 1));
+    assert!(matches!(
+        pages::verify_issue(&dir),
+        Err(SiteDownloaderError::IncompleteIssue { pages, .. }) if pages == vec!["0001.jpg"]
+    ));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn partial_and_unrecorded_pages_block_the_volume() {
+    let dir = temp_dir("partial");
+    assert!(matches!(pages::verify_issue(&dir), Err(SiteDownloaderError::IncompleteIssue { .. })));
+
+    fs::write(dir.join("0001.jpg.part"), "half").unwrap();
+    assert!(pages::page_files(&dir).unwrap().is_empty());
+
+    fs::write(pages::page_path(&dir, 2, "jpg"), "from an older version").unwrap();
+    assert!(pages::verify_issue(&dir).is_err());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn empty_pages_are_an_error() {
+    let dir = temp_dir("empty");
+    let server = TestServer::start(vec![Reply::ok("")]);
+    assert!(pages::save_page(&client(), &server.url, &dir, 1).is_err());
+    assert!(!pages::page_path(&dir, 1, "jpg").exists());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn pages_are_saved_with_the_extension_of_their_format() {
+    let dir = temp_dir("extension");
+    let server = TestServer::start(vec![
+        Reply::ok(b"\x89PNG\r\n\x1a\nrest of the image".to_vec()).header("Content-Type", "image/jpeg"),
+        Reply::ok("unknown bytes").header("Content-Type", "image/webp; charset=binary"),
+    ]);
+
+    pages::save_page(&client(), &server.url, &dir, 1).unwrap();
+    pages::save_page(&client(), &server.url, &dir, 2).unwrap();
+
+    assert_eq!(pages::page_files(&dir).unwrap(), vec![
+        pages::page_path(&dir, 1, "png"),
+        pages::page_path(&dir, 2, "webp"),
+    ]);
+    assert!(pages::is_page_downloaded(&dir, 1));
+    assert_eq!(pages::find_page(&dir, 2), Some(pages::page_path(&dir, 2, "webp")));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn a_page_downloaded_again_replaces_the_old_format() {
+    let dir = temp_dir("replaced");
+    let server = TestServer::start(vec![
+        Reply::ok("old"),
+        Reply::ok(b"GIF89a\x01\0\x01\0".to_vec()),
+    ]);
+    pages::save_page(&client(), &server.url, &dir, 1).unwrap();
+    pages::save_page(&client(), &server.url, &dir, 1).unwrap();
+
+    assert_eq!(pages::page_files(&dir).unwrap(), vec![pages::page_path(&dir, 1, "gif")]);
+    pages::verify_issue(&dir).unwrap();
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn html_instead_of_an_image_is_an_error() {
+    let dir = temp_dir("html");
+    let server = TestServer::start(vec![Reply::ok("<html>not found</html>").header("Content-Type", "text/html")]);
+    assert!(matches!(
+        pages::save_page(&client(), &server.url, &dir, 1),
+        Err(SiteDownloaderError::ParsingError { .. })
+    ));
+    assert!(pages::page_files(&dir).unwrap().is_empty());
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,155 @@
+#![allow(clippy::needless_return)]
+mod common;
+
+use std::fs;
+
+use comic_dl::{
+    image_info,
+    metadata::IssueMetadata,
+    pdf::{DocumentInfo, PageSize, PdfImage, PdfWriter},
+    pipeline::DeviceProfile,
+    volume,
+    Issue,
+    SiteDownloaderError,
+};
+use image::{DynamicImage, Rgb, RgbImage};
+use common::temp_dir;
+
+fn jpeg(width: u32, height: u32) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    DynamicImage::ImageRgb8(RgbImage::from_pixel(width, height, Rgb([200, 10, 10])))
+        .write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Jpeg)
+        .unwrap();
+    return bytes;
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    return haystack.windows(needle.len()).any(|e| e == needle);
+}
+
+/// Checks that every entry of the cross reference table points to its object
+fn assert_valid_xref(pdf: &[u8]) {
+    let position = pdf.windows(10).rposition(|e| e == b"startxref\n").unwrap();
+    let tail = std::str::from_utf8(&pdf[position..]).unwrap();
+    let startxref: usize = tail.lines().nth(1).unwrap().parse().unwrap();
+    let xref = String::from_utf8_lossy(&pdf[startxref..position]);
+    assert!(xref.starts_with("xref\n"));
+    let entries: Vec<&str> = xref.lines().skip(3).take_while(|e| e.ends_with(" n ")).collect();
+    assert!(!entries.is_empty());
+    for (index, entry) in entries.iter().enumerate() {
+        let offset: usize = entry[..10].parse().unwrap();
+        assert!(pdf[offset..].starts_with(format!("{} 0 obj\n", index + 1).as_bytes()));
+    }
+}
+
+#[test]
+fn jpegs_are_embedded_without_encoding_them_again() {
+    let dir = temp_dir("jpeg");
+    let page = jpeg(600, 900);
+    let path = dir.join("0001.jpg");
+    fs::write(&path, &page).unwrap();
+    let issue = Issue { name: "Issue-4".to_string(), link: String::new() };
+    let metadata = IssueMetadata { writer: Some("Zerocalcare".to_string()), ..IssueMetadata::new("Kobane", &issue) };
+    let out_path = dir.join("Kobane-Issue-4.pdf");
+
+    volume::create_pdf(&metadata, &[path], &out_path, PageSize::default()).unwrap();
+
+    let pdf = fs::read(&out_path).unwrap();
+    assert!(pdf.starts_with(b"%PDF-1.7"));
+    assert!(contains(&pdf, b"/Filter /DCTDecode"));
+    assert!(contains(&pdf, &page));
+    // 600x900 pixels at 300 dpi are 2x3 inches
+    assert!(contains(&pdf, b"/MediaBox [0 0 144.00 216.00]"));
+    assert!(contains(&pdf, b"/Outlines"));
+    let title: String = "Kobane - Issue-4".encode_utf16().map(|e| format!("{e:04X}")).collect();
+    assert!(contains(&pdf, format!("/Title <FEFF{title}>").as_bytes()));
+    assert!(contains(&pdf, b"/Author <FEFF"));
+    assert_valid_xref(&pdf);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn fixed_page_sizes_center_the_image() {
+    let mut pdf = PdfWriter::new(Vec::new(), PageSize::A5).unwrap();
+    pdf.add_page(PdfImage::from_bytes(jpeg(100, 100)).unwrap()).unwrap();
+    let pdf = pdf.finish(&DocumentInfo { title: "a5".to_string(), ..DocumentInfo::default() }).unwrap();
+
+    assert!(contains(&pdf, b"/MediaBox [0 0 419.53 595.28]"));
+    assert!(contains(&pdf, b"q 419.53 0 0 419.53 0.00 87.88 cm /Im0 Do Q"));
+    assert!(!contains(&pdf, b"/Outlines"));
+    assert_valid_xref(&pdf);
+}
+
+#[test]
+fn images_without_pixels_are_an_error() {
+    let mut bytes = jpeg(10, 10);
+    let frame = bytes.windows(2).position(|e| e == [0xFF, 0xC0]).unwrap();
+    bytes[frame + 5..frame + 9].fill(0);
+    assert!(PdfImage::from_bytes(bytes).err().unwrap().contains("0x0"));
+
+    let mut image = PdfImage::from_bytes(jpeg(10, 10)).unwrap();
+    image.width = 0;
+    let mut pdf = PdfWriter::new(Vec::new(), PageSize::A5).unwrap();
+    assert_eq!(pdf.add_page(image).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn jpegs_that_readers_cant_show_are_not_embedded() {
+    let page = jpeg(10, 10);
+    let frame = page.windows(2).position(|e| e == [0xFF, 0xC0]).unwrap();
+    assert!(image_info::jpeg_frame(&page).unwrap().is_embeddable());
+
+    let mut arithmetic = page.clone();
+    arithmetic[frame + 1] = 0xC9;
+    let mut twelve_bits = page.clone();
+    twelve_bits[frame + 4] = 12;
+    for bytes in [arithmetic, twelve_bits] {
+        assert!(!image_info::jpeg_frame(&bytes).unwrap().is_embeddable());
+        if let Ok(image) = PdfImage::from_bytes(bytes.clone()) {
+            let mut pdf = PdfWriter::new(Vec::new(), PageSize::A5).unwrap();
+            pdf.add_page(image).unwrap();
+            let pdf = pdf.finish(&DocumentInfo::default()).unwrap();
+            assert!(!contains(&pdf, b"/DCTDecode"));
+        }
+    }
+}
+
+#[test]
+fn device_page_sizes_have_the_proportions_of_the_screen() {
+    let PageSize::Fixed { width, height } = PageSize::device(DeviceProfile::KoboClara) else {
+        panic!("the size of a device is fixed");
+    };
+    assert!((width / height - 1072.0 / 1448.0).abs() < 0.001);
+}
+
+#[test]
+fn unreadable_pages_are_an_error() {
+    let dir = temp_dir("broken");
+    let path = dir.join("0001.jpg");
+    fs::write(&path, "not an image").unwrap();
+    let issue = Issue { name: "1".to_string(), link: String::new() };
+
+    let result = volume::create_pdf(&IssueMetadata::new("comic", &issue), &[path], &dir.join("comic-1.pdf"), PageSize::default());
+
+    assert!(matches!(result, Err(SiteDownloaderError::ImageError { .. })));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(feature = "pdf")]
+#[test]
+fn other_formats_are_compressed_one_at_a_time() {
+    let mut png = Vec::new();
+    DynamicImage::ImageLuma8(image::GrayImage::from_pixel(20, 30, image::Luma([128])))
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+    let mut pdf = PdfWriter::new(Vec::new(), PageSize::Original { dpi: 72.0 }).unwrap();
+    pdf.add_bookmark("chapter 1");
+    pdf.add_page(PdfImage::from_bytes(png).unwrap()).unwrap();
+    pdf.add_page(PdfImage::from_bytes(jpeg(20, 30)).unwrap()).unwrap();
+    let pdf = pdf.finish(&DocumentInfo { title: "mixed".to_string(), ..DocumentInfo::default() }).unwrap();
+
+    assert!(contains(&pdf, b"/ColorSpace /DeviceGray /BitsPerComponent 8 /Filter /FlateDecode"));
+    assert!(contains(&pdf, b"/Type /Pages /Kids [5 0 R 8 0 R] /Count 2"));
+    assert!(contains(&pdf, b"/MediaBox [0 0 20.00 30.00]"));
+    assert_valid_xref(&pdf);
+}
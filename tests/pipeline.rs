@@ -0,0 +1,186 @@
+#![allow(clippy::needless_return)]
+#![cfg(feature = "images")]
+mod common;
+
+use std::fs;
+
+use comic_dl::pipeline::{AutoCrop, DeviceProfile, ImagePipeline, SpreadMode, PROCESSED_DIR};
+use image::{DynamicImage, GenericImageView, Rgb, RgbImage};
+use common::temp_dir;
+
+/// A page with a diagonal gradient from black to white
+fn gradient(width: u32, height: u32) -> DynamicImage {
+    return DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+        let value = ((x + y) * 255 / (width + height - 2)) as u8;
+        Rgb([value, value / 2, 255 - value])
+    }));
+}
+
+#[test]
+fn device_pages_fit_the_screen_in_16_grays() {
+    let pipeline = DeviceProfile::KoboClara.pipeline();
+    let page = pipeline.process_image(gradient(2144, 1000)).remove(0);
+
+    assert_eq!(page.dimensions(), (1072, 500));
+    let gray = page.as_luma8().unwrap();
+    assert!(gray.pixels().all(|pixel| pixel.0[0] % 17 == 0));
+    let mut levels: Vec<u8> = gray.pixels().map(|pixel| pixel.0[0]).collect();
+    levels.sort();
+    levels.dedup();
+    assert!(levels.len() <= 16);
+}
+
+#[test]
+fn small_pages_are_not_enlarged() {
+    let pipeline = DeviceProfile::KoboSage.pipeline();
+    assert_eq!(pipeline.process_image(gradient(800, 1200))[0].dimensions(), (800, 1200));
+}
+
+#[test]
+fn gamma_over_one_darkens_the_middle_tones() {
+    let page = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([128, 128, 128])));
+    let pipeline = ImagePipeline { gamma: 2.0, ..ImagePipeline::default() };
+    let processed = pipeline.process_image(page)[0].to_rgb8();
+    assert!(processed.get_pixel(0, 0).0[0] < 100);
+}
+
+#[test]
+fn processed_pages_are_written_apart_from_the_downloaded_ones() {
+    let dir = temp_dir("process");
+    let original = dir.join("0001.jpg");
+    gradient(300, 400).save_with_format(&original, image::ImageFormat::Jpeg).unwrap();
+    let broken = dir.join("0002.jpg");
+    fs::write(&broken, b"not an image").unwrap();
+    let files = vec![original.clone(), broken.clone()];
+
+    assert_eq!(ImagePipeline::default().process_pages(&dir, &files).unwrap(), files);
+
+    let pipeline = ImagePipeline { max_size: Some((150, 200)), gray_levels: Some(16), ..ImagePipeline::default() };
+    let pages = pipeline.process_pages(&dir, &files).unwrap();
+    assert_eq!(pages, vec![dir.join(PROCESSED_DIR).join("0001.png"), broken]);
+    assert_eq!(image::open(&pages[0]).unwrap().dimensions(), (150, 200));
+    assert!(original.is_file());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+/// A spread with the left half black and the right half white
+fn spread() -> DynamicImage {
+    return DynamicImage::ImageRgb8(RgbImage::from_fn(400, 300, |x, _| {
+        if x < 200 { Rgb([0, 0, 0]) } else { Rgb([255, 255, 255]) }
+    }));
+}
+
+fn brightness(page: &DynamicImage) -> u8 {
+    return page.to_luma8().get_pixel(page.width() / 2, page.height() / 2).0[0];
+}
+
+#[test]
+fn spreads_are_split_in_reading_order() {
+    let pipeline = ImagePipeline { spreads: SpreadMode::Split, ..ImagePipeline::default() };
+    let pages = pipeline.process_image(spread());
+    assert_eq!(pages.iter().map(|e| e.dimensions()).collect::<Vec<_>>(), vec![(200, 300), (200, 300)]);
+    assert_eq!(pages.iter().map(brightness).collect::<Vec<_>>(), vec![0, 255]);
+
+    let pipeline = ImagePipeline { right_to_left: true, ..pipeline };
+    assert_eq!(pipeline.process_image(spread()).iter().map(brightness).collect::<Vec<_>>(), vec![255, 0]);
+}
+
+#[test]
+fn spreads_are_rotated_with_the_first_half_on_top() {
+    let pipeline = ImagePipeline { spreads: SpreadMode::Rotate, ..ImagePipeline::default() };
+    let page = pipeline.process_image(spread()).remove(0);
+    assert_eq!(page.dimensions(), (300, 400));
+    assert_eq!(page.to_luma8().get_pixel(150, 10).0[0], 0);
+
+    let pipeline = ImagePipeline { right_to_left: true, ..pipeline };
+    let page = pipeline.process_image(spread()).remove(0);
+    assert_eq!(page.to_luma8().get_pixel(150, 10).0[0], 255);
+}
+
+#[test]
+fn both_keeps_the_rotated_spread_and_the_halves() {
+    let dir = temp_dir("spreads");
+    let portrait = dir.join("0001.jpg");
+    gradient(300, 400).save_with_format(&portrait, image::ImageFormat::Jpeg).unwrap();
+    let landscape = dir.join("0002.jpg");
+    spread().save_with_format(&landscape, image::ImageFormat::Jpeg).unwrap();
+
+    let pipeline = ImagePipeline { spreads: SpreadMode::Both, ..ImagePipeline::default() };
+    let pages = pipeline.process_pages(&dir, &[portrait.clone(), landscape]).unwrap();
+
+    let processed = dir.join(PROCESSED_DIR);
+    assert_eq!(pages, vec![
+        portrait,
+        processed.join("0002-1.jpg"),
+        processed.join("0002-2.jpg"),
+        processed.join("0002-3.jpg"),
+    ]);
+    assert_eq!(image::open(&pages[1]).unwrap().dimensions(), (300, 400));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+/// A gray page with a white border, a few black specks in it and some
+/// black art touching the bottom side
+fn scan() -> DynamicImage {
+    return DynamicImage::ImageRgb8(RgbImage::from_fn(200, 300, |x, y| {
+        if y >= 290 && (80..120).contains(&x) {
+            return Rgb([0, 0, 0]);
+        }
+        if (x, y) == (5, 100) || (x, y) == (150, 3) {
+            return Rgb([0, 0, 0]);
+        }
+        if !(20..190).contains(&x) || y < 10 {
+            return Rgb([250, 250, 250]);
+        }
+        Rgb([120, 120, 120])
+    }));
+}
+
+#[test]
+fn uniform_borders_are_cropped() {
+    let pipeline = ImagePipeline { crop: Some(AutoCrop::default()), ..ImagePipeline::default() };
+    let page = pipeline.process_image(scan()).remove(0);
+    assert_eq!(page.dimensions(), (170, 290));
+    assert_eq!(brightness(&page), 120);
+}
+
+#[test]
+fn cropping_stops_at_the_safety_cap() {
+    let blank = DynamicImage::ImageRgb8(RgbImage::from_pixel(100, 200, Rgb([255, 255, 255])));
+    let pipeline = ImagePipeline { crop: Some(AutoCrop { max_fraction: 0.1, ..AutoCrop::default() }), ..ImagePipeline::default() };
+    assert_eq!(pipeline.process_image(blank).remove(0).dimensions(), (80, 160));
+}
+
+#[test]
+fn pages_without_borders_are_not_cropped() {
+    let dir = temp_dir("crop");
+    let page = dir.join("0001.jpg");
+    gradient(300, 400).save_with_format(&page, image::ImageFormat::Jpeg).unwrap();
+    let bordered = dir.join("0002.png");
+    scan().save(&bordered).unwrap();
+
+    let pipeline = ImagePipeline { crop: Some(AutoCrop::default()), ..ImagePipeline::default() };
+    let pages = pipeline.process_pages(&dir, &[page.clone(), bordered]).unwrap();
+
+    assert_eq!(pages, vec![page, dir.join(PROCESSED_DIR).join("0002.jpg")]);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn webp_pages_are_converted_to_jpeg() {
+    let dir = temp_dir("to-jpeg");
+    let webp = dir.join("0001.webp");
+    let file = fs::File::create(&webp).unwrap();
+    gradient(30, 40).write_with_encoder(image::codecs::webp::WebPEncoder::new_lossless(file)).unwrap();
+    let png = dir.join("0002.png");
+    gradient(30, 40).save(&png).unwrap();
+
+    let pipeline = ImagePipeline { to_jpeg: true, ..ImagePipeline::default() };
+    let pages = pipeline.process_pages(&dir, &[webp, png.clone()]).unwrap();
+
+    let converted = dir.join(PROCESSED_DIR).join("0001.jpg");
+    assert_eq!(pages, vec![converted.clone(), png]);
+    assert_eq!(image::ImageFormat::from_path(&converted).unwrap(), image::ImageFormat::Jpeg);
+    assert_eq!(image::guess_format(&fs::read(&converted).unwrap()).unwrap(), image::ImageFormat::Jpeg);
+    fs::remove_dir_all(dir).unwrap();
+}
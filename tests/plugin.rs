@@ -0,0 +1,345 @@
+#![cfg(feature = "plugins")]
+#![allow(clippy::needless_return)]
+mod common;
+
+use std::{fs, path::Path};
+
+use comic_dl::{
+    sites::{
+        plugin::{load_plugins, PluginStrategy},
+        registry::{Capabilities, SiteRegistry},
+        ComicDownloader,
+        Issue,
+    },
+    HttpClient, SiteDownloaderError,
+};
+use common::{png, temp_dir, Reply, TestServer};
+
+/// A plugin in the text format: `issues` finds the links with a regex and
+/// `pages` the images with a selector, both on the page from `http_get`
+fn plugin_wat(site: &str, api_version: i32) -> String {
+    let strings = [
+        site,
+        r#"<a class="issue" href="(?P<link>[^"]+)">(?P<name>[^<]+)</a>"#,
+        "div.reader img",
+        "data-src",
+        r#"{"ok":"#,
+        r#"{"error":"a host function failed"}"#,
+    ];
+    let mut data = String::new();
+    let mut constants = Vec::new();
+    let mut offset = 0;
+    for text in strings {
+        data.push_str(&format!("  (data (i32.const {offset}) \"{}\")\n", text.replace('\\', "\\\\").replace('"', "\\\"")));
+        constants.push((offset, text.len()));
+        offset += text.len();
+    }
+    let [site, pattern, selector, attribute, ok, error] = constants.try_into().unwrap();
+    return format!(r#"(module
+  (import "comic_dl" "http_get" (func $http_get (param i32 i32) (result i64)))
+  (import "comic_dl" "html_select" (func $html_select (param i32 i32 i32 i32 i32 i32) (result i64)))
+  (import "comic_dl" "regex_captures" (func $regex_captures (param i32 i32 i32 i32) (result i64)))
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 8192))
+{data}
+  (func $alloc (export "alloc") (param $length i32) (result i32)
+    (local $pointer i32)
+    global.get $next
+    local.set $pointer
+    global.get $next
+    local.get $length
+    i32.add
+    global.set $next
+    (block
+      (loop
+        global.get $next
+        memory.size
+        i32.const 16
+        i32.shl
+        i32.le_u
+        br_if 1
+        i32.const 1
+        memory.grow
+        drop
+        br 0))
+    local.get $pointer)
+  (func $pointer (param $value i64) (result i32)
+    local.get $value
+    i64.const 32
+    i64.shr_u
+    i32.wrap_i64)
+  (func $length (param $value i64) (result i32)
+    local.get $value
+    i32.wrap_i64)
+  (func $pack (param $pointer i32) (param $length i32) (result i64)
+    local.get $pointer
+    i64.extend_i32_u
+    i64.const 32
+    i64.shl
+    local.get $length
+    i64.extend_i32_u
+    i64.or)
+  (func $ok (param $value i64) (result i64)
+    (local $out i32)
+    local.get $value
+    i64.const -1
+    i64.eq
+    (if (then
+      i32.const {error_offset}
+      i32.const {error_length}
+      call $pack
+      return))
+    local.get $value
+    call $length
+    i32.const {ok_envelope}
+    i32.add
+    call $alloc
+    local.set $out
+    local.get $out
+    i32.const {ok_offset}
+    i32.const {ok_length}
+    memory.copy
+    local.get $out
+    i32.const {ok_length}
+    i32.add
+    local.get $value
+    call $pointer
+    local.get $value
+    call $length
+    memory.copy
+    local.get $out
+    i32.const {ok_length}
+    i32.add
+    local.get $value
+    call $length
+    i32.add
+    i32.const 125
+    i32.store8
+    local.get $out
+    local.get $value
+    call $length
+    i32.const {ok_envelope}
+    i32.add
+    call $pack)
+  (func (export "api_version") (result i32)
+    i32.const {api_version})
+  (func (export "site") (result i64)
+    i32.const {site_offset}
+    i32.const {site_length}
+    call $pack)
+  (func (export "issues") (param $url i32) (param $length i32) (result i64)
+    (local $body i64)
+    local.get $url
+    local.get $length
+    call $http_get
+    local.tee $body
+    i64.const -1
+    i64.eq
+    (if (then
+      i64.const -1
+      call $ok
+      return))
+    i32.const {pattern_offset}
+    i32.const {pattern_length}
+    local.get $body
+    call $pointer
+    local.get $body
+    call $length
+    call $regex_captures
+    call $ok)
+  (func (export "pages") (param $url i32) (param $length i32) (result i64)
+    (local $body i64)
+    local.get $url
+    local.get $length
+    call $http_get
+    local.tee $body
+    i64.const -1
+    i64.eq
+    (if (then
+      i64.const -1
+      call $ok
+      return))
+    local.get $body
+    call $pointer
+    local.get $body
+    call $length
+    i32.const {selector_offset}
+    i32.const {selector_length}
+    i32.const {attribute_offset}
+    i32.const {attribute_length}
+    call $html_select
+    call $ok)
+  (func (export "endless") (param i32 i32) (result i64)
+    (loop
+      br 0)
+    i64.const 0))
+"#,
+        site_offset = site.0, site_length = site.1,
+        pattern_offset = pattern.0, pattern_length = pattern.1,
+        selector_offset = selector.0, selector_length = selector.1,
+        attribute_offset = attribute.0, attribute_length = attribute.1,
+        ok_offset = ok.0, ok_length = ok.1, ok_envelope = ok.1 + 1,
+        error_offset = error.0, error_length = error.1,
+    );
+}
+
+fn plugin(site: &str) -> PluginStrategy {
+    let wasm = wat::parse_str(plugin_wat(site, 1)).unwrap();
+    return PluginStrategy::from_bytes(Path::new("test.wasm"), &wasm).unwrap();
+}
+
+#[test]
+fn the_plugin_lists_the_issues_and_downloads_the_pages() {
+    let server = TestServer::start(vec![
+        Reply::ok(r#"<a class="issue" href="/read/1">Chapter 1</a> <a class="issue" href="/read/2">Chapter 2</a>"#),
+        Reply::ok(r#"<div class="reader"><img data-src="/img/1.png"><img data-src="2.png"></div>"#),
+        Reply::ok(png()).header("Content-Type", "image/png"),
+    ]);
+    let strategy = plugin(r#"{"name": "local", "domains": ["127.0.0.1"], "language": "en"}"#);
+    let client = HttpClient::new();
+    let dir = temp_dir("download");
+
+    let issues = strategy.get_issues_list(&client, &format!("{}/comic/local", server.url)).unwrap();
+    strategy.download_issue(&client, &dir, &issues[0]).unwrap();
+
+    assert_eq!(issues, [
+        Issue { name: "Chapter 1".to_string(), link: format!("{}/read/1", server.url) },
+        Issue { name: "Chapter 2".to_string(), link: format!("{}/read/2", server.url) },
+    ]);
+    assert_eq!(server.requests(), ["/comic/local", "/read/1", "/img/1.png", "/read/2.png"]);
+    assert!(dir.join("Chapter 1").join("0001.png").exists());
+    assert!(dir.join("Chapter 1").join("0002.png").exists());
+    assert_eq!(strategy.get_comic_name(&client, "https://example.com/comic/local/").unwrap(), "local");
+    let metadata = strategy.get_issue_metadata(&client, "local", &issues[1]).unwrap();
+    assert_eq!(metadata.language.as_deref(), Some("en"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn names_outside_the_download_path_are_refused() {
+    let server = TestServer::start(vec![
+        Reply::ok(r#"<a class="issue" href="/read/1">..</a> <a class="issue" href="/read/2">Chapter 2</a>"#),
+    ]);
+    let strategy = plugin(r#"{"name": "local", "domains": ["127.0.0.1"]}"#);
+    let client = HttpClient::new();
+    let dir = temp_dir("names");
+
+    let error = strategy.get_issues_list(&client, &format!("{}/comic/local", server.url)).unwrap_err();
+
+    assert!(matches!(error, SiteDownloaderError::ParsingError { .. }), "{error}");
+    let parent = Issue { name: "..".to_string(), link: format!("{}/read/1", server.url) };
+    assert!(matches!(strategy.download_issue(&client, &dir.join("comic"), &parent), Err(SiteDownloaderError::ParsingError { .. })));
+    assert!(!dir.join("comic").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn requests_outside_the_domains_of_the_plugin_are_refused() {
+    let server = TestServer::start(vec![Reply::ok("")]);
+    let strategy = plugin(r#"{"name": "example", "domains": ["example.com"], "hosts": ["cdn.example.com"]}"#);
+
+    let error = strategy.get_issues_list(&HttpClient::new(), &format!("{}/comic/local", server.url)).unwrap_err();
+
+    assert!(matches!(error, SiteDownloaderError::Plugin { .. }));
+    assert!(error.to_string().contains("a host function failed"));
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn images_and_redirects_outside_the_domains_of_the_plugin_are_refused() {
+    let other = TestServer::start(vec![Reply::ok(png()).header("Content-Type", "image/png")]);
+    let other_url = other.url.replace("127.0.0.1", "localhost");
+    let server = TestServer::start(vec![
+        Reply::ok(format!(r#"<div class="reader"><img data-src="{other_url}/1.png"></div>"#)),
+        Reply::status(302).header("Location", &format!("{other_url}/comic/local")),
+    ]);
+    let strategy = plugin(r#"{"name": "local", "domains": ["127.0.0.1"]}"#);
+    let client = HttpClient::new();
+    let dir = temp_dir("hosts");
+    let issue = Issue { name: "1".to_string(), link: format!("{}/read/1", server.url) };
+
+    let images = strategy.download_issue(&client, &dir, &issue).unwrap_err();
+    let redirect = strategy.get_issues_list(&client, &format!("{}/comic/local", server.url)).unwrap_err();
+
+    assert!(images.to_string().contains("is not one of the domains"), "{images}");
+    assert!(matches!(redirect, SiteDownloaderError::Plugin { .. }));
+    assert_eq!(server.requests(), ["/read/1", "/comic/local"]);
+    assert!(other.requests().is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn endless_plugins_run_out_of_fuel() {
+    let wat = plugin_wat(r#"{"name": "endless", "domains": ["example.com"]}"#, 1)
+        .replace("(export \"issues\")", "")
+        .replace("(export \"endless\")", "(export \"issues\")");
+    let wasm = wat::parse_str(wat).unwrap();
+    let mut strategy = PluginStrategy::from_bytes(Path::new("endless.wasm"), &wasm).unwrap();
+    strategy.change_fuel(100_000);
+
+    let error = strategy.get_issues_list(&HttpClient::new(), "https://example.com/comic/endless").unwrap_err();
+
+    assert!(matches!(error, SiteDownloaderError::Plugin { .. }));
+    assert!(error.to_string().contains("fuel"), "{error}");
+}
+
+#[test]
+fn strings_outside_the_memory_of_the_plugin_are_errors() {
+    // a result of 4 GiB, one ending past the memory and a link of 4 GiB
+    let bodies = [
+        "i64.const 4294967295",
+        "i64.const 257698037770000",
+        "i32.const 0\n    i32.const -1\n    call $http_get",
+    ];
+    for body in bodies {
+        let wat = plugin_wat(r#"{"name": "hostile", "domains": ["example.com"]}"#, 1)
+            .replace("(export \"issues\")", "")
+            .replace("(loop\n      br 0)\n    i64.const 0", body)
+            .replace("(export \"endless\")", "(export \"issues\")");
+        let wasm = wat::parse_str(wat).unwrap();
+        let strategy = PluginStrategy::from_bytes(Path::new("hostile.wasm"), &wasm).unwrap();
+
+        let error = strategy.get_issues_list(&HttpClient::new(), "https://example.com/comic/hostile").unwrap_err();
+
+        assert!(matches!(error, SiteDownloaderError::Plugin { .. }));
+        assert!(error.to_string().contains("longer than the limit") || error.to_string().contains("outside of the memory"), "{error}");
+    }
+}
+
+#[test]
+fn invalid_plugins_are_reported_and_skipped() {
+    let dir = temp_dir("load");
+    let site = r#"{"name": "example", "domains": ["example.com"]}"#;
+    fs::write(dir.join("good.wasm"), wat::parse_str(plugin_wat(site, 1)).unwrap()).unwrap();
+    fs::write(dir.join("newer.wasm"), wat::parse_str(plugin_wat(site, 2)).unwrap()).unwrap();
+    fs::write(dir.join("no-pages.wasm"), wat::parse_str(plugin_wat(site, 1).replace("(export \"pages\")", "")).unwrap()).unwrap();
+    fs::write(dir.join("not-wasm.wasm"), "(module)").unwrap();
+    fs::write(dir.join("readme.txt"), "not a plugin").unwrap();
+
+    let loaded = load_plugins(&dir);
+
+    assert_eq!(loaded.len(), 4);
+    assert_eq!(loaded[0].as_ref().unwrap().manifest.name, "example");
+    let reasons = ["version 2", "doesn't export pages", "invalid module"];
+    for (result, reason) in loaded[1..].iter().zip(reasons) {
+        match result {
+            Err(error @ SiteDownloaderError::Plugin { .. }) => assert!(error.to_string().contains(reason), "{error}"),
+            other => panic!("expected an error with {reason}: {other:?}"),
+        }
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn plugins_are_websites_of_the_registry() {
+    let strategy = plugin(r#"{"name": "example", "domains": ["*.example.com"], "example": "https://example.com/series/<name>", "comic_pattern": "/series/[^/]+/?$"}"#);
+    let mut registry = SiteRegistry::builtin();
+
+    registry.register(strategy.site());
+
+    let site = registry.find("https://read.example.com/series/batman").unwrap();
+    assert_eq!(site.name, "example");
+    assert_eq!(site.capabilities, Capabilities::default());
+    assert!(matches!(registry.find("https://example.com/genre/dc"), Err(SiteDownloaderError::NotAComic { .. })));
+    assert!(registry.describe().contains("- example: https://example.com/series/<name>\n"));
+}
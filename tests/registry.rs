@@ -0,0 +1,86 @@
+#![allow(clippy::needless_return)]
+
+use comic_dl::{
+    sites::{
+        readcomic_me::ReadcomicMeStrategy,
+        registry::{domain_matches, Capabilities, Site, SiteRegistry},
+        zerocalcare_net::ZerocalcareNetStrategy,
+    },
+    ComicDownloader, HttpClient, SiteDownloaderError,
+};
+
+#[test]
+fn www_ports_and_mirrors_find_the_website() {
+    let registry = SiteRegistry::builtin();
+    let cases = [
+        ("https://www.readcomic.me/comic/batman", "readcomic.me"),
+        ("https://readcomic.me:443/comic/batman/", "readcomic.me"),
+        ("http://scanita.org:8080/manga/one-piece", "scanita.org"),
+        ("https://scanita.net/manga/one-piece", "scanita.org"),
+        ("https://zerocalcare.net/storie-a-fumetti/una-storia/", "zerocalcare.net"),
+    ];
+
+    for (url, site) in cases {
+        assert_eq!(registry.find(url).unwrap().name, site, "{url}");
+    }
+    let client = HttpClient::new();
+    assert_eq!(ReadcomicMeStrategy.get_comic_name(&client, "https://www.readcomic.me:8443/comic/batman/").unwrap(), "batman");
+    assert_eq!(ZerocalcareNetStrategy.get_comic_name(&client, "https://www.zerocalcare.net/storie-a-fumetti/una-storia/").unwrap(), "una-storia");
+}
+
+#[test]
+fn unsupported_websites_suggest_the_similar_ones() {
+    let registry = SiteRegistry::builtin();
+
+    let error = registry.find("https://readcomic.com/comic/batman").unwrap_err();
+    match &error {
+        SiteDownloaderError::UnsupportedWebsite { suggestions, .. } => assert_eq!(suggestions, &["readcomic.me"]),
+        other => panic!("unexpected error {other:?}"),
+    }
+    assert!(error.to_string().contains("did you mean readcomic.me?"));
+    assert!(registry.find("https://www.scanlta.org/manga/one-piece").unwrap_err().to_string().contains("did you mean scanita.org?"));
+    assert!(registry.find("https://example.com/comic/batman").unwrap_err().to_string().contains("--list-websites"));
+    assert!(matches!(registry.find("ftp://readcomic.me/comic/batman"), Err(SiteDownloaderError::InvalidUrl { .. })));
+}
+
+#[test]
+fn links_with_the_wrong_shape_are_not_comics() {
+    let registry = SiteRegistry::builtin();
+
+    for url in ["https://readcomic.me/genre/dc", "https://readcomic.me/comic/batman/issue-1", "https://scanita.org/"] {
+        let error = registry.find(url).unwrap_err();
+        assert!(matches!(error, SiteDownloaderError::NotAComic { .. }), "{url}");
+        assert!(error.to_string().contains("it should be like https://"));
+    }
+}
+
+#[test]
+fn registered_websites_are_listed_with_their_capabilities() {
+    let mut registry = SiteRegistry::new();
+    let mut site = Site::new("example", &["example.com", "*.example.org"], "https://example.com/series/<name>", || Box::new(ReadcomicMeStrategy));
+    site.change_validator(|url| url.path().starts_with("/series/"));
+    registry.register(site);
+    registry.register(Site::new("fallback", &["*"], "https://fallback/", || Box::new(ZerocalcareNetStrategy)));
+
+    assert_eq!(registry.sites()[0].capabilities, Capabilities { search: true, metadata: false });
+    assert_eq!(registry.sites()[1].capabilities, Capabilities::default());
+    assert_eq!(registry.find("https://cdn.example.org/series/batman").unwrap().name, "example");
+    assert!(registry.find("https://example.com/other/batman").is_err());
+    assert_eq!(registry.find("https://localhost/anything").unwrap().name, "fallback");
+    let list = registry.describe();
+    assert!(list.contains("- example: https://example.com/series/<name>\n  domains: example.com, *.example.org\n  supports: search\n"));
+    assert!(list.contains("- fallback: https://fallback/\n  domains: *\n"));
+}
+
+#[test]
+fn domain_patterns() {
+    assert!(domain_matches("example.com", "EXAMPLE.com"));
+    assert!(domain_matches("example.com", "www.example.com."));
+    assert!(!domain_matches("example.com", "cdn.example.com"));
+    assert!(!domain_matches("www.example.com", "example.com"));
+    assert!(domain_matches("*.example.org", "example.org"));
+    assert!(domain_matches("*.example.org", "cdn.eu.example.org"));
+    assert!(!domain_matches("*.example.org", "badexample.org"));
+    assert!(domain_matches("example.*", "www.example.it"));
+    assert!(!domain_matches("example.*", "example.co.uk"));
+}
@@ -0,0 +1,86 @@
+#![allow(clippy::needless_return)]
+mod common;
+
+use comic_dl::{
+    search::{self, SearchResult},
+    sites::{readcomic_me::ReadcomicMeStrategy, scanita_org::ScanitaOrgStrategy},
+    HttpClient,
+};
+use common::{Reply, TestServer};
+
+fn result(site: &str, title: &str) -> SearchResult {
+    let slug = title.to_lowercase().replace(' ', "-");
+    return SearchResult { site: site.to_string(), title: title.to_string(), url: format!("https://{site}/comic/{slug}") };
+}
+
+#[test]
+fn best_matches_come_first() {
+    let results = vec![
+        result("readcomic.me", "Batman Beyond"),
+        result("scanita.org", "The Batman Who Laughs"),
+        result("readcomic.me", "Superman"),
+        result("readcomic.me", "Batman"),
+        result("scanita.org", "Batman: Year One"),
+        result("readcomic.me", "Batman"),
+    ];
+
+    let titles: Vec<String> = search::rank("batman", results).into_iter().map(|e| e.title).collect();
+
+    assert_eq!(titles, ["Batman", "Batman Beyond", "Batman: Year One", "The Batman Who Laughs"]);
+}
+
+#[test]
+fn titles_with_some_of_the_words_come_after_the_others() {
+    let results = vec![
+        result("scanita.org", "One Punch Man"),
+        result("scanita.org", "Piece of Cake"),
+        result("scanita.org", "One Piece"),
+    ];
+
+    let titles: Vec<String> = search::rank("ONE piece!", results).into_iter().map(|e| e.title).collect();
+
+    assert_eq!(titles, ["One Piece", "One Punch Man", "Piece of Cake"]);
+    assert!(search::rank("  ", vec![result("scanita.org", "One Piece")]).is_empty());
+}
+
+#[test]
+fn readcomic_results_are_the_links_to_the_comics() {
+    let page = r#"<html><body>
+<a href="/comic/popular">Popular</a>
+<div class="items">
+  <div class="item"><a href="/comic/batman-2016" title="Batman (2016)"><img src="cover.jpg"></a>
+    <h3><a href="https://readcomic.me/comic/batman-2016">Batman (2016)</a></h3>
+    <a href="/comic/batman-2016/issue-1">Issue #1</a></div>
+  <div class="item"><a href="/comic/batman-beyond/"><img src="cover.jpg"></a>
+    <h3><a href="/comic/batman-beyond/">  Batman
+      Beyond </a></h3></div>
+</div>
+<a href="/genre/dc">DC</a>
+</body></html>"#;
+    let server = TestServer::start(vec![Reply::ok(page)]);
+
+    let results = ReadcomicMeStrategy.search_at(&HttpClient::new(), &server.url, "batman & robin").unwrap();
+
+    assert_eq!(server.requests(), ["/search?keyword=batman+%26+robin"]);
+    let found: Vec<(String, String)> = results.into_iter().map(|e| (e.title, e.url)).collect();
+    assert_eq!(found, [
+        ("Popular".to_string(), format!("{}/comic/popular", server.url)),
+        ("Batman (2016)".to_string(), format!("{}/comic/batman-2016", server.url)),
+        ("Batman Beyond".to_string(), format!("{}/comic/batman-beyond", server.url)),
+    ]);
+}
+
+#[test]
+fn scanita_results_are_the_links_to_the_manga() {
+    let page = r#"<a href="/manga/one-piece"><h3>One Piece</h3></a><a href="/manga/one-piece/1">Capitolo 1</a>"#;
+    let server = TestServer::start(vec![Reply::ok(page)]);
+
+    let results = ScanitaOrgStrategy.search_at(&HttpClient::new(), &server.url, "one piece").unwrap();
+
+    assert_eq!(server.requests(), ["/search?q=one+piece"]);
+    assert_eq!(results, [SearchResult {
+        site: "scanita.org".to_string(),
+        title: "One Piece".to_string(),
+        url: format!("{}/manga/one-piece", server.url),
+    }]);
+}
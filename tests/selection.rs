@@ -0,0 +1,106 @@
+#![allow(clippy::needless_return)]
+mod common;
+
+use std::fs;
+
+use comic_dl::{
+    selection::{self, Selection},
+    state::DownloadState,
+    ComicUrl,
+    Issue,
+};
+use common::fixtures::FixtureServer;
+
+fn issues(names: &[&str]) -> Vec<Issue> {
+    return names.iter()
+        .map(|name| Issue { name: name.to_string(), link: format!("https://readcomic.me/batman/{name}") })
+        .collect();
+}
+
+fn selected(expressions: &[&str], names: &[&str], listed: &[&str]) -> Vec<String> {
+    let selections: Vec<Selection> = expressions.iter().map(|e| e.parse().unwrap()).collect();
+    let listed: Vec<String> = issues(listed).into_iter().map(|e| e.link).collect();
+    return selection::select(&selections, issues(names), &listed)
+        .into_iter()
+        .map(|e| e.name)
+        .collect();
+}
+
+const NAMES: [&str; 6] = ["Issue-1", "Issue-2", "Issue-3", "Annual-1", "Issue-10", "Issue-10.5"];
+
+#[test]
+fn ranges_and_lists_use_the_number_in_the_name() {
+    assert_eq!(selected(&["2-3"], &NAMES, &[]), ["Issue-2", "Issue-3"]);
+    assert_eq!(selected(&["10-"], &NAMES, &[]), ["Issue-10", "Issue-10.5"]);
+    assert_eq!(selected(&["-1"], &NAMES, &[]), ["Issue-1", "Annual-1"]);
+    assert_eq!(selected(&["1, 3,10.5"], &NAMES, &[]), ["Issue-1", "Issue-3", "Annual-1", "Issue-10.5"]);
+}
+
+#[test]
+fn patterns_match_the_name() {
+    assert_eq!(selected(&["/^annual/i"], &NAMES, &[]), ["Annual-1"]);
+    assert!(selected(&["/^annual/"], &NAMES, &[]).is_empty());
+    // the comma of the regex doesn't split the terms
+    assert_eq!(selected(&["/Issue-1\\d{0,1}$/,3"], &NAMES, &[]), ["Issue-1", "Issue-3", "Issue-10"]);
+}
+
+#[test]
+fn latest_counts_from_the_end_of_the_list() {
+    assert_eq!(selected(&["latest:2"], &NAMES, &[]), ["Issue-10", "Issue-10.5"]);
+    assert_eq!(selected(&["latest:100"], &NAMES, &[]).len(), NAMES.len());
+    assert!(selected(&["latest:0"], &NAMES, &[]).is_empty());
+}
+
+#[test]
+fn new_issues_are_the_ones_not_listed_at_the_last_download() {
+    assert_eq!(selected(&["new"], &NAMES, &NAMES[..4]), ["Issue-10", "Issue-10.5"]);
+    // at the first download nothing was listed yet
+    assert!(selected(&["new"], &NAMES, &[]).is_empty());
+}
+
+#[test]
+fn issues_that_failed_stay_new() {
+    let server = FixtureServer::start("readcomic.me");
+    let dir = std::env::temp_dir().join(format!("comic-dl-selection-failed-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let first = server.link("https://readcomic.me/comic/batman-2016/issue-1");
+    let mut state = DownloadState::load(&dir).unwrap();
+    state.listed = vec![first.clone()];
+    state.save(&dir).unwrap();
+    let mut comic = ComicUrl::new("https://readcomic.me/comic/batman-2016").unwrap();
+    comic.url = server.link("https://readcomic.me/comic/batman-2016");
+    comic.change_path(dir.to_str().unwrap()).unwrap();
+    comic.change_selection(vec!["new".parse().unwrap()]);
+
+    let results = comic.download_new(1).unwrap();
+
+    let names: Vec<&str> = results.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["2", "3"]);
+    assert!(results.iter().all(|(_, result)| result.is_err()));
+    assert_eq!(DownloadState::load(&dir).unwrap().listed, [first]);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn repeated_selections_must_all_match() {
+    assert_eq!(selected(&["1-10", "/Issue/"], &NAMES, &[]), ["Issue-1", "Issue-2", "Issue-3", "Issue-10"]);
+    assert_eq!(selected(&["latest:3", "new"], &NAMES, &NAMES[..5]), ["Issue-10.5"]);
+    assert_eq!(selected(&[], &NAMES, &[]).len(), NAMES.len());
+}
+
+#[test]
+fn invalid_expressions_are_errors() {
+    for expression in ["", " , ", "ten", "25-10", "-", "latest:", "latest:x", "/unclosed", "/(/", "/a/x"] {
+        assert!(expression.parse::<Selection>().is_err(), "{expression} should be invalid");
+    }
+}
+
+#[test]
+fn selections_are_saved_as_their_expression() {
+    let selection: Selection = " 10-25,/annual/i ".parse().unwrap();
+    let json = serde_json::to_string(&vec![selection.clone()]).unwrap();
+    assert_eq!(json, r#"["10-25,/annual/i"]"#);
+    assert_eq!(serde_json::from_str::<Vec<Selection>>(&json).unwrap(), vec![selection]);
+    assert!(serde_json::from_str::<Selection>(r#""25-10""#).is_err());
+}
@@ -0,0 +1,98 @@
+#![allow(clippy::needless_return)]
+mod common;
+
+use comic_dl::{
+    pages,
+    sites::{readcomic_me::ReadcomicMeStrategy, zerocalcare_net::ZerocalcareNetStrategy},
+    ComicDownloader,
+    HttpClient,
+    Issue,
+};
+use common::{temp_dir, Reply, TestServer};
+
+const READCOMIC_DETAIL: &str = r#"<html><head>
+<meta property="og:title" content="Batman (2016) Comic Online">
+<meta property="og:image" content="https://readcomic.me/og.jpg">
+<meta property="og:description" content="short description">
+</head><body>
+<article id="item-detail">
+  <h1 class="title-detail">Batman (2016)</h1>
+  <time class="small">[Updated at 2024-05-01]</time>
+  <div class="col-image"><img src="https://readcomic.me/covers/batman.jpg"></div>
+  <ul class="list-info">
+    <li class="author row"><p class="name col-xs-4">Author</p><p class="col-xs-8"><a>Tom King</a> - <a>David Finch</a></p></li>
+    <li class="status row"><p class="name col-xs-4">Status</p><p class="col-xs-8">Completed</p></li>
+    <li class="kind row"><p class="name col-xs-4">Genres</p><p class="col-xs-8"><a>DC Comics</a> - <a>Superhero</a></p></li>
+  </ul>
+  <div class="detail-content"><p>The dark   knight
+  returns.</p></div>
+</article>
+</body></html>"#;
+
+const READCOMIC_ISSUES: &str = r#"<div id="nt_listchapter"><ul>
+<li><a href="https://readcomic.me/comic/batman/issue-2">
+Issue #2
+</a></li>
+<li><a href="https://readcomic.me/comic/batman/issue-1">
+Issue #1
+</a></li>
+</ul></div>"#;
+
+#[test]
+fn readcomic_metadata_comes_from_the_detail_page() {
+    let server = TestServer::start(vec![
+        Reply::ok(READCOMIC_DETAIL),
+        Reply::ok(READCOMIC_ISSUES),
+        Reply::ok("<div id=\"nt_listchapter\"></div>"),
+    ]);
+    let url = format!("{}/comic/batman", server.url);
+
+    let metadata = ReadcomicMeStrategy.get_comic_metadata(&HttpClient::new(), &url).unwrap();
+
+    assert_eq!(metadata.title, "Batman (2016)");
+    assert_eq!(metadata.url, url);
+    assert_eq!(metadata.cover_url.as_deref(), Some("https://readcomic.me/covers/batman.jpg"));
+    assert_eq!(metadata.authors, ["Tom King", "David Finch"]);
+    assert_eq!(metadata.status.as_deref(), Some("Completed"));
+    assert_eq!(metadata.genres, ["DC Comics", "Superhero"]);
+    assert_eq!(metadata.description.as_deref(), Some("The dark knight returns."));
+    assert_eq!(metadata.issue_count, Some(2));
+    assert_eq!(metadata.last_update.as_deref(), Some("Updated at 2024-05-01"));
+    assert_eq!(server.requests(), ["/comic/batman", "/comic/batman?page=1", "/comic/batman?page=2"]);
+}
+
+#[test]
+fn open_graph_tags_are_used_when_there_is_nothing_else() {
+    let page = r#"<html><head>
+<meta property="og:title" content="Kobane Calling">
+<meta property="og:description" content="  reportage  ">
+<meta property="og:image" content="">
+<meta property="article:modified_time" content="2024-06-01T10:00:00+00:00">
+</head><body></body></html>"#;
+    let server = TestServer::start(vec![Reply::ok(page)]);
+    let url = format!("{}/storie-a-fumetti/kobane-calling/", server.url);
+
+    let metadata = ZerocalcareNetStrategy.get_comic_metadata(&HttpClient::new(), &url).unwrap();
+
+    assert_eq!(metadata.title, "Kobane Calling");
+    assert_eq!(metadata.description.as_deref(), Some("reportage"));
+    assert_eq!(metadata.cover_url, None);
+    assert_eq!(metadata.authors, ["Zerocalcare"]);
+    assert_eq!(metadata.last_update.as_deref(), Some("2024-06-01T10:00:00+00:00"));
+    assert_eq!(metadata.issue_count, Some(1));
+}
+
+#[test]
+fn zerocalcare_images_on_other_hosts_are_not_downloaded() {
+    let other = TestServer::start(vec![Reply::ok("image bytes")]);
+    let image = format!("{}/wp-content/uploads/2024/05/1-1.jpg", other.url.replace("127.0.0.1", "localhost"));
+    let server = TestServer::start(vec![Reply::ok(format!("<html><body><img src=\"{image}\"></body></html>"))]);
+    let dir = temp_dir("zerocalcare-other-hosts");
+    let issue = Issue { name: "kobane-calling".to_string(), link: format!("{}/storie-a-fumetti/kobane-calling/", server.url) };
+
+    ZerocalcareNetStrategy.download_issue(&HttpClient::new(), &dir, &issue).unwrap();
+
+    assert!(other.requests().is_empty());
+    assert!(pages::page_files(&dir.join("kobane-calling")).unwrap().is_empty());
+    std::fs::remove_dir_all(dir).unwrap();
+}
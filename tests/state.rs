@@ -0,0 +1,104 @@
+#![allow(clippy::needless_return)]
+mod common;
+
+use std::fs;
+
+use comic_dl::{
+    state::{DownloadState, IssueListing, IssueRecord, IssueStatus, STATE_FILE},
+    ComicUrl,
+    Issue,
+    OutputFormats,
+};
+use common::temp_dir;
+
+fn issue(name: &str) -> Issue {
+    return Issue { name: name.to_string(), link: format!("https://readcomic.me/comic/batman/issue-{name}") };
+}
+
+#[test]
+fn state_survives_a_round_trip() {
+    let dir = temp_dir("round-trip");
+    assert_eq!(DownloadState::load(&dir).unwrap(), DownloadState::default());
+
+    let volume = dir.join("batman-1.pdf");
+    fs::write(&volume, "abc").unwrap();
+    let mut state = DownloadState::default();
+    state.insert(IssueRecord::new(&issue("1"), 12, OutputFormats::Pdf, &volume).unwrap());
+    state.save(&dir).unwrap();
+
+    let loaded = DownloadState::load(&dir).unwrap();
+    assert_eq!(loaded, state);
+    let record = loaded.get(&issue("1")).unwrap();
+    assert_eq!(record.pages, 12);
+    assert_eq!(record.file, "batman-1.pdf");
+    assert_eq!(record.sha256, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    assert!(fs::read_to_string(dir.join(STATE_FILE)).unwrap().contains("\"format\": \"pdf\""));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn issues_are_identified_by_link_not_by_file() {
+    let dir = temp_dir("by-link");
+    let volume = dir.join("batman-1.cbz");
+    fs::write(&volume, "abc").unwrap();
+    let mut state = DownloadState::default();
+    state.insert(IssueRecord::new(&issue("1"), 12, OutputFormats::Cbz, &volume).unwrap());
+    fs::rename(&volume, dir.join("renamed.cbz")).unwrap();
+
+    let renamed = Issue { name: "first".to_string(), link: issue("1").link };
+    assert!(state.is_downloaded(&renamed));
+    assert!(!state.is_downloaded(&issue("2")));
+
+    state.insert(IssueRecord::new(&issue("1"), 13, OutputFormats::Cbz, &dir.join("renamed.cbz")).unwrap());
+    assert_eq!(state.issues.len(), 1);
+    assert_eq!(state.get(&issue("1")).unwrap().pages, 13);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn volumes_of_older_versions_are_imported() {
+    let dir = temp_dir("legacy");
+    fs::write(dir.join("batman-1.cbz"), "old volume").unwrap();
+    let mut comic = ComicUrl::new("https://readcomic.me/comic/batman").unwrap();
+    comic.change_path(dir.to_str().unwrap()).unwrap();
+    comic.change_format(OutputFormats::Pdf);
+
+    assert!(comic.is_downloaded(&issue("1")).unwrap());
+    assert!(!comic.is_downloaded(&issue("2")).unwrap());
+    assert!(DownloadState::load(&dir).unwrap().get(&issue("1")).is_none());
+
+    comic.import_legacy_volumes(&[issue("1"), issue("2")]).unwrap();
+
+    let saved = DownloadState::load(&dir).unwrap();
+    assert_eq!(saved.get(&issue("1")).unwrap().format, OutputFormats::Cbz);
+    assert!(saved.get(&issue("2")).is_none());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn issues_started_but_not_packed_are_partial() {
+    let dir = temp_dir("status");
+    fs::write(dir.join("batman-1.cbz"), "volume").unwrap();
+    fs::create_dir(dir.join("2")).unwrap();
+    let mut comic = ComicUrl::new("https://readcomic.me/comic/batman").unwrap();
+    comic.change_path(dir.to_str().unwrap()).unwrap();
+
+    assert_eq!(comic.issue_status(&issue("1")).unwrap(), IssueStatus::Downloaded);
+    assert_eq!(comic.issue_status(&issue("2")).unwrap(), IssueStatus::Partial);
+    assert_eq!(comic.issue_status(&issue("3")).unwrap(), IssueStatus::Missing);
+
+    let listing = IssueListing { index: 2, name: "2".to_string(), link: issue("2").link, status: IssueStatus::Partial };
+    assert_eq!(
+        serde_json::to_string(&listing).unwrap(),
+        r#"{"index":2,"name":"2","link":"https://readcomic.me/comic/batman/issue-2","status":"partial"}"#,
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn a_broken_state_is_an_error() {
+    let dir = temp_dir("broken");
+    fs::write(dir.join(STATE_FILE), "{ not json").unwrap();
+    assert!(DownloadState::load(&dir).is_err());
+    fs::remove_dir_all(dir).unwrap();
+}